@@ -0,0 +1,111 @@
+// how fast the literal searches go compared to the simple line by line ones, and the Searcher the
+// command line uses, which skips the lines a literal can't be in a buffer at a time
+// `cargo bench`, or `cargo bench -- 64` for a 64MB haystack instead of the default 16MB
+// The haystack is the poem over and over with some numbered log lines mixed in, so both
+// common words and rare ones get measured.
+
+use std::env;
+use std::hint::black_box;
+use std::io::{self, BufReader};
+use std::path::Path;
+use std::time::{Duration, Instant};
+
+use minigrep::{
+    search, search_case_insensitive, search_literal, search_literal_case_insensitive, search_with,
+    CaseMode, Match, Matcher, Searcher, SearcherBuilder, Sink,
+};
+
+// counts the matching lines, like the other searches return them
+struct Count(usize);
+
+impl Sink for Count {
+    fn matched(&mut self, _m: &Match) -> io::Result<()> {
+        self.0 += 1;
+        Ok(())
+    }
+}
+
+const POEM: &str = include_str!("../poem.txt");
+
+fn main() {
+    let megabytes = env::args()
+        .skip(1)
+        .find_map(|arg| arg.parse::<usize>().ok())
+        .unwrap_or(16);
+    let haystack = haystack(megabytes << 20);
+
+    // a word in every stanza, one that's rare, one that's nowhere and one made of very common letters
+    for query in ["nobody", "bog", "ERROR 4242", "zebra", "e t"] {
+        println!("{query:?}");
+        let sensitive = Matcher::literal(query, false);
+        let insensitive = Matcher::literal(query, true);
+        bench("search", &haystack, || search(query, &haystack).len());
+        bench("search_literal", &haystack, || {
+            search_literal(query, &haystack).len()
+        });
+        bench("Matcher::literal", &haystack, || {
+            search_with(&sensitive, &haystack).len()
+        });
+        bench("search_case_insensitive", &haystack, || {
+            search_case_insensitive(query, &haystack).len()
+        });
+        bench("search_literal_case_insensitive", &haystack, || {
+            search_literal_case_insensitive(query, &haystack).len()
+        });
+        bench("Matcher::literal -i", &haystack, || {
+            search_with(&insensitive, &haystack).len()
+        });
+        for case in [CaseMode::Sensitive, CaseMode::Insensitive] {
+            let searcher = SearcherBuilder::new()
+                .pattern(query)
+                .case(case)
+                .build()
+                .unwrap();
+            let name = format!("Searcher {case:?}");
+            bench(&name, &haystack, || count(&searcher, &haystack));
+        }
+    }
+}
+
+// read through a BufReader the way a file is, a buffer at a time
+fn count(searcher: &Searcher, haystack: &str) -> usize {
+    let mut sink = Count(0);
+    let reader = BufReader::new(haystack.as_bytes());
+    searcher
+        .search_reader(Path::new("haystack"), reader, &mut sink)
+        .unwrap();
+    sink.0
+}
+
+fn haystack(size: usize) -> String {
+    let mut haystack = String::with_capacity(size + POEM.len());
+    let mut n = 0u32;
+    while haystack.len() < size {
+        haystack.push_str(POEM);
+        n = n.wrapping_mul(1103515245).wrapping_add(12345);
+        let level = ["INFO", "WARN", "ERROR"][(n >> 16) as usize % 3];
+        haystack.push_str(&format!(
+            "{level} {} request took {}ms\n",
+            n % 10000,
+            n % 997
+        ));
+    }
+    haystack
+}
+
+// runs search for at least half a second and prints the best time of one run, as MB/s
+fn bench(name: &str, haystack: &str, mut search: impl FnMut() -> usize) {
+    let mut best = Duration::MAX;
+    let mut lines = 0;
+    let started = Instant::now();
+    while started.elapsed() < Duration::from_millis(500) {
+        let start = Instant::now();
+        lines = black_box(search());
+        best = best.min(start.elapsed());
+    }
+    let speed = haystack.len() as f64 / best.as_secs_f64() / (1 << 20) as f64;
+    println!(
+        "  {name:<32} {:>9.2?} {speed:>8.0} MB/s {lines:>8} lines",
+        best
+    );
+}
@@ -0,0 +1,303 @@
+// finds any of many literal patterns in one pass over the text, so `-f blocklist.txt` with thousands of
+// lines costs about the same as searching for one pattern
+// all the patterns go into one trie. Every node also gets a failure link to the longest proper suffix of
+// its path that is also in the trie, so when the next byte doesn't fit we fall back along the links
+// instead of going back in the text. Every byte of the text is looked at exactly once.
+// Following failure links is slow though, so as long as it fits in memory the trie is turned into a table
+// that says straight away where every state goes on every byte (a DFA). Bytes that don't appear in any
+// pattern all behave the same, so they share one column of the table.
+
+use std::collections::VecDeque;
+use std::ops::Range;
+
+const ROOT: usize = 0;
+// the most entries the DFA table can have before we stick with failure links, 16M entries is 64MB
+const MAX_DFA: usize = 1 << 24;
+
+#[derive(Debug, Clone, Default)]
+struct State {
+    // sorted by byte, most nodes only have one or two children so this is a lot smaller than a table
+    next: Vec<(u8, usize)>,
+    fail: usize,
+    // the pattern that ends exactly here, the lowest index wins when a pattern is given twice
+    pattern: Option<usize>,
+    // the closest state along the failure links that has a pattern, so finding every pattern that ends
+    // at a position doesn't have to walk links that lead nowhere
+    dict: Option<usize>,
+    depth: usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct AhoCorasick {
+    states: Vec<State>,
+    // the root is where most searches spend their time, so it gets a full table
+    root: [usize; 256],
+    // which column of the DFA each byte uses
+    classes: [u8; 256],
+    columns: usize,
+    // state * columns + class is the next state, empty when the table would have been too big
+    dfa: Vec<u32>,
+    // whether any pattern ends in this state (itself or through its dict link), checked for every byte
+    ends: Vec<bool>,
+    // the length of the longest pattern, no match can be longer than this
+    longest: usize,
+    // an empty pattern matches at every position
+    empty: Option<usize>,
+}
+
+impl AhoCorasick {
+    pub fn new<S: AsRef<[u8]>>(patterns: &[S]) -> AhoCorasick {
+        let mut states = vec![State::default()];
+        let mut empty = None;
+        let mut longest = 0;
+
+        for (index, pattern) in patterns.iter().enumerate() {
+            let pattern = pattern.as_ref();
+            longest = longest.max(pattern.len());
+            let mut state = ROOT;
+            for &byte in pattern {
+                state = match child(&states[state], byte) {
+                    Some(next) => next,
+                    None => {
+                        let next = states.len();
+                        let depth = states[state].depth + 1;
+                        states.push(State {
+                            depth,
+                            ..State::default()
+                        });
+                        let children = &mut states[state].next;
+                        let at = children.partition_point(|&(b, _)| b < byte);
+                        children.insert(at, (byte, next));
+                        next
+                    }
+                };
+            }
+            if state == ROOT {
+                empty = empty.or(Some(index));
+            } else {
+                states[state].pattern = states[state].pattern.or(Some(index));
+            }
+        }
+
+        // failure links are filled in breadth first, a node's link only depends on shallower nodes
+        let mut queue: VecDeque<usize> = states[ROOT].next.iter().map(|&(_, s)| s).collect();
+        while let Some(state) = queue.pop_front() {
+            for i in 0..states[state].next.len() {
+                let (byte, next) = states[state].next[i];
+                let mut fail = states[state].fail;
+                let link = loop {
+                    if let Some(target) = child(&states[fail], byte) {
+                        break target;
+                    }
+                    if fail == ROOT {
+                        break ROOT;
+                    }
+                    fail = states[fail].fail;
+                };
+                states[next].fail = link;
+                states[next].dict = if states[link].pattern.is_some() {
+                    Some(link)
+                } else {
+                    states[link].dict
+                };
+                queue.push_back(next);
+            }
+        }
+
+        let mut root = [ROOT; 256];
+        for &(byte, next) in &states[ROOT].next {
+            root[byte as usize] = next;
+        }
+
+        let ends = states
+            .iter()
+            .map(|state| state.pattern.is_some() || state.dict.is_some())
+            .collect();
+        let mut automaton = AhoCorasick {
+            states,
+            root,
+            classes: [0; 256],
+            columns: 1,
+            dfa: Vec::new(),
+            ends,
+            longest,
+            empty,
+        };
+        automaton.build_dfa(patterns);
+        automaton
+    }
+
+    fn build_dfa<S: AsRef<[u8]>>(&mut self, patterns: &[S]) {
+        // every byte used in a pattern gets its own column, everything else shares column 0
+        let mut representative = vec![0u8];
+        for pattern in patterns {
+            for &byte in pattern.as_ref() {
+                if self.classes[byte as usize] == 0 && representative.len() < 256 {
+                    self.classes[byte as usize] = representative.len() as u8;
+                    representative.push(byte);
+                }
+            }
+        }
+        // with all 256 byte values in use one of them has to share column 0, so leave it to the links
+        let size = self.states.len() * representative.len();
+        if representative.len() == 256 || size > MAX_DFA || self.states.len() > u32::MAX as usize {
+            self.classes = [0; 256];
+            return;
+        }
+
+        self.columns = representative.len();
+        self.dfa = vec![0; size];
+        // breadth first again, so the state a failure link points at is always filled in already
+        let mut queue = VecDeque::from([ROOT]);
+        while let Some(state) = queue.pop_front() {
+            for (class, &byte) in representative.iter().enumerate() {
+                let next = match child(&self.states[state], byte) {
+                    Some(next) if class > 0 => {
+                        queue.push_back(next);
+                        next as u32
+                    }
+                    _ if state == ROOT => ROOT as u32,
+                    _ => self.dfa[self.states[state].fail * self.columns + class],
+                };
+                self.dfa[state * self.columns + class] = next;
+            }
+        }
+    }
+
+    // the leftmost match starting at or after start that accept agrees with, and which pattern it was
+    // when several patterns match at the same place the longest one wins, like grep -F. accept gets the
+    // range and the pattern index, it's how the caller rules out matches that aren't whole words.
+    pub fn find_at(
+        &self,
+        haystack: &[u8],
+        start: usize,
+        mut accept: impl FnMut(&Range<usize>, usize) -> bool,
+    ) -> Option<(Range<usize>, usize)> {
+        let mut best: Option<(Range<usize>, usize)> = None;
+        let mut consider = |range: Range<usize>, pattern: usize, best: &mut Option<_>| {
+            let better = match best {
+                None => true,
+                Some((current, _)) => {
+                    let current: &Range<usize> = current;
+                    range.start < current.start
+                        || (range.start == current.start && range.end > current.end)
+                }
+            };
+            if better && accept(&range, pattern) {
+                *best = Some((range, pattern));
+            }
+        };
+
+        let mut state = ROOT;
+        for pos in start..=haystack.len() {
+            if let Some(empty) = self.empty {
+                consider(pos..pos, empty, &mut best);
+            }
+            // nothing that ends later can start early enough to beat what we have
+            if best
+                .as_ref()
+                .is_some_and(|(found, _)| pos + 1 > found.start + self.longest)
+            {
+                break;
+            }
+            let Some(&byte) = haystack.get(pos) else {
+                break;
+            };
+            state = self.step(state, byte);
+            if !self.ends[state] {
+                continue;
+            }
+
+            // every pattern that ends right after this byte, longest first
+            let end = pos + 1;
+            let mut matched = Some(state).filter(|&s| self.states[s].pattern.is_some());
+            matched = matched.or(self.states[state].dict);
+            while let Some(s) = matched {
+                let found = &self.states[s];
+                if let Some(pattern) = found.pattern {
+                    consider(end - found.depth..end, pattern, &mut best);
+                }
+                matched = found.dict;
+            }
+        }
+        best
+    }
+
+    fn step(&self, mut state: usize, byte: u8) -> usize {
+        if !self.dfa.is_empty() {
+            let class = self.classes[byte as usize] as usize;
+            return self.dfa[state * self.columns + class] as usize;
+        }
+        loop {
+            if state == ROOT {
+                return self.root[byte as usize];
+            }
+            if let Some(next) = child(&self.states[state], byte) {
+                return next;
+            }
+            state = self.states[state].fail;
+        }
+    }
+}
+
+fn child(state: &State, byte: u8) -> Option<usize> {
+    state
+        .next
+        .binary_search_by_key(&byte, |&(b, _)| b)
+        .ok()
+        .map(|i| state.next[i].1)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(patterns: &[&str], text: &str) -> Vec<(Range<usize>, usize)> {
+        let ac = AhoCorasick::new(patterns);
+        let mut found = Vec::new();
+        let mut start = 0;
+        while let Some((range, pattern)) = ac.find_at(text.as_bytes(), start, |_, _| true) {
+            start = range.end.max(range.start + 1);
+            found.push((range, pattern));
+        }
+        found
+    }
+
+    #[test]
+    fn finds_every_pattern_in_one_pass() {
+        let patterns = ["he", "she", "his", "hers"];
+        assert_eq!(vec![(1..4, 1), (8..11, 2)], find(&patterns, "ushers, his"));
+        // at the same start the longest pattern wins
+        assert_eq!(vec![(0..4, 3)], find(&patterns, "hers"));
+        assert!(find(&patterns, "nothing").is_empty());
+    }
+
+    #[test]
+    fn leftmost_beats_earlier_ending() {
+        // "bcd" ends first but "abcde" starts further left
+        assert_eq!(vec![(0..5, 0)], find(&["abcde", "bcd"], "abcde"));
+        assert_eq!(vec![(1..4, 1)], find(&["abcdX", "bcd"], "abcde"));
+    }
+
+    #[test]
+    fn accept_can_skip_matches() {
+        let ac = AhoCorasick::new(&["duct", "productive"]);
+        let text = b"productive duct";
+        // the "duct" inside "productive" is turned down, like -w would
+        let found = ac.find_at(text, 0, |range, _| range.start != 0 && range.start != 3);
+        assert_eq!(Some((11..15, 0)), found);
+    }
+
+    #[test]
+    fn falls_back_to_links_without_a_table() {
+        // every byte value in some pattern leaves no column for "anything else"
+        let mut patterns: Vec<Vec<u8>> = (0..=255u8).map(|b| vec![b'#', b]).collect();
+        patterns.push(b"bcd".to_vec());
+        let ac = AhoCorasick::new(&patterns);
+        assert!(ac.dfa.is_empty());
+        let found = ac.find_at(b"abcd #\xff", 0, |_, _| true);
+        assert_eq!(Some((1..4, 256)), found);
+        let found = ac.find_at(b"abcd #\xff", 4, |_, _| true);
+        assert_eq!(Some((5..7, 255)), found);
+    }
+}
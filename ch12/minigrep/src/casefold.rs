@@ -0,0 +1,156 @@
+// unicode case folding, which is what ignoring case really means
+// lowercasing isn't enough: 'ß' uppercases to "SS", so "STRASSE" and "straße" are only equal once both are
+// folded to "strasse", and 'ς', 'σ' and 'Σ' are all the same letter even though 'ς' lowercases to itself.
+// Folding can turn one character into several, so the search functions here only ever hand back byte ranges
+// of the original text, never of the folded copy.
+
+use std::iter;
+use std::ops::Range;
+
+// the full folding of one character, never more than 3 characters long
+#[derive(Debug, Clone)]
+pub struct Folded {
+    chars: [char; 3],
+    len: usize,
+    next: usize,
+}
+
+impl Iterator for Folded {
+    type Item = char;
+
+    fn next(&mut self) -> Option<char> {
+        let c = self.chars[..self.len].get(self.next).copied();
+        self.next += 1;
+        c
+    }
+}
+
+// full case folding: 'ß' and 'ẞ' fold to "ss", 'İ' to "i̇" (an i with a combining dot above), 'ﬁ' to "fi"
+// going through upper case and back catches everything lowercasing alone misses, like 'ſ' and 'ς'. The one
+// exception is the Turkish dotless 'ı', it uppercases to 'I' but is a different letter from 'i' everywhere
+// except in Turkish, so like the unicode tables we leave it alone.
+pub fn fold(c: char) -> Folded {
+    let mut folded = Folded {
+        chars: [c; 3],
+        len: 1,
+        next: 0,
+    };
+    if c.is_ascii() {
+        folded.chars[0] = c.to_ascii_lowercase();
+    } else if c != 'ı' {
+        folded.len = 0;
+        let chars = c
+            .to_lowercase()
+            .flat_map(char::to_uppercase)
+            .flat_map(char::to_lowercase);
+        for c in chars {
+            folded.chars[folded.len] = c;
+            folded.len += 1;
+        }
+    }
+    folded
+}
+
+// simple case folding, one character in and one out, for when a character can't turn into several
+// (the regex engine steps through the text one character at a time). 'ẞ' folds to 'ß' here, not "ss".
+pub fn fold_simple(c: char) -> char {
+    let mut f = fold(c);
+    match (f.next(), f.next()) {
+        (Some(folded), None) => folded,
+        _ => {
+            let mut lower = c.to_lowercase();
+            match (lower.next(), lower.next()) {
+                (Some(lower), None) => lower,
+                _ => c,
+            }
+        }
+    }
+}
+
+pub fn fold_str(s: &str) -> String {
+    s.chars().flat_map(fold).collect()
+}
+
+// the folded text, and for every byte of it where the character it came from starts in text
+// only the first byte of each character's folding gets an offset, so a match in the folded text that begins
+// or ends in the middle of one character's folding (half of the "ss" 'ß' turns into) can be told apart.
+// There's one more entry than there are bytes, for the end of the text.
+pub fn fold_with_offsets(text: &str) -> (String, Vec<Option<usize>>) {
+    let mut folded = String::with_capacity(text.len());
+    let mut offsets = Vec::with_capacity(text.len() + 1);
+    for (i, c) in text.char_indices() {
+        let before = folded.len();
+        folded.extend(fold(c));
+        offsets.push(Some(i));
+        offsets.resize(offsets.len() + folded.len() - before - 1, None);
+    }
+    offsets.push(Some(text.len()));
+    (folded, offsets)
+}
+
+// finds an already folded query in text without folding the whole text, so the range points into the
+// original. A match has to start and end on a character of the original text, "s" doesn't match half of 'ß'.
+pub fn find(text: &str, folded_query: &str, start: usize) -> Option<Range<usize>> {
+    let starts = text[start..]
+        .char_indices()
+        .map(|(i, _)| start + i)
+        .chain(iter::once(text.len()));
+    for begin in starts {
+        if let Some(len) = folded_prefix_len(&text[begin..], folded_query) {
+            return Some(begin..begin + len);
+        }
+    }
+    None
+}
+
+// if text folded starts with folded_query, how many bytes of text that took
+fn folded_prefix_len(text: &str, folded_query: &str) -> Option<usize> {
+    let mut want = folded_query.chars().peekable();
+    for (i, c) in text.char_indices() {
+        if want.peek().is_none() {
+            return Some(i);
+        }
+        for folded in fold(c) {
+            if want.next() != Some(folded) {
+                return None;
+            }
+        }
+    }
+    if want.peek().is_none() {
+        Some(text.len())
+    } else {
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn folds_beyond_lowercase() {
+        assert_eq!("strasse", fold_str("Straße"));
+        assert_eq!("strasse", fold_str("STRAẞE"));
+        assert_eq!(fold_str("ΣΊΣΥΦΟΣ"), fold_str("σίσυφος"));
+        assert_eq!("i\u{307}stanbul", fold_str("İSTANBUL"));
+        assert_eq!("ı", fold_str("ı"));
+        assert_eq!('k', fold_simple('\u{212A}')); // the kelvin sign
+        assert_eq!('ß', fold_simple('ẞ'));
+        assert_eq!('s', fold_simple('ſ'));
+    }
+
+    #[test]
+    fn ranges_point_into_the_original() {
+        let text = "Die Straße, DIE STRASSE";
+        let query = fold_str("strasse");
+        assert_eq!(Some(4..11), find(text, &query, 0));
+        assert_eq!(Some(17..24), find(text, &query, 11));
+        let (folded, offsets) = fold_with_offsets("aß");
+        assert_eq!("ass", folded);
+        assert_eq!(vec![Some(0), Some(1), None, Some(3)], offsets);
+
+        // half of a folded character is not a match
+        assert_eq!(None, find("ß", "s", 0));
+        assert_eq!(Some(0..2), find("ß", "ss", 0));
+    }
+}
@@ -0,0 +1,483 @@
+// the command line parser
+// it understands short flags (-n), several short flags at once (-in), short flags with a value (-A 3 or -A3),
+// long flags (--count), long flags with a value (--context 3 or --context=3) and `--` to stop looking for
+// options, so a pattern can start with a dash. It only splits the arguments up, Config::build decides what
+// each option means.
+
+// one option minigrep understands, this table is also where --help gets its text from
+pub struct Opt {
+    pub short: Option<char>,
+    pub long: &'static str,
+    // name of the value shown in --help, None for options that are just switched on
+    pub value: Option<&'static str>,
+    pub help: &'static str,
+}
+
+pub const OPTIONS: &[Opt] = &[
+    Opt {
+        short: Some('i'),
+        long: "ignore-case",
+        value: None,
+        help: "Ignore case when matching, overrides IGNORE_CASE",
+    },
+    Opt {
+        short: Some('s'),
+        long: "case-sensitive",
+        value: None,
+        help: "Match case exactly, overrides IGNORE_CASE",
+    },
+    Opt {
+        short: Some('S'),
+        long: "smart-case",
+        value: None,
+        help: "Ignore case unless PATTERN has an upper case letter",
+    },
+    Opt {
+        short: Some('e'),
+        long: "regexp",
+        value: Some("PATTERN"),
+        help: "Search for PATTERN, can be given more than once",
+    },
+    Opt {
+        short: Some('f'),
+        long: "file",
+        value: Some("FILE"),
+        help: "Search for every pattern in FILE, one per line",
+    },
+    Opt {
+        short: None,
+        long: "regex",
+        value: None,
+        help: "Treat PATTERN as a regular expression",
+    },
+    Opt {
+        short: None,
+        long: "fuzzy",
+        value: Some("K"),
+        help: "Also match PATTERN with up to K typos, prints how many",
+    },
+    Opt {
+        short: None,
+        long: "query",
+        value: Some("EXPR"),
+        help: "Select lines by an expression like 'a AND (b OR NOT c)'",
+    },
+    Opt {
+        short: Some('w'),
+        long: "word-regexp",
+        value: None,
+        help: "Only match whole words",
+    },
+    Opt {
+        short: Some('x'),
+        long: "line-regexp",
+        value: None,
+        help: "Only match whole lines",
+    },
+    Opt {
+        short: Some('U'),
+        long: "multiline",
+        value: None,
+        help: "Let matches span lines, \\n is a newline in plain text patterns",
+    },
+    Opt {
+        short: Some('v'),
+        long: "invert-match",
+        value: None,
+        help: "Print the lines that don't match",
+    },
+    Opt {
+        short: None,
+        long: "replace",
+        value: Some("TEXT"),
+        help: "Print TEXT instead of each match, $1 or ${name} for groups",
+    },
+    Opt {
+        short: None,
+        long: "in-place",
+        value: None,
+        help: "Write the replacements back to the files",
+    },
+    Opt {
+        short: None,
+        long: "dry-run",
+        value: None,
+        help: "Print what --in-place would change as a diff",
+    },
+    Opt {
+        short: Some('n'),
+        long: "line-number",
+        value: None,
+        help: "Print the line number of each line",
+    },
+    Opt {
+        short: Some('b'),
+        long: "byte-offset",
+        value: None,
+        help: "Print the byte offset of each line",
+    },
+    Opt {
+        short: None,
+        long: "column",
+        value: None,
+        help: "Print the column of the first match, implies -n",
+    },
+    Opt {
+        short: Some('A'),
+        long: "after-context",
+        value: Some("NUM"),
+        help: "Print NUM lines after each match",
+    },
+    Opt {
+        short: Some('B'),
+        long: "before-context",
+        value: Some("NUM"),
+        help: "Print NUM lines before each match",
+    },
+    Opt {
+        short: Some('C'),
+        long: "context",
+        value: Some("NUM"),
+        help: "Print NUM lines before and after each match",
+    },
+    Opt {
+        short: Some('o'),
+        long: "only-matching",
+        value: None,
+        help: "Print only the matched parts of each line",
+    },
+    Opt {
+        short: None,
+        long: "show-pattern",
+        value: None,
+        help: "Print which pattern matched, useful with -e and -f",
+    },
+    Opt {
+        short: Some('c'),
+        long: "count",
+        value: None,
+        help: "Print how many lines matched in each file",
+    },
+    Opt {
+        short: Some('l'),
+        long: "files-with-matches",
+        value: None,
+        help: "Print only the names of files with a match",
+    },
+    Opt {
+        short: Some('L'),
+        long: "files-without-match",
+        value: None,
+        help: "Print only the names of files without a match",
+    },
+    Opt {
+        short: None,
+        long: "json",
+        value: None,
+        help: "Print the results as JSON Lines",
+    },
+    Opt {
+        short: None,
+        long: "binary-files",
+        value: Some("TYPE"),
+        help: "Binary files: binary, without-match or text (default: binary)",
+    },
+    Opt {
+        short: Some('a'),
+        long: "text",
+        value: None,
+        help: "Search binary files like text, same as --binary-files=text",
+    },
+    Opt {
+        short: Some('I'),
+        long: "skip-binary",
+        value: None,
+        help: "Skip binary files, same as --binary-files=without-match",
+    },
+    Opt {
+        short: Some('z'),
+        long: "search-zip",
+        value: None,
+        help: "Search inside gzip compressed files",
+    },
+    Opt {
+        short: Some('H'),
+        long: "with-filename",
+        value: None,
+        help: "Start every line with its file name",
+    },
+    Opt {
+        short: None,
+        long: "no-filename",
+        value: None,
+        help: "Never print file names in front of lines",
+    },
+    Opt {
+        short: None,
+        long: "color",
+        value: Some("WHEN"),
+        help: "Highlight matches: auto, always or never (default: auto)",
+    },
+    Opt {
+        short: None,
+        long: "follow",
+        value: None,
+        help: "Follow symlinks while searching directories",
+    },
+    Opt {
+        short: None,
+        long: "no-follow",
+        value: None,
+        help: "Skip symlinks while searching directories (default)",
+    },
+    Opt {
+        short: None,
+        long: "max-depth",
+        value: Some("NUM"),
+        help: "Descend at most NUM directories deep",
+    },
+    Opt {
+        short: None,
+        long: "hidden",
+        value: None,
+        help: "Search hidden files and directories too",
+    },
+    Opt {
+        short: None,
+        long: "no-ignore",
+        value: None,
+        help: "Don't skip what .gitignore, .ignore and .git/info/exclude list",
+    },
+    Opt {
+        short: Some('j'),
+        long: "threads",
+        value: Some("NUM"),
+        help: "Search NUM files at a time (default: one per CPU)",
+    },
+    Opt {
+        short: None,
+        long: "help",
+        value: None,
+        help: "Print this help and exit",
+    },
+    Opt {
+        short: Some('V'),
+        long: "version",
+        value: None,
+        help: "Print the version and exit",
+    },
+];
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Arg {
+    // an option by its long name, with its value if it takes one
+    Option(&'static str, Option<String>),
+    Positional(String),
+}
+
+// args should not include the program name
+pub fn parse(args: &[String]) -> Result<Vec<Arg>, String> {
+    let mut parsed = Vec::new();
+    let mut args = args.iter();
+
+    while let Some(arg) = args.next() {
+        if arg == "--" {
+            parsed.extend(args.by_ref().map(|arg| Arg::Positional(arg.clone())));
+            break;
+        }
+
+        if let Some(long) = arg.strip_prefix("--") {
+            let (name, inline) = match long.split_once('=') {
+                Some((name, value)) => (name, Some(value.to_string())),
+                None => (long, None),
+            };
+            let opt = OPTIONS
+                .iter()
+                .find(|opt| opt.long == name)
+                .ok_or_else(|| format!("unrecognized option '--{name}'"))?;
+            let value = match (opt.value, inline) {
+                (None, Some(_)) => return Err(format!("option '--{name}' doesn't take a value")),
+                (None, None) => None,
+                (Some(_), Some(value)) => Some(value),
+                (Some(_), None) => Some(
+                    args.next()
+                        .cloned()
+                        .ok_or_else(|| format!("option '--{name}' needs a value"))?,
+                ),
+            };
+            parsed.push(Arg::Option(opt.long, value));
+            continue;
+        }
+
+        // a lone "-" is a file name (standard input, for commands that support it), not an option
+        let shorts = match arg.strip_prefix('-') {
+            Some(shorts) if !shorts.is_empty() => shorts,
+            _ => {
+                parsed.push(Arg::Positional(arg.clone()));
+                continue;
+            }
+        };
+
+        for (i, c) in shorts.char_indices() {
+            let opt = OPTIONS
+                .iter()
+                .find(|opt| opt.short == Some(c))
+                .ok_or_else(|| {
+                    if shorts.len() == c.len_utf8() {
+                        format!("unrecognized option '-{c}'")
+                    } else {
+                        format!("unrecognized option '-{c}' in '{arg}'")
+                    }
+                })?;
+            if opt.value.is_none() {
+                parsed.push(Arg::Option(opt.long, None));
+                continue;
+            }
+            // whatever follows a short option that takes a value is the value, -A3 is the same as -A 3
+            let rest = &shorts[i + c.len_utf8()..];
+            let value = if rest.is_empty() {
+                args.next()
+                    .cloned()
+                    .ok_or_else(|| format!("option '-{c}' needs a value"))?
+            } else {
+                rest.to_string()
+            };
+            parsed.push(Arg::Option(opt.long, Some(value)));
+            break;
+        }
+    }
+
+    Ok(parsed)
+}
+
+// for options like --context that need a whole number
+pub fn number(name: &str, value: Option<String>) -> Result<usize, String> {
+    let value = value.unwrap_or_default();
+    value
+        .parse()
+        .map_err(|_| format!("invalid value '{value}' for '--{name}': expected a number"))
+}
+
+pub fn help() -> String {
+    let mut help = String::from(
+        "\
+Usage: minigrep [OPTIONS] PATTERN [FILE]...
+       minigrep [OPTIONS] -e PATTERN... [FILE]...
+       minigrep [OPTIONS] -f PATTERN_FILE [FILE]...
+       minigrep [OPTIONS] --query EXPR [FILE]...
+       minigrep index [OPTIONS] DIR
+       minigrep query [OPTIONS] PATTERN DIR
+
+Search each FILE for lines containing PATTERN, or any of the patterns given with -e and -f.
+--query combines words and \"quoted phrases\" with AND, OR, NOT and parentheses, i:word ignores case.
+If FILE is a directory every file under it is searched, except hidden ones and what ignore files list.
+FILE can also be a glob like 'logs/**/*.txt', minigrep expands it itself.
+With no FILE, or when FILE is -, standard input is searched.
+Bytes that aren't UTF-8 are shown as \u{FFFD}, files with a NUL byte in them count as binary.
+`minigrep index` saves an index of DIR's files in DIR/.minigrep-index, running it again updates it.
+`minigrep query` searches DIR like `minigrep PATTERN DIR`, but only reads the files the index says could match.
+They're only subcommands in front of one DIR: `minigrep index notes.txt` searches notes.txt for \"index\".
+Use `minigrep -e index DIR` to search a directory for the word itself.
+Set the IGNORE_CASE environment variable to ignore case by default.
+Colors can be changed with MINIGREP_COLORS, e.g. MINIGREP_COLORS='mt=01;32:fn=34:ln=33'.
+
+Options:
+",
+    );
+    for opt in OPTIONS {
+        let short = match opt.short {
+            Some(c) => format!("-{c}, "),
+            None => String::from("    "),
+        };
+        let long = match opt.value {
+            Some(value) => format!("--{} {value}", opt.long),
+            None => format!("--{}", opt.long),
+        };
+        help.push_str(&format!("  {short}{long:<24}{}\n", opt.help));
+    }
+    help
+}
+
+pub fn version() -> String {
+    format!("minigrep {}", env!("CARGO_PKG_VERSION"))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn short_and_long_options() {
+        let parsed = parse(&args(&[
+            "-in",
+            "--context=2",
+            "-A3",
+            "--max-depth",
+            "4",
+            "to",
+            "poem.txt",
+        ]))
+        .unwrap();
+        assert_eq!(
+            vec![
+                Arg::Option("ignore-case", None),
+                Arg::Option("line-number", None),
+                Arg::Option("context", Some("2".to_string())),
+                Arg::Option("after-context", Some("3".to_string())),
+                Arg::Option("max-depth", Some("4".to_string())),
+                Arg::Positional("to".to_string()),
+                Arg::Positional("poem.txt".to_string()),
+            ],
+            parsed
+        );
+    }
+
+    #[test]
+    fn double_dash_ends_options() {
+        let parsed = parse(&args(&["-c", "--", "-n", "-"])).unwrap();
+        assert_eq!(
+            vec![
+                Arg::Option("count", None),
+                Arg::Positional("-n".to_string()),
+                Arg::Positional("-".to_string()),
+            ],
+            parsed
+        );
+    }
+
+    #[test]
+    fn errors_name_the_argument() {
+        assert_eq!(
+            Err("unrecognized option '--colour'".to_string()),
+            parse(&args(&["--colour"]))
+        );
+        assert_eq!(
+            Err("unrecognized option '-y' in '-nyc'".to_string()),
+            parse(&args(&["-nyc"]))
+        );
+        assert_eq!(
+            Err("option '-A' needs a value".to_string()),
+            parse(&args(&["-A"]))
+        );
+        assert_eq!(
+            Err("option '--count' doesn't take a value".to_string()),
+            parse(&args(&["--count=3"]))
+        );
+        assert_eq!(
+            Err("invalid value 'lots' for '--context': expected a number".to_string()),
+            number("context", Some("lots".to_string()))
+        );
+    }
+
+    #[test]
+    fn help_lists_every_option() {
+        let help = help();
+        for opt in OPTIONS {
+            assert!(help.contains(&format!("--{}", opt.long)));
+        }
+    }
+}
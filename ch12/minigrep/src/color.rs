@@ -0,0 +1,200 @@
+// --color, highlighting matches, file names, line numbers and separators with ANSI escape codes
+// the colors can be changed with MINIGREP_COLORS, which works like GREP_COLORS: a `:` separated list of
+// `key=SGR` pairs, e.g. `mt=01;32:fn=34`. The keys are mt (matched text), fn (file name), ln (line number),
+// cn (column), bn (byte offset) and se (separators). An empty value turns that color off.
+
+use std::env;
+use std::fmt;
+use std::io::{self, IsTerminal};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum ColorChoice {
+    // only when stdout is a terminal and NO_COLOR isn't set
+    #[default]
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    pub fn parse(value: &str) -> Result<ColorChoice, String> {
+        match value {
+            "auto" => Ok(ColorChoice::Auto),
+            "always" => Ok(ColorChoice::Always),
+            "never" => Ok(ColorChoice::Never),
+            _ => Err(format!(
+                "invalid value '{value}' for '--color': expected auto, always or never"
+            )),
+        }
+    }
+
+    pub fn enabled(self) -> bool {
+        match self {
+            ColorChoice::Always => true,
+            ColorChoice::Never => false,
+            // https://no-color.org: any value counts, as long as it isn't empty
+            ColorChoice::Auto => {
+                io::stdout().is_terminal() && env::var_os("NO_COLOR").is_none_or(|v| v.is_empty())
+            }
+        }
+    }
+}
+
+// the numbers of one SGR escape sequence, like the 01;31 in `\x1b[01;31m`
+// kept as a fixed size array so PrintOptions can stay Copy
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Sgr {
+    params: [u8; 8],
+    len: usize,
+}
+
+impl Sgr {
+    pub fn parse(value: &str) -> Option<Sgr> {
+        let mut sgr = Sgr::default();
+        if value.is_empty() {
+            return Some(sgr);
+        }
+        for param in value.split(';') {
+            // 255 is the biggest number SGR ever needs, that's the most a 38;5;N color can take
+            *sgr.params.get_mut(sgr.len)? = param.parse().ok()?;
+            sgr.len += 1;
+        }
+        Some(sgr)
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    // `text` wrapped in this color, or left alone when the color is turned off
+    pub fn paint<'a>(&'a self, text: &'a str) -> Painted<'a> {
+        Painted { sgr: self, text }
+    }
+}
+
+pub struct Painted<'a> {
+    sgr: &'a Sgr,
+    text: &'a str,
+}
+
+impl fmt::Display for Painted<'_> {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        if self.sgr.is_empty() || self.text.is_empty() {
+            return f.write_str(self.text);
+        }
+        f.write_str("\x1b[")?;
+        for (i, param) in self.sgr.params[..self.sgr.len].iter().enumerate() {
+            if i > 0 {
+                f.write_str(";")?;
+            }
+            write!(f, "{param}")?;
+        }
+        write!(f, "m{}\x1b[0m", self.text)
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct Colors {
+    pub matched: Sgr,
+    pub path: Sgr,
+    pub line_number: Sgr,
+    pub column: Sgr,
+    pub byte_offset: Sgr,
+    pub separator: Sgr,
+}
+
+impl Default for Colors {
+    // the same colors grep uses
+    fn default() -> Colors {
+        let sgr = |value| Sgr::parse(value).expect("default colors are valid");
+        Colors {
+            matched: sgr("01;31"),
+            path: sgr("35"),
+            line_number: sgr("32"),
+            column: sgr("32"),
+            byte_offset: sgr("32"),
+            separator: sgr("36"),
+        }
+    }
+}
+
+impl Colors {
+    // every color turned off, what the printer uses without --color
+    pub fn plain() -> Colors {
+        Colors {
+            matched: Sgr::default(),
+            path: Sgr::default(),
+            line_number: Sgr::default(),
+            column: Sgr::default(),
+            byte_offset: Sgr::default(),
+            separator: Sgr::default(),
+        }
+    }
+
+    // the defaults with whatever MINIGREP_COLORS changes. Like grep with a broken GREP_COLORS, a bad one
+    // doesn't stop the search, it gets one warning and the default colors are used instead
+    pub fn from_env() -> Colors {
+        let Ok(spec) = env::var("MINIGREP_COLORS") else {
+            return Colors::default();
+        };
+        Colors::parse(&spec).unwrap_or_else(|e| {
+            eprintln!("minigrep: {e}, using the default colors");
+            Colors::default()
+        })
+    }
+
+    pub fn parse(spec: &str) -> Result<Colors, String> {
+        let mut colors = Colors::default();
+        for pair in spec.split(':').filter(|pair| !pair.is_empty()) {
+            let (key, value) = pair.split_once('=').unwrap_or((pair, ""));
+            let color = match key {
+                "mt" => &mut colors.matched,
+                "fn" => &mut colors.path,
+                "ln" => &mut colors.line_number,
+                "cn" => &mut colors.column,
+                "bn" => &mut colors.byte_offset,
+                "se" => &mut colors.separator,
+                // like GREP_COLORS, anything minigrep doesn't know about (sl, cx, ne, ...) is left alone
+                _ => continue,
+            };
+            *color = Sgr::parse(value)
+                .ok_or_else(|| format!("invalid color '{value}' for '{key}' in MINIGREP_COLORS"))?;
+        }
+        Ok(colors)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn colors_from_a_spec() {
+        let colors = Colors::parse("mt=01;32:fn=:se=38;5;208").unwrap();
+        assert_eq!(
+            "\x1b[1;32mduct\x1b[0m",
+            colors.matched.paint("duct").to_string()
+        );
+        assert_eq!("poem.txt", colors.path.paint("poem.txt").to_string());
+        assert_eq!(
+            "\x1b[38;5;208m:\x1b[0m",
+            colors.separator.paint(":").to_string()
+        );
+        // anything not mentioned keeps the default
+        assert_eq!(Colors::default().line_number, colors.line_number);
+
+        // keys minigrep doesn't use are skipped, only a bad value for one it does use is an error
+        let colors = Colors::parse("mt=01;32:zz=1:sl=x:ne").unwrap();
+        assert_eq!(Sgr::parse("01;32"), Some(colors.matched));
+        assert!(Colors::parse("mt=red").is_err());
+        assert!(Colors::parse("mt=1;2;3;4;5;6;7;8;9").is_err());
+    }
+
+    #[test]
+    fn choices() {
+        assert_eq!(Ok(ColorChoice::Always), ColorChoice::parse("always"));
+        assert!(ColorChoice::parse("sometimes").is_err());
+        assert!(ColorChoice::Always.enabled());
+        assert!(!ColorChoice::Never.enabled());
+    }
+}
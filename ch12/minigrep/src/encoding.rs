@@ -0,0 +1,229 @@
+// what a file looks like before it's split into lines
+// most files are UTF-8, or close enough: bytes that aren't valid UTF-8 are shown as � instead of stopping the
+// search, that part happens when lines are read. Files starting with a UTF-16 byte order mark are turned into
+// UTF-8 as they're read, so they can be searched like everything else, and a UTF-8 byte order mark is dropped.
+// A NUL byte is what makes a file binary, like in grep: text never has one, nearly every binary format does.
+
+use std::borrow::Cow;
+use std::io::{self, BufRead, Read};
+
+use crate::literal;
+
+// a reader that gives UTF-8 whatever the file was written in
+pub fn decode<'a, R: BufRead + 'a>(mut reader: R) -> io::Result<Box<dyn BufRead + 'a>> {
+    // a byte order mark that got split over two reads isn't recognised, the first read of a file is
+    // always bigger than that in practice
+    let start = reader.fill_buf()?;
+    let decoded: Box<dyn BufRead + 'a> = match start {
+        [0xEF, 0xBB, 0xBF, ..] => {
+            reader.consume(3);
+            Box::new(reader)
+        }
+        [0xFF, 0xFE, ..] => {
+            reader.consume(2);
+            Box::new(io::BufReader::new(Utf16::new(reader, false)))
+        }
+        [0xFE, 0xFF, ..] => {
+            reader.consume(2);
+            Box::new(io::BufReader::new(Utf16::new(reader, true)))
+        }
+        _ => Box::new(reader),
+    };
+    Ok(decoded)
+}
+
+// the text of bytes with whatever isn't UTF-8 shown as �, like String::from_utf8_lossy. When anything had to be
+// replaced, also where each byte of the text came from in bytes, so a match found in the text can be reported
+// where it really is: every � is 3 bytes long, the bytes it stands for can be fewer or more. There's one more
+// offset than the text has bytes, for its end.
+pub fn lossy(bytes: &[u8]) -> (Cow<'_, str>, Option<Vec<usize>>) {
+    let text = String::from_utf8_lossy(bytes);
+    if let Cow::Borrowed(_) = text {
+        return (text, None);
+    }
+    let mut offsets = Vec::with_capacity(text.len() + 1);
+    let mut raw = 0;
+    for chunk in bytes.utf8_chunks() {
+        offsets.extend(raw..raw + chunk.valid().len());
+        raw += chunk.valid().len();
+        if !chunk.invalid().is_empty() {
+            offsets.extend([raw; char::REPLACEMENT_CHARACTER.len_utf8()]);
+            raw += chunk.invalid().len();
+        }
+    }
+    offsets.push(raw);
+    (text, Some(offsets))
+}
+
+pub fn is_binary(bytes: &[u8]) -> bool {
+    literal::memchr(0, bytes).is_some()
+}
+
+// turns UTF-16 into UTF-8 a buffer at a time, anything that isn't valid UTF-16 becomes �
+pub struct Utf16<R> {
+    inner: R,
+    big_endian: bool,
+    // the first byte of a code unit whose second byte hasn't been read yet
+    odd: Option<u8>,
+    // the first half of a surrogate pair, waiting for the second
+    high: Option<u16>,
+    // UTF-8 that's been decoded but not read yet
+    out: Vec<u8>,
+    read: usize,
+}
+
+impl<R: BufRead> Utf16<R> {
+    pub fn new(inner: R, big_endian: bool) -> Utf16<R> {
+        Utf16 {
+            inner,
+            big_endian,
+            odd: None,
+            high: None,
+            out: Vec::new(),
+            read: 0,
+        }
+    }
+
+    // decodes the next buffer of the file, false at the end of it
+    fn fill(&mut self) -> io::Result<bool> {
+        let Utf16 {
+            inner,
+            big_endian,
+            odd,
+            high,
+            out,
+            read,
+        } = self;
+        out.clear();
+        *read = 0;
+        let input = inner.fill_buf()?;
+        if input.is_empty() {
+            // the file stopped in the middle of a character
+            if odd.take().is_some() || high.take().is_some() {
+                push(out, char::REPLACEMENT_CHARACTER);
+            }
+            return Ok(!out.is_empty());
+        }
+        for &byte in input {
+            let Some(first) = odd.take() else {
+                *odd = Some(byte);
+                continue;
+            };
+            let unit = if *big_endian {
+                u16::from_be_bytes([first, byte])
+            } else {
+                u16::from_le_bytes([first, byte])
+            };
+            decode_unit(unit, high, out);
+        }
+        let consumed = input.len();
+        inner.consume(consumed);
+        Ok(true)
+    }
+}
+
+fn decode_unit(unit: u16, high: &mut Option<u16>, out: &mut Vec<u8>) {
+    if let Some(first) = high.take() {
+        if (0xDC00..0xE000).contains(&unit) {
+            let c = 0x10000 + ((first as u32 - 0xD800) << 10) + (unit as u32 - 0xDC00);
+            push(
+                out,
+                char::from_u32(c).unwrap_or(char::REPLACEMENT_CHARACTER),
+            );
+            return;
+        }
+        // a first half with no second half
+        push(out, char::REPLACEMENT_CHARACTER);
+    }
+    if (0xD800..0xDC00).contains(&unit) {
+        *high = Some(unit);
+    } else {
+        // a second half on its own isn't a char either
+        push(
+            out,
+            char::from_u32(unit as u32).unwrap_or(char::REPLACEMENT_CHARACTER),
+        );
+    }
+}
+
+fn push(out: &mut Vec<u8>, c: char) {
+    let mut utf8 = [0; 4];
+    out.extend_from_slice(c.encode_utf8(&mut utf8).as_bytes());
+}
+
+impl<R: BufRead> Read for Utf16<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        // a buffer of the file can decode to nothing, when it's only half of a character
+        while self.read == self.out.len() {
+            if !self.fill()? {
+                return Ok(0);
+            }
+        }
+        let n = buf.len().min(self.out.len() - self.read);
+        buf[..n].copy_from_slice(&self.out[self.read..self.read + n]);
+        self.read += n;
+        Ok(n)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn decoded(bytes: &[u8]) -> String {
+        let mut text = String::new();
+        decode(bytes).unwrap().read_to_string(&mut text).unwrap();
+        text
+    }
+
+    #[test]
+    fn byte_order_marks() {
+        assert_eq!("Duct tape.", decoded(b"\xEF\xBB\xBFDuct tape."));
+        assert_eq!("Duct tape.", decoded(b"Duct tape."));
+
+        let utf16: Vec<u16> = "Straße 🦀\n".encode_utf16().collect();
+        let le: Vec<u8> = [0xFEFF]
+            .iter()
+            .chain(&utf16)
+            .flat_map(|u| u.to_le_bytes())
+            .collect();
+        let be: Vec<u8> = [0xFEFF]
+            .iter()
+            .chain(&utf16)
+            .flat_map(|u| u.to_be_bytes())
+            .collect();
+        assert_eq!("Straße 🦀\n", decoded(&le));
+        assert_eq!("Straße 🦀\n", decoded(&be));
+    }
+
+    #[test]
+    fn broken_utf16() {
+        // a lone second half, a first half followed by a normal character, and half a code unit at the end
+        assert_eq!("�a�b�", decoded(b"\xFF\xFE\x00\xDCa\x00\x00\xD8b\x00c"));
+        // one byte at a time still puts the pieces back together
+        let crab: Vec<u8> = "🦀".encode_utf16().flat_map(u16::to_le_bytes).collect();
+        let reader = io::BufReader::with_capacity(1, crab.as_slice());
+        let mut text = String::new();
+        Utf16::new(reader, false).read_to_string(&mut text).unwrap();
+        assert_eq!("🦀", text);
+    }
+
+    #[test]
+    fn lossy_offsets() {
+        assert_eq!((Cow::Borrowed("café"), None), lossy("café".as_bytes()));
+        // é in Latin-1 is one byte, its � is three
+        let (text, offsets) = lossy(b"caf\xe9 nobody");
+        assert_eq!("caf\u{FFFD} nobody", text);
+        let offsets = offsets.unwrap();
+        assert_eq!(text.len() + 1, offsets.len());
+        assert_eq!(&[0, 1, 2, 3, 3, 3, 4, 5], &offsets[..8]);
+        assert_eq!(5, offsets[text.find("nobody").unwrap()]);
+        assert_eq!(11, offsets[text.len()]);
+    }
+
+    #[test]
+    fn nul_means_binary() {
+        assert!(is_binary(b"PNG\x00\x00"));
+        assert!(!is_binary(b"Rust:\nsafe, fast, productive."));
+    }
+}
@@ -0,0 +1,147 @@
+// every way minigrep can fail, so main can tell them apart instead of getting a Box<dyn Error>
+
+use std::error::Error;
+use std::fmt;
+use std::io;
+use std::path::PathBuf;
+
+use crate::gzip::GzipError;
+use crate::query::QueryError;
+use crate::regex::RegexError;
+use crate::walk::WalkError;
+
+#[derive(Debug)]
+pub enum MinigrepError {
+    // the command line didn't make sense, the message says which argument was wrong
+    Args(String),
+    // a file or directory couldn't be read
+    Io { path: PathBuf, source: io::Error },
+    // a pattern file given to -f isn't valid UTF-8, files being searched can have any bytes in them
+    Encoding { path: PathBuf },
+    // `minigrep query`, the index file is broken or from another version of minigrep
+    BadIndex { path: PathBuf },
+    // -z, a compressed file is broken or compressed with something minigrep can't unpack
+    Decompress { path: PathBuf, source: GzipError },
+    // the regex didn't compile
+    Pattern(RegexError),
+    // the --query expression didn't parse
+    Query(QueryError),
+    // writing the results failed, usually because whoever was reading stdout went away
+    Output(io::Error),
+}
+
+impl MinigrepError {
+    // picks Decompress or Encoding over Io when reading failed because of what was in the file
+    pub fn reading(path: impl Into<PathBuf>, source: io::Error) -> MinigrepError {
+        let path = path.into();
+        if let Some(e) = source.get_ref().and_then(|e| e.downcast_ref::<GzipError>()) {
+            MinigrepError::Decompress {
+                path,
+                source: e.clone(),
+            }
+        } else if source.kind() == io::ErrorKind::InvalidData {
+            MinigrepError::Encoding { path }
+        } else {
+            MinigrepError::Io { path, source }
+        }
+    }
+
+    // `minigrep ... | head` closes the pipe early, that's not worth complaining about
+    pub fn is_broken_pipe(&self) -> bool {
+        matches!(self, MinigrepError::Output(e) if e.kind() == io::ErrorKind::BrokenPipe)
+    }
+}
+
+impl fmt::Display for MinigrepError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            MinigrepError::Args(message) => write!(f, "{message}"),
+            MinigrepError::Io { path, source } => write!(f, "{}: {source}", path.display()),
+            MinigrepError::Encoding { path } => {
+                write!(f, "{}: stream did not contain valid UTF-8", path.display())
+            }
+            MinigrepError::BadIndex { path } => write!(
+                f,
+                "{}: not an index minigrep can read, run 'minigrep index {}' again",
+                path.display(),
+                path.parent().unwrap_or(path).display()
+            ),
+            MinigrepError::Decompress { path, source } => {
+                write!(f, "{}: {source}", path.display())
+            }
+            MinigrepError::Pattern(e) => write!(f, "{e}"),
+            MinigrepError::Query(e) => write!(f, "{e}"),
+            MinigrepError::Output(e) => write!(f, "couldn't write output: {e}"),
+        }
+    }
+}
+
+impl Error for MinigrepError {
+    fn source(&self) -> Option<&(dyn Error + 'static)> {
+        match self {
+            MinigrepError::Io { source, .. } | MinigrepError::Output(source) => Some(source),
+            MinigrepError::Pattern(e) => Some(e),
+            MinigrepError::Query(e) => Some(e),
+            MinigrepError::Decompress { source, .. } => Some(source),
+            MinigrepError::Args(_)
+            | MinigrepError::Encoding { .. }
+            | MinigrepError::BadIndex { .. } => None,
+        }
+    }
+}
+
+impl From<String> for MinigrepError {
+    fn from(message: String) -> MinigrepError {
+        MinigrepError::Args(message)
+    }
+}
+
+impl From<&str> for MinigrepError {
+    fn from(message: &str) -> MinigrepError {
+        MinigrepError::Args(message.to_string())
+    }
+}
+
+impl From<RegexError> for MinigrepError {
+    fn from(e: RegexError) -> MinigrepError {
+        MinigrepError::Pattern(e)
+    }
+}
+
+impl From<QueryError> for MinigrepError {
+    fn from(e: QueryError) -> MinigrepError {
+        MinigrepError::Query(e)
+    }
+}
+
+impl From<WalkError> for MinigrepError {
+    fn from(e: WalkError) -> MinigrepError {
+        MinigrepError::Io {
+            path: e.path,
+            source: e.error,
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn reading_errors_keep_the_path() {
+        let missing = io::Error::new(io::ErrorKind::NotFound, "No such file or directory");
+        let e = MinigrepError::reading("poem.txt", missing);
+        assert!(matches!(e, MinigrepError::Io { .. }));
+        assert_eq!("poem.txt: No such file or directory", e.to_string());
+
+        let binary = io::Error::new(io::ErrorKind::InvalidData, "bad bytes");
+        let e = MinigrepError::reading("image.png", binary);
+        assert!(matches!(e, MinigrepError::Encoding { .. }));
+        assert!(e.to_string().starts_with("image.png: "));
+
+        let corrupt = io::Error::from(GzipError::Truncated);
+        let e = MinigrepError::reading("app.log.gz", corrupt);
+        assert!(matches!(e, MinigrepError::Decompress { .. }));
+        assert_eq!("app.log.gz: gzip data ends unexpectedly", e.to_string());
+    }
+}
@@ -0,0 +1,295 @@
+// --fuzzy K, finding the query with up to K typos: letters added, left out or swapped for other ones
+// the number of typos between two strings is their Levenshtein distance. The usual way to find it fills in
+// a table with a row for every letter of the query and a column for every letter of the line, which is slow
+// for long lines. Myers' algorithm notices that neighbouring cells in the table never differ by more than
+// one, so a whole column fits into the bits of a few u64s (whether each cell went up or down from the one
+// above it) and a handful of bit operations moves on to the next column. A 64 letter query costs a few
+// instructions per letter of the line. Longer queries are split into blocks of 64 that pass a carry down.
+// The scan finds where a match ends, running it backwards from there over the reversed query finds
+// where it starts. With -i both sides are fully case folded like everywhere else, so "strasse" finds
+// "Straße": the letters of one character's folding go through together, a match never ends halfway.
+
+use std::collections::HashMap;
+use std::ops::Range;
+
+use crate::casefold;
+
+#[derive(Debug, Clone)]
+pub struct Fuzzy {
+    len: usize,
+    max_distance: usize,
+    ignore_case: bool,
+    forward: Peq,
+    backward: Peq,
+}
+
+impl Fuzzy {
+    pub fn new(pattern: &str, max_distance: usize, ignore_case: bool) -> Fuzzy {
+        let chars: Vec<char> = pattern.chars().flat_map(|c| fold(c, ignore_case)).collect();
+        let reversed: Vec<char> = chars.iter().rev().copied().collect();
+        Fuzzy {
+            len: chars.len(),
+            max_distance,
+            ignore_case,
+            forward: Peq::new(&chars),
+            backward: Peq::new(&reversed),
+        }
+    }
+
+    // the first match that ends at or after start, and its distance. When several ends in a row are close
+    // enough the best of them is taken, the last one on a tie so "frg" finds all of "frog" and not just
+    // "fr", and the shortest match ending there
+    pub fn find_at(&self, text: &str, start: usize) -> Option<(Range<usize>, usize)> {
+        // with at least as many typos allowed as the query is long, even an empty match is close enough
+        if self.len <= self.max_distance {
+            return Some((start..start, self.len));
+        }
+        let mut columns = Columns::new(&self.forward);
+        let mut found: Option<(usize, usize)> = None;
+        for (i, c) in text[start..].char_indices() {
+            let distance =
+                fold(c, self.ignore_case).fold(0, |_, c| columns.step(&self.forward, c, 0));
+            let end = start + i + c.len_utf8();
+            match found {
+                // the run of ends that are close enough is over
+                Some(_) if distance > self.max_distance => break,
+                Some((_, best)) if distance > best => {}
+                _ if distance <= self.max_distance => found = Some((end, distance)),
+                _ => {}
+            }
+        }
+        let (end, distance) = found?;
+
+        // backwards from end the start is anchored, so every step to the left costs a letter
+        let mut columns = Columns::new(&self.backward);
+        for (i, c) in text[start..end].char_indices().rev() {
+            let steps = fold(c, self.ignore_case).rev();
+            if steps.fold(0, |_, c| columns.step(&self.backward, c, 1)) == distance {
+                return Some((start + i..end, distance));
+            }
+        }
+        // only an empty match could be left, which can't be when the query is longer than distance
+        None
+    }
+
+    // the Levenshtein distance between the whole query and the whole of text
+    pub fn distance(&self, text: &str) -> usize {
+        if self.len == 0 {
+            return text.chars().count();
+        }
+        let mut columns = Columns::new(&self.forward);
+        let mut distance = self.len;
+        for c in text.chars() {
+            for c in fold(c, self.ignore_case) {
+                distance = columns.step(&self.forward, c, 1);
+            }
+        }
+        distance
+    }
+}
+
+// c the way the query sees it, with -i that can be more than one letter ('ß' is "ss")
+fn fold(c: char, ignore_case: bool) -> impl DoubleEndedIterator<Item = char> {
+    let mut chars = [c; 3];
+    let mut len = 1;
+    if ignore_case {
+        len = 0;
+        for folded in casefold::fold(c) {
+            chars[len] = folded;
+            len += 1;
+        }
+    }
+    chars.into_iter().take(len)
+}
+
+// for every letter, the bits of the query positions that have that letter, one u64 per block
+#[derive(Debug, Clone)]
+struct Peq {
+    len: usize,
+    blocks: usize,
+    // the bit of the query's last letter in the last block
+    last: u64,
+    ascii: Vec<u64>,
+    other: HashMap<char, Vec<u64>>,
+}
+
+impl Peq {
+    fn new(pattern: &[char]) -> Peq {
+        let blocks = pattern.len().div_ceil(64).max(1);
+        let mut peq = Peq {
+            len: pattern.len(),
+            blocks,
+            last: 1 << ((pattern.len().max(1) - 1) % 64),
+            ascii: vec![0; 128 * blocks],
+            other: HashMap::new(),
+        };
+        for (i, &c) in pattern.iter().enumerate() {
+            let bit = 1 << (i % 64);
+            if c.is_ascii() {
+                peq.ascii[c as usize * blocks + i / 64] |= bit;
+            } else {
+                peq.other.entry(c).or_insert_with(|| vec![0; blocks])[i / 64] |= bit;
+            }
+        }
+        peq
+    }
+
+    fn get(&self, c: char, block: usize) -> u64 {
+        if c.is_ascii() {
+            self.ascii[c as usize * self.blocks + block]
+        } else {
+            self.other.get(&c).map_or(0, |bits| bits[block])
+        }
+    }
+}
+
+// one column of the table: for every row whether it's one more (pv) or one less (mv) than the row above,
+// and the value in the last row
+struct Columns {
+    pv: Vec<u64>,
+    mv: Vec<u64>,
+    score: usize,
+}
+
+impl Columns {
+    fn new(peq: &Peq) -> Columns {
+        // the first column counts up one per row, the query against nothing at all
+        Columns {
+            pv: vec![!0; peq.blocks],
+            mv: vec![0; peq.blocks],
+            score: peq.len,
+        }
+    }
+
+    // moves on by one letter of the text and returns the new value in the last row. top is how much the
+    // top row goes up per column: 0 when a match can start anywhere, 1 when it has to start at the beginning
+    fn step(&mut self, peq: &Peq, c: char, top: i32) -> usize {
+        let mut carry = top;
+        for block in 0..peq.blocks {
+            let high = if block == peq.blocks - 1 {
+                peq.last
+            } else {
+                1 << 63
+            };
+            let (pv, mv) = (self.pv[block], self.mv[block]);
+            let mut eq = peq.get(c, block);
+            let xv = eq | mv;
+            if carry < 0 {
+                eq |= 1;
+            }
+            let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+            let mut ph = mv | !(xh | pv);
+            let mut mh = pv & xh;
+            let out = if ph & high != 0 {
+                1
+            } else if mh & high != 0 {
+                -1
+            } else {
+                0
+            };
+            ph <<= 1;
+            mh <<= 1;
+            if carry < 0 {
+                mh |= 1;
+            } else if carry > 0 {
+                ph |= 1;
+            }
+            self.pv[block] = mh | !(xv | ph);
+            self.mv[block] = ph & xv;
+            carry = out;
+        }
+        self.score = self.score.wrapping_add_signed(carry as isize);
+        self.score
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // the textbook table, to check the bits against
+    fn levenshtein(a: &str, b: &str) -> usize {
+        let b: Vec<char> = b.chars().collect();
+        let mut row: Vec<usize> = (0..=b.len()).collect();
+        for (i, ca) in a.chars().enumerate() {
+            let mut diagonal = row[0];
+            row[0] = i + 1;
+            for j in 0..b.len() {
+                let substitute = diagonal + usize::from(ca != b[j]);
+                diagonal = row[j + 1];
+                row[j + 1] = substitute.min(row[j] + 1).min(row[j + 1] + 1);
+            }
+        }
+        row[b.len()]
+    }
+
+    #[test]
+    fn distances_match_the_table() {
+        let words = [
+            "",
+            "frog",
+            "frgo",
+            "fog",
+            "kitten",
+            "sitting",
+            "Straße",
+            "admiring bog",
+        ];
+        for a in words {
+            for b in words {
+                assert_eq!(
+                    levenshtein(a, b),
+                    Fuzzy::new(a, 0, false).distance(b),
+                    "{a} {b}"
+                );
+            }
+        }
+        // more than one block
+        let long = "safe, fast, productive. ".repeat(6);
+        let typo = long
+            .replacen("fast", "fats", 2)
+            .replace("productive", "prodcutive");
+        assert_eq!(
+            levenshtein(&long, &typo),
+            Fuzzy::new(&long, 0, false).distance(&typo)
+        );
+    }
+
+    #[test]
+    fn finds_typos() {
+        let fuzzy = Fuzzy::new("public", 1, false);
+        assert_eq!(Some((4..9, 1)), fuzzy.find_at("How pubic, like a frog", 0));
+        assert_eq!(None, fuzzy.find_at("How dreary to be somebody!", 0));
+        // "nob" is already close enough, but "nobody" is closer
+        let fuzzy = Fuzzy::new("nobdy", 2, false);
+        assert_eq!(Some((4..10, 1)), fuzzy.find_at("I'm nobody!", 0));
+
+        let fuzzy = Fuzzy::new("admiring", 2, true);
+        assert_eq!(Some((6..13, 1)), fuzzy.find_at("To an ADMRING bog!", 0));
+        assert_eq!(None, fuzzy.find_at("To an ADMRING bog!", 14));
+        // full case folding, like -i without --fuzzy
+        let fuzzy = Fuzzy::new("strasse", 1, true);
+        assert_eq!(Some((4..11, 0)), fuzzy.find_at("Die Straße", 0));
+        assert_eq!(Some((4..10, 1)), fuzzy.find_at("Die Strße", 0));
+        assert_eq!(0, Fuzzy::new("STRAẞE", 0, true).distance("strasse"));
+
+        // "fr", "fro" and "frog" are all one typo away, the longest of them wins
+        let fuzzy = Fuzzy::new("frg", 1, false);
+        assert_eq!(
+            Some((19..23, 1)),
+            fuzzy.find_at("How public, like a frog", 0)
+        );
+        let fuzzy = Fuzzy::new("SOMEBODI", 2, true);
+        assert_eq!(
+            Some((17..25, 1)),
+            fuzzy.find_at("How dreary to be somebody!", 0)
+        );
+
+        let long = "I'm nobody! Who are you? Are you nobody, too? Then there's a pair of us";
+        let text = format!("-- {} --", long.replace("pair", "piar"));
+        assert_eq!(
+            Some((3..3 + long.len(), 2)),
+            Fuzzy::new(long, 3, false).find_at(&text, 0)
+        );
+    }
+}
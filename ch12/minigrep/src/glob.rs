@@ -0,0 +1,379 @@
+// shell style wildcards, so `minigrep PATTERN 'logs/*.txt'` works the same no matter which shell runs it
+// `*` matches anything inside one path component, `?` matches one character, `[abc]`, `[a-z]` and `[!a-z]`
+// match one character from (or not from) a set, `**` on its own matches any number of directories and
+// a backslash makes the next character literal. Like in the shell, a name starting with `.` is only
+// matched by a pattern that starts with `.` too, and ignore files don't hide anything from a glob
+
+use std::io;
+use std::path::{Path, PathBuf};
+
+use crate::error::MinigrepError;
+use crate::walk::{Walk, WalkOptions};
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    Char(char),
+    // *
+    Star,
+    // ?
+    Any,
+    Class {
+        ranges: Vec<(char, char)>,
+        negated: bool,
+    },
+}
+
+impl Token {
+    fn matches(&self, c: char) -> bool {
+        match self {
+            Token::Char(expected) => c == *expected,
+            Token::Star | Token::Any => true,
+            Token::Class { ranges, negated } => {
+                ranges.iter().any(|&(start, end)| start <= c && c <= end) != *negated
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Segment {
+    // ** matches zero or more whole path components
+    AnyDirs,
+    Pattern(Vec<Token>),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Glob {
+    segments: Vec<Segment>,
+    // whether wildcards match the `.` at the start of a name, like in ignore files but not in the shell
+    dotfiles: bool,
+}
+
+// does s have any wildcards in it at all
+pub fn is_glob(s: &str) -> bool {
+    s.contains(['*', '?', '['])
+}
+
+impl Glob {
+    // every pattern is valid, a `[` without a matching `]` is just a literal `[` like in the shell
+    pub fn new(pattern: &str) -> Glob {
+        let segments = pattern
+            .split('/')
+            .map(|segment| {
+                if segment == "**" {
+                    Segment::AnyDirs
+                } else {
+                    Segment::Pattern(parse_segment(segment))
+                }
+            })
+            .collect();
+        Glob {
+            segments,
+            dotfiles: true,
+        }
+    }
+
+    // a glob the user typed, which leaves names starting with `.` alone unless it spells out the `.`
+    pub fn shell(pattern: &str) -> Glob {
+        Glob {
+            dotfiles: false,
+            ..Glob::new(pattern)
+        }
+    }
+
+    // path uses `/` between components
+    pub fn matches(&self, path: &str) -> bool {
+        let parts: Vec<&str> = path.split('/').collect();
+        match_segments(&self.segments, &parts, self.dotfiles)
+    }
+
+    pub fn has_any_dirs(&self) -> bool {
+        self.segments.contains(&Segment::AnyDirs)
+    }
+}
+
+fn parse_segment(segment: &str) -> Vec<Token> {
+    let chars: Vec<char> = segment.chars().collect();
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        match chars[i] {
+            // a run of stars is the same as one star
+            '*' if tokens.last() == Some(&Token::Star) => {}
+            '*' => tokens.push(Token::Star),
+            '?' => tokens.push(Token::Any),
+            '\\' if i + 1 < chars.len() => {
+                i += 1;
+                tokens.push(Token::Char(chars[i]));
+            }
+            '[' => match parse_class(&chars[i + 1..]) {
+                Some((token, len)) => {
+                    tokens.push(token);
+                    i += len;
+                }
+                None => tokens.push(Token::Char('[')),
+            },
+            c => tokens.push(Token::Char(c)),
+        }
+        i += 1;
+    }
+    tokens
+}
+
+// chars starts right after the `[`, returns the class and how many chars it used including the `]`
+fn parse_class(chars: &[char]) -> Option<(Token, usize)> {
+    let mut i = 0;
+    let negated = matches!(chars.first(), Some('!') | Some('^'));
+    if negated {
+        i += 1;
+    }
+    let mut ranges = Vec::new();
+    let first = i;
+    loop {
+        let c = *chars.get(i)?;
+        // a `]` straight after the `[` is part of the set
+        if c == ']' && i > first {
+            return Some((Token::Class { ranges, negated }, i + 1));
+        }
+        if chars.get(i + 1) == Some(&'-') && chars.get(i + 2).is_some_and(|&end| end != ']') {
+            ranges.push((c, chars[i + 2]));
+            i += 3;
+        } else {
+            ranges.push((c, c));
+            i += 1;
+        }
+    }
+}
+
+fn match_segments(segments: &[Segment], parts: &[&str], dotfiles: bool) -> bool {
+    let hidden = |part: &str| !dotfiles && part.starts_with('.');
+    match segments.first() {
+        None => parts.is_empty(),
+        // without dotfiles ** doesn't go into hidden directories either
+        Some(Segment::AnyDirs) => (0..=parts.len())
+            .take_while(|&skip| skip == 0 || !hidden(parts[skip - 1]))
+            .any(|skip| match_segments(&segments[1..], &parts[skip..], dotfiles)),
+        Some(Segment::Pattern(tokens)) => {
+            !parts.is_empty()
+                && (!hidden(parts[0]) || tokens.first() == Some(&Token::Char('.')))
+                && match_tokens(tokens, &parts[0].chars().collect::<Vec<_>>())
+                && match_segments(&segments[1..], &parts[1..], dotfiles)
+        }
+    }
+}
+
+// the usual wildcard matcher: when something after a star doesn't fit, go back and let the star eat one
+// more character. Only the most recent star ever needs revisiting, so this never takes exponential time
+fn match_tokens(tokens: &[Token], text: &[char]) -> bool {
+    let (mut t, mut c) = (0, 0);
+    let mut star: Option<(usize, usize)> = None;
+    while c < text.len() {
+        match tokens.get(t) {
+            Some(Token::Star) => {
+                star = Some((t, c));
+                t += 1;
+            }
+            Some(token) if token.matches(text[c]) => {
+                t += 1;
+                c += 1;
+            }
+            _ => match star {
+                Some((star_t, star_c)) => {
+                    t = star_t + 1;
+                    c = star_c + 1;
+                    star = Some((star_t, star_c + 1));
+                }
+                None => return false,
+            },
+        }
+    }
+    tokens[t..].iter().all(|token| *token == Token::Star)
+}
+
+// the files a glob expands to, in the same sorted order Walk uses
+// when nothing matches that's reported as an error, the same way a shell with failglob would
+pub struct Expand {
+    walk: Walk,
+    glob: Glob,
+    pattern: String,
+    // the directory the walk starts in, and whether it was written in the pattern or is just "."
+    base: PathBuf,
+    implicit_base: bool,
+    found: bool,
+    finished: bool,
+}
+
+pub fn expand(pattern: &str, options: WalkOptions) -> Expand {
+    // everything up to the first component with a wildcard is a plain directory we can start walking from
+    let parts: Vec<&str> = pattern.split('/').collect();
+    let first_glob = parts.iter().position(|part| is_glob(part)).unwrap_or(0);
+    let mut base = parts[..first_glob].join("/");
+    if base.is_empty() && pattern.starts_with('/') {
+        base = String::from("/");
+    }
+    let implicit_base = base.is_empty();
+    if implicit_base {
+        base = String::from(".");
+    }
+
+    let glob = Glob::shell(&parts[first_glob..].join("/"));
+    // the walk has to see everything a shell would, the glob decides what's hidden
+    let mut options = options;
+    options.hidden = true;
+    options.no_ignore = true;
+    if !glob.has_any_dirs() {
+        // without ** the glob can't match anything deeper than it has components
+        let depth = parts.len() - first_glob;
+        options.max_depth = Some(options.max_depth.map_or(depth, |max| max.min(depth)));
+    }
+
+    Expand {
+        walk: Walk::new(&base, options),
+        glob,
+        pattern: pattern.to_string(),
+        base: PathBuf::from(base),
+        implicit_base,
+        found: false,
+        finished: false,
+    }
+}
+
+impl Iterator for Expand {
+    type Item = Result<PathBuf, MinigrepError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        if self.finished {
+            return None;
+        }
+        for path in self.walk.by_ref() {
+            let path = match path {
+                Ok(path) => path,
+                Err(e) => {
+                    // the error already explains why nothing turned up
+                    self.found = true;
+                    return Some(Err(e.into()));
+                }
+            };
+            let relative = match path.strip_prefix(&self.base) {
+                Ok(relative) => relative,
+                Err(_) => continue,
+            };
+            if self.glob.matches(&components(relative)) {
+                self.found = true;
+                let path = if self.implicit_base {
+                    relative.to_path_buf()
+                } else {
+                    path
+                };
+                return Some(Ok(path));
+            }
+        }
+
+        self.finished = true;
+        if self.found {
+            return None;
+        }
+        Some(Err(MinigrepError::Io {
+            path: PathBuf::from(&self.pattern),
+            source: io::Error::new(io::ErrorKind::NotFound, "no files match this pattern"),
+        }))
+    }
+}
+
+// the path with `/` between components on every platform, which is what globs are written with
+fn components(path: &Path) -> String {
+    let parts: Vec<_> = path
+        .components()
+        .map(|component| component.as_os_str().to_string_lossy())
+        .collect();
+    parts.join("/")
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    #[test]
+    fn wildcards() {
+        assert!(Glob::new("*.txt").matches("poem.txt"));
+        assert!(!Glob::new("*.txt").matches("logs/poem.txt"));
+        assert!(Glob::new("poem.???").matches("poem.txt"));
+        assert!(Glob::new("log-[0-9][!a-z].gz").matches("log-12.gz"));
+        assert!(!Glob::new("log-[0-9][!a-z].gz").matches("log-1a.gz"));
+        assert!(Glob::new("a*b*c").matches("aXXbYYbc"));
+        assert!(Glob::new(r"\*.md").matches("*.md"));
+        assert!(!Glob::new(r"\*.md").matches("README.md"));
+        assert!(Glob::new("[x").matches("[x"));
+        // only a typed glob leaves dotfiles alone
+        assert!(!Glob::shell("*").matches(".env"));
+        assert!(!Glob::shell("?env").matches(".env"));
+        assert!(Glob::shell(".e*").matches(".env"));
+        assert!(Glob::new("*").matches(".env"));
+    }
+
+    #[test]
+    fn double_star_crosses_directories() {
+        let glob = Glob::new("src/**/*.rs");
+        assert!(glob.matches("src/lib.rs"));
+        assert!(glob.matches("src/a/b/c.rs"));
+        assert!(!glob.matches("tests/cli.rs"));
+        assert!(Glob::new("logs/**").matches("logs/2024/01/app.log"));
+        assert!(!Glob::shell("**/*.rs").matches(".git/hooks/a.rs"));
+        assert!(Glob::new("**/*.rs").matches(".git/hooks/a.rs"));
+    }
+
+    #[test]
+    fn expands_against_the_file_system() {
+        let root = env::temp_dir().join(format!("minigrep-glob-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("logs/old")).unwrap();
+        for file in ["logs/a.txt", "logs/b.log", "logs/old/c.txt"] {
+            fs::write(root.join(file), "x").unwrap();
+        }
+
+        let pattern = format!("{}/logs/*.txt", root.display());
+        let found: Vec<PathBuf> = expand(&pattern, WalkOptions::default())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(vec![root.join("logs/a.txt")], found);
+
+        let pattern = format!("{}/**/*.txt", root.display());
+        let found: Vec<PathBuf> = expand(&pattern, WalkOptions::default())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            vec![root.join("logs/a.txt"), root.join("logs/old/c.txt")],
+            found
+        );
+
+        // ignore files and hidden files are the walk's business, a glob finds what the shell would
+        fs::create_dir_all(root.join(".git")).unwrap();
+        fs::write(root.join(".gitignore"), "*.log\n").unwrap();
+        fs::write(root.join(".env"), "x").unwrap();
+        let pattern = format!("{}/logs/*.log", root.display());
+        let found: Vec<PathBuf> = expand(&pattern, WalkOptions::default())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(vec![root.join("logs/b.log")], found);
+        let pattern = format!("{}/.e*", root.display());
+        let found: Vec<PathBuf> = expand(&pattern, WalkOptions::default())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(vec![root.join(".env")], found);
+        // only .env and .gitignore are files up there, and * doesn't match them
+        let pattern = format!("{}/*", root.display());
+        assert!(expand(&pattern, WalkOptions::default())
+            .next()
+            .unwrap()
+            .is_err());
+
+        let pattern = format!("{}/*.csv", root.display());
+        let mut expand = expand(&pattern, WalkOptions::default());
+        assert!(expand.next().unwrap().is_err());
+        assert!(expand.next().is_none());
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
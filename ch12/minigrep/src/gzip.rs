@@ -0,0 +1,737 @@
+// -z, searching .gz files without unpacking them first, so rotated logs can be searched where they are
+// a gzip file is one or more members, each a small header, a DEFLATE stream and the CRC-32 and length of
+// what it unpacks to. DEFLATE (RFC 1951) is a series of blocks: stored as they are, or LZ77 back references
+// into the last 32 KiB written, with the literals, lengths and distances packed in Huffman codes that are
+// either fixed or sent at the start of the block. It's unpacked a chunk at a time as lines are read, only
+// the 32 KiB window and the part that hasn't been read yet are held in memory.
+// A file is treated as compressed when it starts with the gzip magic bytes or ends in .gz, one that ends in
+// .gz but isn't gzip is an error rather than being searched as it is. bzip2, xz and zstd files are
+// recognised too, but only to say they can't be unpacked.
+
+use std::error::Error;
+use std::fmt;
+use std::io::{self, BufRead, Read};
+use std::path::Path;
+use std::rc::Rc;
+
+const MAGIC: [u8; 2] = [0x1F, 0x8B];
+// DEFLATE is the only compression method gzip has ever had
+const DEFLATE: u8 = 8;
+const WINDOW: usize = 32 * 1024;
+// how much is unpacked at a time before handing it out
+const CHUNK: usize = 32 * 1024;
+
+// what's wrong with a compressed file
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum GzipError {
+    // the name says gzip but the bytes don't
+    NotGzip,
+    // compressed, but not with something minigrep can unpack
+    Unsupported(&'static str),
+    // a method other than DEFLATE, or header flags nobody has defined
+    BadHeader,
+    // the DEFLATE data doesn't make sense, the message says where it went wrong
+    Corrupt(&'static str),
+    // the file stopped in the middle of a member
+    Truncated,
+    // the unpacked data isn't what the trailer says it should be
+    Checksum,
+}
+
+impl fmt::Display for GzipError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            GzipError::NotGzip => write!(f, "not in gzip format"),
+            GzipError::Unsupported(format) => {
+                write!(f, "compressed with {format}, only gzip can be searched")
+            }
+            GzipError::BadHeader => write!(f, "corrupt gzip header"),
+            GzipError::Corrupt(what) => write!(f, "corrupt gzip data: {what}"),
+            GzipError::Truncated => write!(f, "gzip data ends unexpectedly"),
+            GzipError::Checksum => write!(f, "gzip checksum doesn't match"),
+        }
+    }
+}
+
+impl Error for GzipError {}
+
+impl From<GzipError> for io::Error {
+    fn from(e: GzipError) -> io::Error {
+        io::Error::new(io::ErrorKind::InvalidData, e)
+    }
+}
+
+// the reader to search: reader itself, or what it unpacks to when it's compressed
+pub fn decompress<'a, R: BufRead + 'a>(
+    path: &Path,
+    mut reader: R,
+) -> io::Result<Box<dyn BufRead + 'a>> {
+    let extension = path.extension().and_then(|e| e.to_str()).unwrap_or("");
+    // like the byte order marks, the magic bytes are always in the first read
+    let start = reader.fill_buf()?;
+    let unsupported = match start {
+        [0x1F, 0x8B, ..] => return Ok(Box::new(io::BufReader::new(GzDecoder::new(reader)))),
+        [b'B', b'Z', b'h', b'1'..=b'9', ..] => Some("bzip2"),
+        [0xFD, b'7', b'z', b'X', b'Z', 0x00, ..] => Some("xz"),
+        [0x28, 0xB5, 0x2F, 0xFD, ..] => Some("zstd"),
+        _ => match extension {
+            "bz2" => Some("bzip2"),
+            "xz" => Some("xz"),
+            "zst" => Some("zstd"),
+            _ => None,
+        },
+    };
+    if let Some(format) = unsupported {
+        return Err(GzipError::Unsupported(format).into());
+    }
+    if matches!(extension, "gz" | "tgz") {
+        return Err(GzipError::NotGzip.into());
+    }
+    Ok(Box::new(reader))
+}
+
+// unpacks a gzip file as it's read
+pub struct GzDecoder<R> {
+    bits: Bits<R>,
+    state: State,
+    fixed: Option<Rc<Codes>>,
+    // what's been unpacked: the window back references can reach into, then what hasn't been read yet
+    out: Vec<u8>,
+    read: usize,
+    // how much of out the checksum covers
+    summed: usize,
+    // of the member being unpacked, for its trailer
+    crc: u32,
+    size: u64,
+}
+
+// the literal and length code, and the distance code, of a block
+type Codes = (Huffman, Huffman);
+
+enum State {
+    // a member's header comes next
+    Header,
+    // a block's header comes next, unless the block before was the last one
+    Block { last: bool },
+    Stored { remaining: usize, last: bool },
+    Compressed { codes: Rc<Codes>, last: bool },
+    // the member's CRC-32 and size come next
+    Trailer,
+    Done,
+}
+
+impl<R: BufRead> GzDecoder<R> {
+    pub fn new(inner: R) -> GzDecoder<R> {
+        GzDecoder {
+            bits: Bits::new(inner),
+            state: State::Header,
+            fixed: None,
+            out: Vec::new(),
+            read: 0,
+            summed: 0,
+            crc: 0,
+            size: 0,
+        }
+    }
+
+    // unpacks the next chunk, false once there's nothing left
+    fn fill(&mut self) -> io::Result<bool> {
+        // everything in out has been read, only the window needs keeping
+        if self.out.len() > WINDOW {
+            self.out.drain(..self.out.len() - WINDOW);
+        }
+        self.read = self.out.len();
+        self.summed = self.out.len();
+        loop {
+            match self.state {
+                State::Done => break,
+                State::Trailer => {
+                    self.sum();
+                    self.trailer()?;
+                }
+                _ if self.out.len() - self.read >= CHUNK => break,
+                _ => self.step()?,
+            }
+        }
+        self.sum();
+        Ok(self.out.len() > self.read)
+    }
+
+    fn sum(&mut self) {
+        let unpacked = &self.out[self.summed..];
+        self.crc = crc32(self.crc, unpacked);
+        self.size += unpacked.len() as u64;
+        self.summed = self.out.len();
+    }
+
+    // a header, or a run of unpacked bytes
+    fn step(&mut self) -> io::Result<()> {
+        self.state = match std::mem::replace(&mut self.state, State::Done) {
+            State::Header => {
+                self.header()?;
+                State::Block { last: false }
+            }
+            State::Block { last: true } => {
+                self.bits.align();
+                State::Trailer
+            }
+            State::Block { last: false } => self.block_header()?,
+            State::Stored { remaining, last } => {
+                let n = remaining.min(CHUNK);
+                for _ in 0..n {
+                    let byte = self.bits.get(8)? as u8;
+                    self.out.push(byte);
+                }
+                match remaining - n {
+                    0 => State::Block { last },
+                    remaining => State::Stored { remaining, last },
+                }
+            }
+            State::Compressed { codes, last } => {
+                if self.symbols(&codes)? {
+                    State::Block { last }
+                } else {
+                    State::Compressed { codes, last }
+                }
+            }
+            state @ (State::Trailer | State::Done) => state,
+        };
+        Ok(())
+    }
+
+    fn header(&mut self) -> io::Result<()> {
+        let bits = &mut self.bits;
+        if [bits.get(8)? as u8, bits.get(8)? as u8] != MAGIC {
+            return Err(GzipError::NotGzip.into());
+        }
+        let method = bits.get(8)? as u8;
+        let flags = bits.get(8)? as u8;
+        if method != DEFLATE || flags & 0xE0 != 0 {
+            return Err(GzipError::BadHeader.into());
+        }
+        // the modification time, extra flags and operating system don't matter for searching
+        bits.get(32)?;
+        bits.get(16)?;
+        // FEXTRA
+        if flags & 0x04 != 0 {
+            let len = bits.get(16)?;
+            for _ in 0..len {
+                bits.get(8)?;
+            }
+        }
+        // FNAME and FCOMMENT, both end with a NUL
+        for flag in [0x08, 0x10] {
+            if flags & flag != 0 {
+                while bits.get(8)? != 0 {}
+            }
+        }
+        // FHCRC
+        if flags & 0x02 != 0 {
+            bits.get(16)?;
+        }
+        self.crc = 0;
+        self.size = 0;
+        Ok(())
+    }
+
+    fn block_header(&mut self) -> io::Result<State> {
+        let last = self.bits.get(1)? == 1;
+        let state = match self.bits.get(2)? {
+            0 => {
+                self.bits.align();
+                let len = self.bits.get(16)?;
+                if self.bits.get(16)? != !len & 0xFFFF {
+                    return Err(GzipError::Corrupt("stored block length").into());
+                }
+                State::Stored {
+                    remaining: len as usize,
+                    last,
+                }
+            }
+            1 => {
+                let fixed = match &self.fixed {
+                    Some(fixed) => fixed,
+                    None => self.fixed.insert(Rc::new(fixed_codes()?)),
+                };
+                State::Compressed {
+                    codes: Rc::clone(fixed),
+                    last,
+                }
+            }
+            2 => State::Compressed {
+                codes: Rc::new(self.dynamic_codes()?),
+                last,
+            },
+            _ => return Err(GzipError::Corrupt("invalid block type").into()),
+        };
+        Ok(state)
+    }
+
+    // the codes a block brings with it, their lengths are themselves sent with a Huffman code
+    fn dynamic_codes(&mut self) -> io::Result<Codes> {
+        const ORDER: [usize; 19] = [
+            16, 17, 18, 0, 8, 7, 9, 6, 10, 5, 11, 4, 12, 3, 13, 2, 14, 1, 15,
+        ];
+        let bits = &mut self.bits;
+        let literals = bits.get(5)? as usize + 257;
+        let distances = bits.get(5)? as usize + 1;
+        let code_lengths = bits.get(4)? as usize + 4;
+        if literals > 286 || distances > 30 {
+            return Err(GzipError::Corrupt("too many codes").into());
+        }
+        let mut lengths = [0; 19];
+        for &i in &ORDER[..code_lengths] {
+            lengths[i] = bits.get(3)? as u8;
+        }
+        let lengths_code = Huffman::new(&lengths)?;
+
+        let mut lengths = vec![0; literals + distances];
+        let mut i = 0;
+        while i < lengths.len() {
+            let (length, repeat) = match lengths_code.decode(bits)? {
+                symbol @ 0..=15 => (symbol as u8, 1),
+                16 if i == 0 => return Err(GzipError::Corrupt("repeat with nothing before").into()),
+                16 => (lengths[i - 1], 3 + bits.get(2)? as usize),
+                17 => (0, 3 + bits.get(3)? as usize),
+                _ => (0, 11 + bits.get(7)? as usize),
+            };
+            if i + repeat > lengths.len() {
+                return Err(GzipError::Corrupt("too many code lengths").into());
+            }
+            lengths[i..i + repeat].fill(length);
+            i += repeat;
+        }
+        if lengths[256] == 0 {
+            return Err(GzipError::Corrupt("no end of block code").into());
+        }
+        Ok((
+            Huffman::new(&lengths[..literals])?,
+            Huffman::new(&lengths[literals..])?,
+        ))
+    }
+
+    // unpacks literals and back references until the block ends (true) or a chunk is ready (false)
+    fn symbols(&mut self, (literals, distances): &Codes) -> io::Result<bool> {
+        while self.out.len() - self.read < CHUNK {
+            let symbol = literals.decode(&mut self.bits)? as usize;
+            if symbol < 256 {
+                self.out.push(symbol as u8);
+                continue;
+            } else if symbol == 256 {
+                return Ok(true);
+            }
+            let Some(&(base, extra)) = LENGTHS.get(symbol - 257) else {
+                return Err(GzipError::Corrupt("invalid length code").into());
+            };
+            let length = base as usize + self.bits.get(extra)? as usize;
+            let symbol = distances.decode(&mut self.bits)? as usize;
+            let Some(&(base, extra)) = DISTANCES.get(symbol) else {
+                return Err(GzipError::Corrupt("invalid distance code").into());
+            };
+            let distance = base as usize + self.bits.get(extra)? as usize;
+            // only as far back as this member goes, out can still have the end of the one before
+            let member = self.size + (self.out.len() - self.summed) as u64;
+            if distance as u64 > member || distance > self.out.len() {
+                return Err(GzipError::Corrupt("distance too far back").into());
+            }
+            // the copy can overlap what it's writing, that's how a run of the same bytes is packed
+            let from = self.out.len() - distance;
+            for i in from..from + length {
+                let byte = self.out[i];
+                self.out.push(byte);
+            }
+        }
+        Ok(false)
+    }
+
+    // checks the member that just ended, another one can follow it
+    fn trailer(&mut self) -> io::Result<()> {
+        let crc = self.bits.get(32)?;
+        let size = self.bits.get(32)?;
+        // the size is only kept modulo 4 GiB
+        if crc != self.crc || size != self.size as u32 {
+            return Err(GzipError::Checksum.into());
+        }
+        // gzip ignores whatever comes after the last member, like the zeros tar pads with
+        self.state = if self.bits.starts_with(&MAGIC)? {
+            State::Header
+        } else {
+            State::Done
+        };
+        Ok(())
+    }
+}
+
+impl<R: BufRead> Read for GzDecoder<R> {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if self.read == self.out.len() && !self.fill()? {
+            return Ok(0);
+        }
+        let n = buf.len().min(self.out.len() - self.read);
+        buf[..n].copy_from_slice(&self.out[self.read..self.read + n]);
+        self.read += n;
+        Ok(n)
+    }
+}
+
+// (base, extra bits) for the lengths 257 to 285 stand for, and for the 30 distance codes
+const LENGTHS: [(u16, u32); 29] = [
+    (3, 0),
+    (4, 0),
+    (5, 0),
+    (6, 0),
+    (7, 0),
+    (8, 0),
+    (9, 0),
+    (10, 0),
+    (11, 1),
+    (13, 1),
+    (15, 1),
+    (17, 1),
+    (19, 2),
+    (23, 2),
+    (27, 2),
+    (31, 2),
+    (35, 3),
+    (43, 3),
+    (51, 3),
+    (59, 3),
+    (67, 4),
+    (83, 4),
+    (99, 4),
+    (115, 4),
+    (131, 5),
+    (163, 5),
+    (195, 5),
+    (227, 5),
+    (258, 0),
+];
+const DISTANCES: [(u16, u32); 30] = [
+    (1, 0),
+    (2, 0),
+    (3, 0),
+    (4, 0),
+    (5, 1),
+    (7, 1),
+    (9, 2),
+    (13, 2),
+    (17, 3),
+    (25, 3),
+    (33, 4),
+    (49, 4),
+    (65, 5),
+    (97, 5),
+    (129, 6),
+    (193, 6),
+    (257, 7),
+    (385, 7),
+    (513, 8),
+    (769, 8),
+    (1025, 9),
+    (1537, 9),
+    (2049, 10),
+    (3073, 10),
+    (4097, 11),
+    (6145, 11),
+    (8193, 12),
+    (12289, 12),
+    (16385, 13),
+    (24577, 13),
+];
+
+// the codes blocks of type 1 use, so small blocks don't have to send their own
+fn fixed_codes() -> io::Result<Codes> {
+    let mut literals = [8; 288];
+    literals[144..256].fill(9);
+    literals[256..280].fill(7);
+    Ok((Huffman::new(&literals)?, Huffman::new(&[5; 30])?))
+}
+
+// the bits come least significant first, a u64 holds the next few bytes of them
+struct Bits<R> {
+    inner: R,
+    bits: u64,
+    count: u32,
+}
+
+impl<R: BufRead> Bits<R> {
+    fn new(inner: R) -> Bits<R> {
+        Bits {
+            inner,
+            bits: 0,
+            count: 0,
+        }
+    }
+
+    // tops up the buffer with as many whole bytes as fit, fewer at the end of the file
+    fn refill(&mut self) -> io::Result<()> {
+        while self.count <= 56 {
+            let input = self.inner.fill_buf()?;
+            if input.is_empty() {
+                break;
+            }
+            let take = (((64 - self.count) / 8) as usize).min(input.len());
+            for &byte in &input[..take] {
+                self.bits |= (byte as u64) << self.count;
+                self.count += 8;
+            }
+            self.inner.consume(take);
+        }
+        Ok(())
+    }
+
+    // the next n bits, n is at most 32
+    fn get(&mut self, n: u32) -> io::Result<u32> {
+        if self.count < n {
+            self.refill()?;
+            if self.count < n {
+                return Err(GzipError::Truncated.into());
+            }
+        }
+        let value = (self.bits & ((1 << n) - 1)) as u32;
+        self.consume(n);
+        Ok(value)
+    }
+
+    fn consume(&mut self, n: u32) {
+        self.bits >>= n;
+        self.count -= n;
+    }
+
+    // stored blocks and the trailer start on a whole byte
+    fn align(&mut self) {
+        self.consume(self.count % 8);
+    }
+
+    // whether the next bytes are these, without using them up. Only called on a byte boundary
+    fn starts_with(&mut self, bytes: &[u8]) -> io::Result<bool> {
+        self.refill()?;
+        let available = (self.count / 8) as usize;
+        Ok(available >= bytes.len()
+            && bytes
+                .iter()
+                .enumerate()
+                .all(|(i, &byte)| (self.bits >> (8 * i)) as u8 == byte))
+    }
+}
+
+// a canonical Huffman code, decoded with a table for the short codes and a bit at a time for the rest
+struct Huffman {
+    // how many codes there are of each length
+    counts: [u16; 16],
+    // the symbols in the order of their codes
+    symbols: Vec<u16>,
+    // indexed by the next FAST_BITS bits: the symbol << 4 | the code's length, 0 when the code is longer
+    fast: Vec<u16>,
+}
+
+const FAST_BITS: u32 = 9;
+
+impl Huffman {
+    // from the length of every symbol's code, 0 for symbols that don't have one
+    fn new(lengths: &[u8]) -> io::Result<Huffman> {
+        let mut counts = [0u16; 16];
+        for &length in lengths {
+            counts[length as usize] += 1;
+        }
+        counts[0] = 0;
+        // more codes of a length than there's room for can't be decoded. Fewer is fine, deflate sends
+        // a single distance code when a block only has one distance
+        let mut left = 1i32;
+        for &count in &counts[1..] {
+            left = left * 2 - count as i32;
+            if left < 0 {
+                return Err(GzipError::Corrupt("over-subscribed Huffman code").into());
+            }
+        }
+
+        let mut offsets = [0u16; 16];
+        for length in 1..15 {
+            offsets[length + 1] = offsets[length] + counts[length];
+        }
+        let mut symbols = vec![0; offsets[15] as usize + counts[15] as usize];
+        let mut fast = vec![0; 1 << FAST_BITS];
+        let mut next_code = [0u32; 16];
+        let mut code = 0;
+        for length in 1..16 {
+            code = (code + counts[length - 1] as u32) << 1;
+            next_code[length] = code;
+        }
+        for (symbol, &length) in lengths.iter().enumerate() {
+            let length = length as usize;
+            if length == 0 {
+                continue;
+            }
+            symbols[offsets[length] as usize] = symbol as u16;
+            offsets[length] += 1;
+            let code = next_code[length];
+            next_code[length] += 1;
+            if length as u32 <= FAST_BITS {
+                // the code goes into the stream first bit first, so it's looked up reversed
+                let reversed = code.reverse_bits() >> (32 - length);
+                let entry = (symbol as u16) << 4 | length as u16;
+                for high in 0..1 << (FAST_BITS - length as u32) {
+                    fast[(reversed | high << length) as usize] = entry;
+                }
+            }
+        }
+        Ok(Huffman {
+            counts,
+            symbols,
+            fast,
+        })
+    }
+
+    fn decode<R: BufRead>(&self, bits: &mut Bits<R>) -> io::Result<u16> {
+        if bits.count < FAST_BITS {
+            bits.refill()?;
+        }
+        if bits.count >= FAST_BITS {
+            let entry = self.fast[(bits.bits & ((1 << FAST_BITS) - 1)) as usize];
+            if entry != 0 {
+                bits.consume(entry as u32 & 15);
+                return Ok(entry >> 4);
+            }
+        }
+        // the codes of each length are consecutive numbers, after all the shorter ones
+        let (mut code, mut first, mut index) = (0, 0, 0);
+        for &count in &self.counts[1..] {
+            code |= bits.get(1)? as i32;
+            let count = count as i32;
+            if code - first < count {
+                return Ok(self.symbols[(index + code - first) as usize]);
+            }
+            index += count;
+            first = (first + count) << 1;
+            code <<= 1;
+        }
+        Err(GzipError::Corrupt("invalid Huffman code").into())
+    }
+}
+
+// the CRC-32 gzip uses, a byte at a time from a table
+fn crc32(crc: u32, bytes: &[u8]) -> u32 {
+    const TABLE: [u32; 256] = {
+        let mut table = [0; 256];
+        let mut i = 0;
+        while i < 256 {
+            let mut c = i as u32;
+            let mut bit = 0;
+            while bit < 8 {
+                c = if c & 1 != 0 {
+                    0xEDB88320 ^ (c >> 1)
+                } else {
+                    c >> 1
+                };
+                bit += 1;
+            }
+            table[i] = c;
+            i += 1;
+        }
+        table
+    };
+    let mut crc = !crc;
+    for &byte in bytes {
+        crc = TABLE[((crc ^ byte as u32) & 0xFF) as usize] ^ (crc >> 8);
+    }
+    !crc
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn gunzip(bytes: &[u8]) -> io::Result<String> {
+        let mut text = String::new();
+        decompress(Path::new("poem.txt.gz"), bytes)?.read_to_string(&mut text)?;
+        Ok(text)
+    }
+
+    fn gzip_error(e: io::Error) -> GzipError {
+        e.into_inner()
+            .unwrap()
+            .downcast::<GzipError>()
+            .map(|e| *e)
+            .unwrap()
+    }
+
+    // a gzip file with stored blocks, which is easy to make by hand
+    fn stored(text: &[u8]) -> Vec<u8> {
+        let mut gz = vec![0x1F, 0x8B, 8, 0, 0, 0, 0, 0, 0, 0xFF];
+        let mut blocks = text.chunks(7).peekable();
+        while let Some(block) = blocks.next() {
+            gz.push(u8::from(blocks.peek().is_none()));
+            gz.extend((block.len() as u16).to_le_bytes());
+            gz.extend((!(block.len() as u16)).to_le_bytes());
+            gz.extend(block);
+        }
+        gz.extend(crc32(0, text).to_le_bytes());
+        gz.extend((text.len() as u32).to_le_bytes());
+        gz
+    }
+
+    #[test]
+    fn crc_of_the_check_string() {
+        assert_eq!(0xCBF43926, crc32(0, b"123456789"));
+        assert_eq!(0xCBF43926, crc32(crc32(0, b"1234"), b"56789"));
+    }
+
+    #[test]
+    fn unpacks_every_kind_of_block() {
+        // what gzip -9 makes of the poem, a block with its own codes
+        let poem = include_str!("../poem.txt");
+        assert_eq!(poem, gunzip(include_bytes!("../poem.txt.gz")).unwrap());
+        // `echo frog | gzip`, small enough for the fixed codes
+        let frog = b"\x1F\x8B\x08\x00\x00\x00\x00\x00\x02\x03\x4B\x2B\xCA\x4F\xE7\x02\x00\x84\x85\x84\x98\x05\x00\x00\x00";
+        assert_eq!("frog\n", gunzip(frog).unwrap());
+        assert_eq!(poem, gunzip(&stored(poem.as_bytes())).unwrap());
+
+        // members one after the other unpack to one file, and zeros after the last are ignored
+        let mut both = frog.to_vec();
+        both.extend(stored(b"toad\n"));
+        both.extend([0; 16]);
+        assert_eq!("frog\ntoad\n", gunzip(&both).unwrap());
+
+        // one byte at a time
+        let reader = io::BufReader::with_capacity(1, &include_bytes!("../poem.txt.gz")[..]);
+        let mut text = String::new();
+        GzDecoder::new(reader).read_to_string(&mut text).unwrap();
+        assert_eq!(poem, text);
+    }
+
+    #[test]
+    fn broken_files_say_what_is_wrong() {
+        let gz = include_bytes!("../poem.txt.gz");
+        let e = gunzip(&gz[..gz.len() / 2]).unwrap_err();
+        assert_eq!(GzipError::Truncated, gzip_error(e));
+
+        let mut flipped = gz.to_vec();
+        let last = flipped.len() - 5;
+        flipped[last] ^= 1;
+        assert_eq!(
+            GzipError::Checksum,
+            gzip_error(gunzip(&flipped).unwrap_err())
+        );
+
+        let mut reserved = stored(b"frog");
+        reserved[10] = 0b110;
+        let e = gunzip(&reserved).unwrap_err();
+        assert_eq!(GzipError::Corrupt("invalid block type"), gzip_error(e));
+    }
+
+    #[test]
+    fn detects_by_magic_and_by_name() {
+        let open = |name: &str, bytes: &'static [u8]| {
+            let mut text = String::new();
+            decompress(Path::new(name), bytes)?.read_to_string(&mut text)?;
+            Ok::<_, io::Error>(text)
+        };
+        let frog = b"\x1F\x8B\x08\x00\x00\x00\x00\x00\x02\x03\x4B\x2B\xCA\x4F\xE7\x02\x00\x84\x85\x84\x98\x05\x00\x00\x00";
+        assert_eq!("frog\n", open("frog.log.1", frog).unwrap());
+        assert_eq!("frog\n", open("frog.txt", b"frog\n").unwrap());
+        let e = open("frog.log.gz", b"frog\n").unwrap_err();
+        assert_eq!(GzipError::NotGzip, gzip_error(e));
+        let e = open("frog.log", b"BZh91AY&SY").unwrap_err();
+        assert_eq!(GzipError::Unsupported("bzip2"), gzip_error(e));
+    }
+}
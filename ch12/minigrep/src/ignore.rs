@@ -0,0 +1,243 @@
+// .gitignore, .ignore and .git/info/exclude, so walking a repository skips target/ and friends
+// the rules work like git's: `#` starts a comment, a leading `!` brings back something an earlier rule
+// ignored, a pattern with a `/` at the start or in the middle only matches relative to the directory the
+// file is in, one without matches a name at any depth, and a trailing `/` only matches directories.
+// The wildcards are the ones from glob.rs. When several rules match the last one wins, deeper files win
+// over the ones further up, and .ignore wins over .gitignore, which wins over .git/info/exclude.
+// An ignored directory isn't walked at all, so nothing inside it can be brought back, just like in git.
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::glob::Glob;
+
+#[derive(Debug, Clone)]
+struct Rule {
+    glob: Glob,
+    // `!pattern`, matching paths are not ignored after all
+    negated: bool,
+    // `pattern/`
+    dir_only: bool,
+    // has a `/` before the end, so it's matched against the whole path instead of just the name
+    anchored: bool,
+}
+
+// the rules from one file
+#[derive(Debug, Clone)]
+pub struct Gitignore {
+    // the directory the rules are relative to, the way the walk spells it
+    dir: PathBuf,
+    // for files above where the walk started, the path from their directory down to dir
+    prefix: String,
+    rules: Vec<Rule>,
+}
+
+impl Gitignore {
+    pub fn parse(dir: &Path, contents: &str) -> Gitignore {
+        let rules = contents.lines().filter_map(parse_rule).collect();
+        Gitignore {
+            dir: dir.to_path_buf(),
+            prefix: String::new(),
+            rules,
+        }
+    }
+
+    // None when the file doesn't exist or has no rules in it, an unreadable ignore file is the same as none
+    fn read(dir: &Path, file: &Path) -> Option<Gitignore> {
+        let contents = fs::read(file).ok()?;
+        let ignore = Gitignore::parse(dir, &String::from_utf8_lossy(&contents));
+        (!ignore.rules.is_empty()).then_some(ignore)
+    }
+
+    // Some(true) when path is ignored, Some(false) when a `!` rule says it isn't, None when no rule cares
+    pub fn matched(&self, path: &Path, is_dir: bool) -> Option<bool> {
+        let relative = path.strip_prefix(&self.dir).ok()?;
+        let mut parts: Vec<String> = relative
+            .components()
+            .map(|component| component.as_os_str().to_string_lossy().into_owned())
+            .collect();
+        let name = parts.last()?.clone();
+        if !self.prefix.is_empty() {
+            parts.insert(0, self.prefix.clone());
+        }
+        let full = parts.join("/");
+
+        self.rules.iter().rev().find_map(|rule| {
+            if rule.dir_only && !is_dir {
+                return None;
+            }
+            let matched = if rule.anchored {
+                rule.glob.matches(&full)
+            } else {
+                rule.glob.matches(&name)
+            };
+            matched.then_some(!rule.negated)
+        })
+    }
+}
+
+fn parse_rule(line: &str) -> Option<Rule> {
+    // trailing spaces don't count unless they're escaped
+    let mut line = line.trim_end_matches('\r');
+    while line.ends_with(' ') && !line.ends_with("\\ ") {
+        line = &line[..line.len() - 1];
+    }
+    if line.is_empty() || line.starts_with('#') {
+        return None;
+    }
+    let (negated, line) = match line.strip_prefix('!') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let (dir_only, line) = match line.strip_suffix('/') {
+        Some(rest) => (true, rest),
+        None => (false, line),
+    };
+    let anchored = line.contains('/');
+    let line = line.strip_prefix('/').unwrap_or(line);
+    if line.is_empty() {
+        return None;
+    }
+    Some(Rule {
+        glob: Glob::new(line),
+        negated,
+        dir_only,
+        anchored,
+    })
+}
+
+// every ignore file that applies inside one directory: its own and those of the directories above it
+// each directory the walk goes into adds a link, the children share their parent's chain
+#[derive(Debug)]
+pub struct Ignores {
+    parent: Option<Rc<Ignores>>,
+    // the most important first
+    files: Vec<Gitignore>,
+}
+
+impl Ignores {
+    // the rules for the directory the walk starts in. Inside a git repository that includes the ignore files
+    // of the directories above it, up to the top of the repository
+    pub fn root(dir: &Path) -> Rc<Ignores> {
+        let mut files = Ignores::files_in(dir, dir);
+        let canonical = fs::canonicalize(dir).unwrap_or_default();
+        let is_repo = |dir: &Path| dir.join(".git").exists();
+        if !is_repo(&canonical) && canonical.ancestors().any(is_repo) {
+            // the path from each directory above down to dir, so their anchored rules still line up
+            let mut prefix = Vec::new();
+            for (below, ancestor) in canonical.ancestors().zip(canonical.ancestors().skip(1)) {
+                if let Some(name) = below.file_name() {
+                    prefix.insert(0, name.to_string_lossy().into_owned());
+                }
+                for mut ignore in Ignores::files_in(ancestor, dir) {
+                    ignore.prefix = prefix.join("/");
+                    files.push(ignore);
+                }
+                if is_repo(ancestor) {
+                    break;
+                }
+            }
+        }
+        Rc::new(Ignores {
+            parent: None,
+            files,
+        })
+    }
+
+    // the rules for a directory inside the walk
+    pub fn child(self: &Rc<Ignores>, dir: &Path) -> Rc<Ignores> {
+        let files = Ignores::files_in(dir, dir);
+        if files.is_empty() {
+            return Rc::clone(self);
+        }
+        Rc::new(Ignores {
+            parent: Some(Rc::clone(self)),
+            files,
+        })
+    }
+
+    // the ignore files in dir (read from on_disk, named relative to dir), most important first
+    fn files_in(on_disk: &Path, dir: &Path) -> Vec<Gitignore> {
+        [
+            on_disk.join(".ignore"),
+            on_disk.join(".gitignore"),
+            on_disk.join(".git/info/exclude"),
+        ]
+        .iter()
+        .filter_map(|file| Gitignore::read(dir, file))
+        .collect()
+    }
+
+    pub fn is_ignored(&self, path: &Path, is_dir: bool) -> bool {
+        let mut ignores = Some(self);
+        while let Some(current) = ignores {
+            for file in &current.files {
+                if let Some(ignored) = file.matched(path, is_dir) {
+                    return ignored;
+                }
+            }
+            ignores = current.parent.as_deref();
+        }
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn ignored(rules: &str, path: &str, is_dir: bool) -> bool {
+        let ignore = Gitignore::parse(Path::new("repo"), rules);
+        ignore.matched(&Path::new("repo").join(path), is_dir) == Some(true)
+    }
+
+    #[test]
+    fn git_rules() {
+        let rules = "\
+# build output
+target/
+*.log
+!keep.log
+/notes.txt
+docs/*.html
+\\#hash
+";
+        assert!(ignored(rules, "target", true));
+        assert!(ignored(rules, "sub/target", true));
+        // only directories
+        assert!(!ignored(rules, "target", false));
+        assert!(ignored(rules, "a/b/debug.log", false));
+        assert!(!ignored(rules, "a/b/keep.log", false));
+        // anchored to the directory of the ignore file
+        assert!(ignored(rules, "notes.txt", false));
+        assert!(!ignored(rules, "sub/notes.txt", false));
+        assert!(ignored(rules, "docs/index.html", false));
+        assert!(!ignored(rules, "sub/docs/index.html", false));
+        assert!(!ignored(rules, "docs/api/index.html", false));
+        assert!(ignored(rules, "#hash", false));
+        assert!(!ignored(rules, "src/lib.rs", false));
+    }
+
+    #[test]
+    fn double_stars() {
+        assert!(ignored("**/cache", "a/b/cache", true));
+        assert!(ignored("logs/**/*.gz", "logs/2024/01/app.gz", false));
+        assert!(!ignored("logs/**/*.gz", "other/logs/app.gz", false));
+    }
+
+    #[test]
+    fn deeper_files_win() {
+        let outer = Rc::new(Ignores {
+            parent: None,
+            files: vec![Gitignore::parse(Path::new("repo"), "*.txt\n")],
+        });
+        let inner = Ignores {
+            parent: Some(outer),
+            files: vec![Gitignore::parse(Path::new("repo/keep"), "!*.txt\n")],
+        };
+        assert!(inner.is_ignored(Path::new("repo/a.txt"), false));
+        assert!(!inner.is_ignored(Path::new("repo/keep/a.txt"), false));
+        assert!(!inner.is_ignored(Path::new("repo/a.rs"), false));
+    }
+}
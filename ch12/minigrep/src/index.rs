@@ -0,0 +1,660 @@
+// `minigrep index DIR` and `minigrep query PATTERN DIR`, for searching the same big tree over and over
+// the index remembers every trigram (three bytes in a row) in every file. A line can only contain "frog"
+// if its file has "fro" and "rog" in it, so a query only has to read the files that have all the trigrams
+// of its pattern, the normal search then checks them for real. Regexes use the plain runs of characters
+// every match needs, see regex::required. The trigrams are of the case folded text, so the same index
+// works with and without -i. Patterns shorter than three bytes, --fuzzy, -v, -c and -L need every file.
+// UTF-16 files are indexed the way they're searched, decoded. A gzip file is indexed by what it unpacks to,
+// which only helps -z, without it the compressed bytes are searched and anything could be in those.
+// Running index again only reads the files whose size or modification time changed since last time, and
+// drops the ones that are gone. A query also checks that nothing changed under it: a file that did is
+// searched whether the index says so or not, and so is every file that's new since the index was built,
+// with a warning that it's time to run index again.
+//
+// the index is one file, DIR/.minigrep-index, every number in it is a LEB128 varint:
+//   MAGIC, the number of files, then per file: path length, path (relative to DIR, `/` between the parts),
+//   modification time (seconds, nanoseconds), size, flags
+//   the number of trigrams, then per trigram in order: the three bytes, length of its list, the list. The
+//   list has the ids (the file's position above) of the files with that trigram, each as the gap from the
+//   one before
+
+use std::collections::HashMap;
+use std::fs::{self, File, Metadata};
+use std::io::{self, BufRead, BufReader};
+use std::ops::Range;
+use std::path::Path;
+use std::time::UNIX_EPOCH;
+
+use crate::casefold;
+use crate::encoding;
+use crate::error::MinigrepError;
+use crate::gzip;
+use crate::replace;
+use crate::walk::{Walk, WalkOptions};
+use crate::{report, Summary};
+
+pub const INDEX_FILE: &str = ".minigrep-index";
+const MAGIC: &[u8] = b"minigrep index 1\n";
+// a file that couldn't be unpacked or decoded, it's searched whatever the pattern
+const ALWAYS: u8 = 1;
+// a gzip file, its trigrams are of what it unpacks to
+const GZIP: u8 = 2;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Entry {
+    path: String,
+    modified: (u64, u32),
+    size: u64,
+    flags: u8,
+}
+
+impl Entry {
+    fn new(path: String, metadata: &Metadata) -> Entry {
+        let modified = metadata
+            .modified()
+            .ok()
+            .and_then(|time| time.duration_since(UNIX_EPOCH).ok())
+            .map_or((0, 0), |since| (since.as_secs(), since.subsec_nanos()));
+        Entry {
+            path,
+            modified,
+            size: metadata.len(),
+            flags: 0,
+        }
+    }
+
+    fn unchanged(&self, metadata: &Metadata) -> bool {
+        let now = Entry::new(String::new(), metadata);
+        (self.modified, self.size) == (now.modified, now.size)
+    }
+}
+
+#[derive(Debug, Default)]
+pub struct Index {
+    files: Vec<Entry>,
+    // every trigram in order, with where its encoded list of file ids is in lists
+    postings: Vec<(u32, Range<usize>)>,
+    lists: Vec<u8>,
+}
+
+// what `minigrep query` should search
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Candidates {
+    pub paths: Vec<String>,
+    // how many of those the index doesn't know about yet
+    pub unindexed: usize,
+}
+
+// what `minigrep index` did
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Updated {
+    pub files: usize,
+    // how many had to be read because they're new or changed
+    pub read: usize,
+    pub removed: usize,
+}
+
+impl Index {
+    pub fn load(path: &Path) -> Result<Index, MinigrepError> {
+        let bytes = fs::read(path).map_err(|e| MinigrepError::reading(path, e))?;
+        Index::parse(bytes).ok_or_else(|| MinigrepError::BadIndex {
+            path: path.to_path_buf(),
+        })
+    }
+
+    // the lists stay where they are in the file, a query only ever decodes a few of them
+    fn parse(file: Vec<u8>) -> Option<Index> {
+        let mut bytes = file.strip_prefix(MAGIC)?;
+        let mut files = Vec::new();
+        for _ in 0..read_varint(&mut bytes)? {
+            let path = read_bytes(&mut bytes)?;
+            files.push(Entry {
+                path: String::from_utf8(path.to_vec()).ok()?,
+                modified: (read_varint(&mut bytes)?, read_varint(&mut bytes)? as u32),
+                size: read_varint(&mut bytes)?,
+                flags: *read_n(&mut bytes, 1)?.first()?,
+            });
+        }
+        let mut postings = Vec::new();
+        for _ in 0..read_varint(&mut bytes)? {
+            let &[a, b, c] = read_n(&mut bytes, 3)? else {
+                return None;
+            };
+            let list = read_bytes(&mut bytes)?;
+            let start = list.as_ptr() as usize - file.as_ptr() as usize;
+            postings.push((trigram([a, b, c]), start..start + list.len()));
+        }
+        if !bytes.is_empty() || !postings.is_sorted_by_key(|&(trigram, _)| trigram) {
+            return None;
+        }
+        Some(Index {
+            files,
+            postings,
+            lists: file,
+        })
+    }
+
+    fn to_bytes(&self) -> Vec<u8> {
+        let mut bytes = MAGIC.to_vec();
+        write_varint(&mut bytes, self.files.len() as u64);
+        for entry in &self.files {
+            write_varint(&mut bytes, entry.path.len() as u64);
+            bytes.extend_from_slice(entry.path.as_bytes());
+            write_varint(&mut bytes, entry.modified.0);
+            write_varint(&mut bytes, entry.modified.1 as u64);
+            write_varint(&mut bytes, entry.size);
+            bytes.push(entry.flags);
+        }
+        write_varint(&mut bytes, self.postings.len() as u64);
+        for (trigram, list) in &self.postings {
+            bytes.extend_from_slice(&trigram.to_be_bytes()[1..]);
+            write_varint(&mut bytes, list.len() as u64);
+            bytes.extend_from_slice(&self.lists[list.clone()]);
+        }
+        bytes
+    }
+
+    // the ids of the files that have every one of trigrams
+    fn files_with(&self, trigrams: &[u32]) -> Vec<usize> {
+        let mut found: Option<Vec<usize>> = None;
+        for trigram in trigrams {
+            let ids = match self
+                .postings
+                .binary_search_by_key(trigram, |&(trigram, _)| trigram)
+            {
+                Ok(i) => decode(&self.lists[self.postings[i].1.clone()]),
+                Err(_) => Vec::new(),
+            };
+            found = Some(match found {
+                None => ids,
+                Some(found) => found
+                    .into_iter()
+                    .filter(|id| ids.binary_search(id).is_ok())
+                    .collect(),
+            });
+        }
+        found.unwrap_or_default()
+    }
+}
+
+// `minigrep index DIR`, builds the index or brings it up to date
+pub fn update(
+    dir: &Path,
+    options: WalkOptions,
+    summary: &mut Summary,
+) -> Result<Updated, MinigrepError> {
+    let index_path = dir.join(INDEX_FILE);
+    // one that can't be read, or is from another version of minigrep, is built again from scratch
+    let old = Index::load(&index_path).unwrap_or_default();
+    let old_ids: HashMap<&str, usize> = old
+        .files
+        .iter()
+        .enumerate()
+        .map(|(id, entry)| (entry.path.as_str(), id))
+        .collect();
+
+    let mut updated = Updated::default();
+    let mut files = Vec::new();
+    // each file's trigrams, the ones that didn't change are filled in from the old index below
+    let mut trigrams: Vec<Vec<u32>> = Vec::new();
+    let mut kept: HashMap<usize, usize> = HashMap::new();
+    let mut still_there = 0;
+    let mut seen = Seen::new();
+    for file in Walk::new(dir, options) {
+        let file = match file {
+            Ok(file) => file,
+            Err(e) => {
+                report(summary, e.into());
+                continue;
+            }
+        };
+        // the index itself, and the temporary file it's written to
+        if file
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().contains(INDEX_FILE))
+        {
+            continue;
+        }
+        let Some(relative) = relative_path(dir, &file) else {
+            let e = io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "path isn't UTF-8, can't index it",
+            );
+            report(
+                summary,
+                MinigrepError::Io {
+                    path: file,
+                    source: e,
+                },
+            );
+            continue;
+        };
+        let metadata = match fs::metadata(&file) {
+            Ok(metadata) => metadata,
+            Err(e) => {
+                report(summary, MinigrepError::reading(file, e));
+                continue;
+            }
+        };
+        let id = files.len();
+        let old_id = old_ids.get(relative.as_str()).copied();
+        still_there += usize::from(old_id.is_some());
+        match old_id {
+            Some(old_id) if old.files[old_id].unchanged(&metadata) => {
+                files.push(old.files[old_id].clone());
+                trigrams.push(Vec::new());
+                kept.insert(old_id, id);
+            }
+            _ => {
+                let mut entry = Entry::new(relative, &metadata);
+                match read_trigrams(&file, &mut seen) {
+                    Ok((flags, found)) => {
+                        entry.flags = flags;
+                        trigrams.push(found);
+                    }
+                    Err(e) => {
+                        report(summary, MinigrepError::reading(file, e));
+                        continue;
+                    }
+                }
+                files.push(entry);
+                updated.read += 1;
+            }
+        }
+    }
+    updated.files = files.len();
+    updated.removed = old.files.len() - still_there;
+
+    for (trigram, list) in &old.postings {
+        for old_id in decode(&old.lists[list.clone()]) {
+            if let Some(&id) = kept.get(&old_id) {
+                trigrams[id].push(*trigram);
+            }
+        }
+    }
+    let mut postings: HashMap<u32, (usize, Vec<u8>)> = HashMap::new();
+    for (id, found) in trigrams.iter().enumerate() {
+        for &trigram in found {
+            let (next, list) = postings.entry(trigram).or_default();
+            write_varint(list, (id - *next) as u64);
+            *next = id + 1;
+        }
+    }
+    // in order, so the same tree always gives the same index
+    let mut sorted: Vec<(u32, Vec<u8>)> = postings
+        .into_iter()
+        .map(|(trigram, (_, list))| (trigram, list))
+        .collect();
+    sorted.sort_unstable_by_key(|&(trigram, _)| trigram);
+    let mut index = Index {
+        files,
+        ..Index::default()
+    };
+    for (trigram, list) in sorted {
+        let start = index.lists.len();
+        index.lists.extend_from_slice(&list);
+        index.postings.push((trigram, start..index.lists.len()));
+    }
+    replace::write_atomically(&index_path, &index.to_bytes()).map_err(|source| {
+        MinigrepError::Io {
+            path: index_path,
+            source,
+        }
+    })?;
+    Ok(updated)
+}
+
+// `minigrep query`, the files in dir that could have a match, in the order a search of dir would go
+// through them. required is what Matcher::required says, None to get every file. decompress is -z
+pub fn candidates(
+    dir: &Path,
+    required: Option<Vec<Vec<String>>>,
+    decompress: bool,
+    options: WalkOptions,
+    summary: &mut Summary,
+) -> Result<Candidates, MinigrepError> {
+    let index_path = dir.join(INDEX_FILE);
+    if !index_path.exists() {
+        let dir = dir.display();
+        return Err(format!("no index in {dir}, run 'minigrep index {dir}' first").into());
+    }
+    let index = Index::load(&index_path)?;
+
+    let mut wanted = vec![required.is_none(); index.files.len()];
+    for strings in required.unwrap_or_default() {
+        let mut trigrams: Vec<u32> = strings
+            .iter()
+            .flat_map(|string| string.as_bytes().windows(3))
+            .map(|bytes| trigram([bytes[0], bytes[1], bytes[2]]))
+            .collect();
+        trigrams.sort_unstable();
+        trigrams.dedup();
+        if trigrams.is_empty() {
+            // nothing to go on, this pattern could be in any file
+            wanted.fill(true);
+            break;
+        }
+        for id in index.files_with(&trigrams) {
+            wanted[id] = true;
+        }
+    }
+
+    // the tree is walked like a search of dir would, so a file the index doesn't have isn't missed and
+    // one that's gone since isn't looked for
+    let ids: HashMap<&str, usize> = index
+        .files
+        .iter()
+        .enumerate()
+        .map(|(id, entry)| (entry.path.as_str(), id))
+        .collect();
+    let mut candidates = Candidates::default();
+    for file in Walk::new(dir, options) {
+        let file = match file {
+            Ok(file) => file,
+            Err(e) => {
+                report(summary, e.into());
+                continue;
+            }
+        };
+        if file
+            .file_name()
+            .is_some_and(|name| name.to_string_lossy().contains(INDEX_FILE))
+        {
+            continue;
+        }
+        let path = file.to_string_lossy().into_owned();
+        let id = relative_path(dir, &file).and_then(|relative| ids.get(relative.as_str()).copied());
+        let Some(id) = id else {
+            candidates.unindexed += 1;
+            candidates.paths.push(path);
+            continue;
+        };
+        let entry = &index.files[id];
+        let always = entry.flags & ALWAYS != 0 || (entry.flags & GZIP != 0 && !decompress);
+        // one that can't be looked at is left to the search to complain about
+        let changed = fs::metadata(&file).map_or(true, |metadata| !entry.unchanged(&metadata));
+        if wanted[id] || always || changed {
+            candidates.paths.push(path);
+        }
+    }
+    Ok(candidates)
+}
+
+fn relative_path(dir: &Path, file: &Path) -> Option<String> {
+    let parts: Option<Vec<&str>> = file
+        .strip_prefix(dir)
+        .ok()?
+        .components()
+        .map(|part| part.as_os_str().to_str())
+        .collect();
+    Some(parts?.join("/"))
+}
+
+fn trigram(bytes: [u8; 3]) -> u32 {
+    u32::from_be_bytes([0, bytes[0], bytes[1], bytes[2]])
+}
+
+// which trigrams a file has turned up so far, one bit for each of the 2^24
+struct Seen {
+    bits: Vec<u64>,
+    found: Vec<u32>,
+}
+
+impl Seen {
+    fn new() -> Seen {
+        Seen {
+            bits: vec![0; (1 << 24) / 64],
+            found: Vec::new(),
+        }
+    }
+
+    fn add(&mut self, trigram: u32) {
+        let (word, bit) = (trigram as usize / 64, 1 << (trigram % 64));
+        if self.bits[word] & bit == 0 {
+            self.bits[word] |= bit;
+            self.found.push(trigram);
+        }
+    }
+
+    // the trigrams found, sorted, and forgets them for the next file
+    fn take(&mut self) -> Vec<u32> {
+        for &trigram in &self.found {
+            self.bits[trigram as usize / 64] = 0;
+        }
+        let mut found = std::mem::take(&mut self.found);
+        found.sort_unstable();
+        found
+    }
+}
+
+// the trigrams of a file's case folded text, after unpacking and decoding it the way the search would,
+// and the flags for its entry
+fn read_trigrams(path: &Path, seen: &mut Seen) -> io::Result<(u8, Vec<u32>)> {
+    let mut reader = BufReader::new(File::open(path)?);
+    let flags = match reader.fill_buf()? {
+        [0x1F, 0x8B, ..] => GZIP,
+        _ => 0,
+    };
+    let read = match flags {
+        GZIP => gzip::decompress(path, reader),
+        _ => Ok(Box::new(reader) as Box<dyn BufRead>),
+    }
+    .and_then(encoding::decode)
+    .and_then(|reader| add_lines(reader, seen));
+    match read {
+        Ok(()) => Ok((flags, seen.take())),
+        // the search will have something to say about this file whatever the pattern is
+        Err(e) if e.kind() == io::ErrorKind::InvalidData => {
+            seen.take();
+            Ok((flags | ALWAYS, Vec::new()))
+        }
+        Err(e) => Err(e),
+    }
+}
+
+// a line at a time, so a huge file doesn't have to fit in memory
+fn add_lines(mut reader: impl BufRead, seen: &mut Seen) -> io::Result<()> {
+    let mut line = Vec::new();
+    // the end of the line before, so a trigram can go over a line break
+    let mut window = Vec::new();
+    loop {
+        line.clear();
+        if reader.read_until(b'\n', &mut line)? == 0 {
+            break;
+        }
+        let text = String::from_utf8_lossy(&line);
+        let folded = if text.is_ascii() {
+            text.to_ascii_lowercase()
+        } else {
+            casefold::fold_str(&text)
+        };
+        window.extend_from_slice(folded.as_bytes());
+        for bytes in window.windows(3) {
+            seen.add(trigram([bytes[0], bytes[1], bytes[2]]));
+        }
+        window.drain(..window.len().saturating_sub(2));
+    }
+    Ok(())
+}
+
+fn write_varint(bytes: &mut Vec<u8>, mut n: u64) {
+    while n >= 0x80 {
+        bytes.push(n as u8 | 0x80);
+        n >>= 7;
+    }
+    bytes.push(n as u8);
+}
+
+fn read_varint(bytes: &mut &[u8]) -> Option<u64> {
+    let mut n = 0u64;
+    for shift in (0..64).step_by(7) {
+        let (&byte, rest) = bytes.split_first()?;
+        *bytes = rest;
+        n |= ((byte & 0x7F) as u64) << shift;
+        if byte < 0x80 {
+            return Some(n);
+        }
+    }
+    None
+}
+
+fn read_n<'a>(bytes: &mut &'a [u8], n: usize) -> Option<&'a [u8]> {
+    if bytes.len() < n {
+        return None;
+    }
+    let (read, rest) = bytes.split_at(n);
+    *bytes = rest;
+    Some(read)
+}
+
+// a length, then that many bytes
+fn read_bytes<'a>(bytes: &mut &'a [u8]) -> Option<&'a [u8]> {
+    let n = read_varint(bytes)?;
+    read_n(bytes, usize::try_from(n).ok()?)
+}
+
+// the file ids in a trigram's list
+fn decode(mut list: &[u8]) -> Vec<usize> {
+    let mut ids = Vec::new();
+    let mut next = 0;
+    while let Some(gap) = read_varint(&mut list) {
+        let id = next + gap as usize;
+        ids.push(id);
+        next = id + 1;
+    }
+    ids
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::matcher::Matcher;
+    use crate::regex::Bounds;
+    use std::env;
+    use std::thread;
+    use std::time::Duration;
+
+    fn query(dir: &Path, matcher: &Matcher) -> Vec<String> {
+        let mut summary = Summary::default();
+        let found = candidates(
+            dir,
+            matcher.required(),
+            false,
+            WalkOptions::default(),
+            &mut summary,
+        )
+        .unwrap();
+        found
+            .paths
+            .iter()
+            .map(|path| {
+                Path::new(path)
+                    .strip_prefix(dir)
+                    .unwrap()
+                    .display()
+                    .to_string()
+            })
+            .collect()
+    }
+
+    #[test]
+    fn varints_and_lists() {
+        let mut bytes = Vec::new();
+        for n in [0, 1, 127, 128, 300, u64::MAX] {
+            write_varint(&mut bytes, n);
+        }
+        let mut read = bytes.as_slice();
+        let numbers: Vec<u64> = (0..6).map_while(|_| read_varint(&mut read)).collect();
+        assert_eq!(vec![0, 1, 127, 128, 300, u64::MAX], numbers);
+        assert!(read.is_empty());
+
+        // the gaps from one id to the next
+        assert_eq!(vec![0, 1, 5, 200], decode(&[0, 0, 3, 0xC2, 0x01]));
+    }
+
+    #[test]
+    fn narrows_down_and_updates() {
+        let root = env::temp_dir().join(format!("minigrep-index-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("docs")).unwrap();
+        fs::write(root.join("poem.txt"), include_str!("../poem.txt")).unwrap();
+        fs::write(
+            root.join("docs/rust.md"),
+            "Rust:\nsafe, fast, productive.\n",
+        )
+        .unwrap();
+        fs::write(root.join("docs/tape.md"), "Duct tape.\nTrust me.\n").unwrap();
+
+        let mut summary = Summary::default();
+        let updated = update(&root, WalkOptions::default(), &mut summary).unwrap();
+        assert_eq!(
+            Updated {
+                files: 3,
+                read: 3,
+                removed: 0
+            },
+            updated
+        );
+
+        let all = vec!["docs/rust.md", "docs/tape.md", "poem.txt"];
+        assert_eq!(
+            vec!["poem.txt"],
+            query(&root, &Matcher::literal("frog", false))
+        );
+        // the trigrams are case folded
+        assert_eq!(
+            vec!["docs/rust.md"],
+            query(&root, &Matcher::literal("RUST:", false))
+        );
+        assert_eq!(
+            vec!["docs/rust.md", "docs/tape.md"],
+            query(&root, &Matcher::literal("ust", true))
+        );
+        let either = Matcher::literals(&["frog", "tape"], false, Bounds::Anywhere);
+        assert_eq!(vec!["docs/tape.md", "poem.txt"], query(&root, &either));
+        let regex = Matcher::Regex(crate::regex::Regex::new(r"pro\w+ive").unwrap());
+        assert_eq!(vec!["docs/rust.md"], query(&root, &regex));
+        // too short to narrow anything down
+        assert_eq!(all, query(&root, &Matcher::literal("to", false)));
+        assert!(query(&root, &Matcher::literal("toad", false)).is_empty());
+
+        // a changed file can match before the index knows about it
+        thread::sleep(Duration::from_millis(20));
+        fs::write(root.join("docs/tape.md"), "Duct tape.\nA toad.\n").unwrap();
+        fs::remove_file(root.join("docs/rust.md")).unwrap();
+        assert_eq!(
+            vec!["docs/tape.md"],
+            query(&root, &Matcher::literal("toad", false))
+        );
+        // and so can a new one
+        fs::write(root.join("docs/toad.md"), "A toad.\n").unwrap();
+        assert_eq!(
+            vec!["docs/tape.md", "docs/toad.md"],
+            query(&root, &Matcher::literal("toad", false))
+        );
+        let found = candidates(&root, None, false, WalkOptions::default(), &mut summary).unwrap();
+        assert_eq!(1, found.unindexed);
+        fs::remove_file(root.join("docs/toad.md")).unwrap();
+
+        let updated = update(&root, WalkOptions::default(), &mut summary).unwrap();
+        assert_eq!(
+            Updated {
+                files: 2,
+                read: 1,
+                removed: 1
+            },
+            updated
+        );
+        assert_eq!(
+            vec!["docs/tape.md"],
+            query(&root, &Matcher::literal("toad", false))
+        );
+        assert_eq!(
+            vec!["poem.txt"],
+            query(&root, &Matcher::literal("frog", false))
+        );
+        assert_eq!(0, summary.errors);
+
+        fs::remove_dir_all(&root).unwrap();
+    }
+}
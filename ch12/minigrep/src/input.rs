@@ -0,0 +1,94 @@
+// turns the FILE arguments into the list of things to search, in the order they were given
+// an argument can be `-` for standard input, a file, a directory to walk, or a glob to expand
+
+use std::path::{Path, PathBuf};
+
+use crate::error::MinigrepError;
+use crate::glob::{self, Expand};
+use crate::walk::{Walk, WalkOptions};
+use crate::STDIN;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum Input {
+    Stdin,
+    File(PathBuf),
+}
+
+// the iterator behind inputs(), a directory or glob is expanded one file at a time as it's needed
+pub struct Inputs<'a> {
+    paths: std::slice::Iter<'a, String>,
+    options: WalkOptions,
+    current: Option<Expansion>,
+}
+
+enum Expansion {
+    Walk(Walk),
+    Glob(Expand),
+}
+
+pub fn inputs(paths: &[String], options: WalkOptions) -> Inputs<'_> {
+    Inputs {
+        paths: paths.iter(),
+        options,
+        current: None,
+    }
+}
+
+impl Iterator for Inputs<'_> {
+    type Item = Result<Input, MinigrepError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        loop {
+            let next = match &mut self.current {
+                Some(Expansion::Walk(walk)) => {
+                    walk.next().map(|file| file.map_err(MinigrepError::from))
+                }
+                Some(Expansion::Glob(expand)) => expand.next(),
+                None => None,
+            };
+            if let Some(next) = next {
+                return Some(next.map(Input::File));
+            }
+            self.current = None;
+
+            let arg = self.paths.next()?;
+            let path = Path::new(arg);
+            if arg == STDIN {
+                return Some(Ok(Input::Stdin));
+            } else if path.is_dir() {
+                self.current = Some(Expansion::Walk(Walk::new(path, self.options)));
+            } else if glob::is_glob(arg) && !path.exists() {
+                // a file that really has a * in its name is searched as it is
+                self.current = Some(Expansion::Glob(glob::expand(arg, self.options)));
+            } else {
+                // if it doesn't exist, opening it reports that
+                return Some(Ok(Input::File(path.to_path_buf())));
+            }
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_the_order_they_were_given() {
+        let paths: Vec<String> = ["poem.txt", "-", "missing.txt", "src/gl*.rs"]
+            .iter()
+            .map(|s| s.to_string())
+            .collect();
+        let found: Vec<Input> = inputs(&paths, WalkOptions::default())
+            .map(Result::unwrap)
+            .collect();
+        assert_eq!(
+            vec![
+                Input::File(PathBuf::from("poem.txt")),
+                Input::Stdin,
+                Input::File(PathBuf::from("missing.txt")),
+                Input::File(PathBuf::from("src/glob.rs")),
+            ],
+            found
+        );
+    }
+}
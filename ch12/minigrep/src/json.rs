@@ -0,0 +1,160 @@
+// --json, one JSON object per line for programs that want to read the results
+// every object has a "type" and a "data": a "begin-file" before the first match in a file, a "match" for
+// every selected line, an "end-file" after the file's last match and a "summary" once everything's searched.
+// Files without a match don't show up at all, the summary still counts them.
+// Every submatch says which pattern it matched, which is how -e and -f searches tell their patterns apart,
+// and with --fuzzy how many typos it has. A -U match that runs over several lines has a "last_line_number".
+// Text (paths, lines, submatches, patterns) is written as {"text": "..."} when it's valid UTF-8 and as
+// {"bytes": "<base64>"} when it isn't, so nothing gets mangled on the way through.
+
+use std::fmt::Write;
+use std::path::Path;
+
+use crate::{Match, Summary};
+
+pub fn begin_file(path: &Path) -> String {
+    format!(
+        r#"{{"type":"begin-file","data":{{"path":{}}}}}"#,
+        path_data(path)
+    )
+}
+
+pub fn matched(path: &Path, m: &Match) -> String {
+    let mut submatches = String::new();
+    for (i, range) in m.ranges.iter().enumerate() {
+        if i > 0 {
+            submatches.push(',');
+        }
+        let _ = write!(
+            submatches,
+            r#"{{"match":{},"start":{},"end":{}"#,
+            data(&m.line[range.clone()]),
+            range.start,
+            range.end
+        );
+        if let Some(pattern) = m.patterns.get(i) {
+            let _ = write!(submatches, r#","pattern":{}"#, data(pattern.as_bytes()));
+        }
+        if let Some(distance) = m.distances.get(i) {
+            let _ = write!(submatches, r#","distance":{distance}"#);
+        }
+        submatches.push('}');
+    }
+    // only -U matches can take up more than one line
+    let last_line_number = match m.last_line_number() {
+        last if last > m.line_number => format!(r#","last_line_number":{last}"#),
+        _ => String::new(),
+    };
+    format!(
+        r#"{{"type":"match","data":{{"path":{},"line_number":{}{},"byte_offset":{},"line":{},"submatches":[{}]}}}}"#,
+        path_data(path),
+        m.line_number,
+        last_line_number,
+        m.byte_offset,
+        data(m.line),
+        submatches
+    )
+}
+
+pub fn end_file(path: &Path, matched_lines: usize, matches: usize) -> String {
+    format!(
+        r#"{{"type":"end-file","data":{{"path":{},"matched_lines":{matched_lines},"matches":{matches}}}}}"#,
+        path_data(path)
+    )
+}
+
+pub fn summary(summary: &Summary) -> String {
+    format!(
+        r#"{{"type":"summary","data":{{"files_searched":{},"files_matched":{},"errors":{}}}}}"#,
+        summary.searched, summary.files_matched, summary.errors
+    )
+}
+
+// a path is usually UTF-8, but on unix it can be any bytes at all
+fn path_data(path: &Path) -> String {
+    match path.to_str() {
+        Some(path) => data(path.as_bytes()),
+        None => data(path.as_os_str().as_encoded_bytes()),
+    }
+}
+
+// {"text": ...} or {"bytes": ...}, whichever can hold these bytes without losing any
+pub fn data(bytes: &[u8]) -> String {
+    match std::str::from_utf8(bytes) {
+        Ok(text) => format!(r#"{{"text":{}}}"#, string(text)),
+        Err(_) => format!(r#"{{"bytes":"{}"}}"#, base64(bytes)),
+    }
+}
+
+// a quoted JSON string, with everything JSON doesn't allow as it is escaped
+pub fn string(s: &str) -> String {
+    let mut quoted = String::with_capacity(s.len() + 2);
+    quoted.push('"');
+    for c in s.chars() {
+        match c {
+            '"' => quoted.push_str("\\\""),
+            '\\' => quoted.push_str("\\\\"),
+            '\n' => quoted.push_str("\\n"),
+            '\r' => quoted.push_str("\\r"),
+            '\t' => quoted.push_str("\\t"),
+            c if c < ' ' || c == '\u{7f}' => {
+                let _ = write!(quoted, "\\u{:04x}", c as u32);
+            }
+            c => quoted.push(c),
+        }
+    }
+    quoted.push('"');
+    quoted
+}
+
+// standard base64 with padding, what every JSON reader knows how to decode
+fn base64(bytes: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity(bytes.len().div_ceil(3) * 4);
+    for chunk in bytes.chunks(3) {
+        let n = chunk
+            .iter()
+            .enumerate()
+            .fold(0u32, |n, (i, &b)| n | (b as u32) << (16 - 8 * i));
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(ALPHABET[(n >> (18 - 6 * i) & 63) as usize] as char);
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::ops::Range;
+
+    #[test]
+    fn escapes_what_json_needs_escaped() {
+        assert_eq!(r#""say \"hi\"\\\n\u0007ß""#, string("say \"hi\"\\\n\u{7}ß"));
+        assert_eq!(r#"{"text":"poem.txt"}"#, data(b"poem.txt"));
+        assert_eq!(r#"{"bytes":"/w=="}"#, data(b"\xff"));
+        assert_eq!("", base64(b""));
+        assert_eq!("TWFu", base64(b"Man"));
+        assert_eq!("TWE=", base64(b"Ma"));
+    }
+
+    #[test]
+    fn match_events() {
+        let m = Match {
+            line_number: 2,
+            byte_offset: 6,
+            line: b"safe, fast, productive.",
+            ranges: vec![Range { start: 15, end: 19 }],
+            patterns: vec!["duct"],
+            distances: Vec::new(),
+        };
+        assert_eq!(
+            r#"{"type":"match","data":{"path":{"text":"poem.txt"},"line_number":2,"byte_offset":6,"line":{"text":"safe, fast, productive."},"submatches":[{"match":{"text":"duct"},"start":15,"end":19,"pattern":{"text":"duct"}}]}}"#,
+            matched(Path::new("poem.txt"), &m)
+        );
+    }
+}
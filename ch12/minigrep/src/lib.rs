@@ -1,49 +1,584 @@
 // this file should focus on the logic behind the program
 
 use std::env;
-use std::error::Error;
 use std::fs;
+use std::io::{self, Read, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::thread;
+
+pub mod aho_corasick;
+pub mod casefold;
+pub mod cli;
+pub mod color;
+pub mod encoding;
+pub mod error;
+pub mod fuzzy;
+pub mod glob;
+pub mod gzip;
+pub mod ignore;
+pub mod index;
+pub mod input;
+pub mod json;
+pub mod literal;
+pub mod matcher;
+pub mod parallel;
+pub mod printer;
+pub mod query;
+pub mod regex;
+pub mod replace;
+pub mod searcher;
+pub mod walk;
+
+use cli::Arg;
+use color::{ColorChoice, Colors};
+pub use error::MinigrepError;
+pub use input::{inputs, Input};
+use literal::Finder;
+pub use matcher::{Matcher, MatcherKind};
+pub use printer::{BinaryFiles, OutputMode, PrintOptions, Printer};
+use regex::Bounds;
+pub use replace::Replacement;
+pub use searcher::{CaseMode, Searcher, SearcherBuilder, Sink};
+pub use walk::{Walk, WalkOptions};
+
+// what the command line asked minigrep to do
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Action {
+    Search,
+    Help,
+    Version,
+    // `minigrep index DIR`, build or update DIR's index
+    Index,
+    // `minigrep query PATTERN DIR`, search the files DIR's index says could match
+    Query,
+}
+
+// the file name that means "read standard input instead", and how it shows up in the output
+pub const STDIN: &str = "-";
+pub const STDIN_NAME: &str = "(standard input)";
 
 pub struct Config {
+    pub action: Action,
     pub query: String,
-    pub file_path: String,
+    // -e and -f, when they're used the query is empty and every positional argument is a path
+    // None means there's just the query, Some(empty) is a pattern file with nothing in it, which matches nothing
+    pub patterns: Option<Vec<String>>,
+    // files, directories, globs or `-`, searched in this order
+    pub paths: Vec<String>,
     pub ignore_case: bool,
+    // --smart-case, ignore case unless the query has an upper case letter in it. Wins over ignore_case
+    pub smart_case: bool,
+    // -w, matches can't have a word character right before or after them
+    pub whole_word: bool,
+    // -x, a match has to be the whole line
+    pub whole_line: bool,
+    pub matcher: MatcherKind,
+    // --fuzzy, how many typos a match can have
+    pub fuzzy: Option<usize>,
+    // -v, select the lines that don't match instead
+    pub invert: bool,
+    // --replace, what every match is changed into
+    pub replace: Option<Replacement>,
+    // --in-place, write the replaced lines back to the files instead of printing them
+    pub in_place: bool,
+    // --dry-run, print the changes --in-place would make as a diff
+    pub dry_run: bool,
+    // -z, search what compressed files unpack to
+    pub decompress: bool,
+    // -U, matches can run over more than one line
+    pub multiline: bool,
+    // only used for directories and globs
+    pub walk: WalkOptions,
+    // -j, how many files to search at the same time, None picks based on the machine
+    pub threads: Option<usize>,
+    pub output: PrintOptions,
 }
 
 impl Config {
-    pub fn build(args: &[String]) -> Result<Config, &'static str> {
-        if args.len() < 3 {
-            return Err("not enough arguments");
+    pub fn build(args: &[String]) -> Result<Config, MinigrepError> {
+        Config::build_with(args, true)
+    }
+
+    // with subcommands false, a first argument of index or query is just the pattern
+    fn build_with(all: &[String], subcommands: bool) -> Result<Config, MinigrepError> {
+        // index and query only count as the very first argument, `minigrep -e index` still searches
+        let (mut action, args) = match all.get(1).map(String::as_str) {
+            Some("index") if subcommands => (Action::Index, &all[2..]),
+            Some("query") if subcommands => (Action::Query, &all[2..]),
+            _ => (Action::Search, all.get(1..).unwrap_or_default()),
+        };
+        let mut matcher = MatcherKind::Literal;
+        // the environment variable is only the default, -i and -s win over it
+        let mut ignore_case = env::var("IGNORE_CASE").is_ok();
+        let mut smart_case = false;
+        let mut whole_word = false;
+        let mut whole_line = false;
+        let mut invert = false;
+        let mut fuzzy = None;
+        let mut expression = None;
+        let mut replace = None;
+        let mut in_place = false;
+        let mut dry_run = false;
+        let mut decompress = false;
+        let mut multiline = false;
+        // None means decide later, depending on whether there's more than one file
+        let mut with_filename = None;
+        let mut walk = WalkOptions::default();
+        let mut threads = None;
+        let mut output = PrintOptions::default();
+        let mut color = ColorChoice::default();
+        let mut patterns: Option<Vec<String>> = None;
+        let mut positional = Vec::new();
+
+        // options can go anywhere, everything else is the query followed by the paths
+        for arg in cli::parse(args)? {
+            let (name, value) = match arg {
+                Arg::Positional(arg) => {
+                    positional.push(arg);
+                    continue;
+                }
+                Arg::Option(name, value) => (name, value),
+            };
+            match name {
+                "help" => action = Action::Help,
+                "version" => action = Action::Version,
+                // whichever of -i, -s and -S comes last decides
+                "ignore-case" => (ignore_case, smart_case) = (true, false),
+                "case-sensitive" => (ignore_case, smart_case) = (false, false),
+                "smart-case" => smart_case = true,
+                "regex" => matcher = MatcherKind::Regex,
+                "fuzzy" => fuzzy = Some(cli::number(name, value)?),
+                "query" => expression = value,
+                "regexp" => patterns
+                    .get_or_insert_with(Vec::new)
+                    .push(value.unwrap_or_default()),
+                "file" => patterns
+                    .get_or_insert_with(Vec::new)
+                    .extend(read_patterns(&value.unwrap_or_default())?),
+                "word-regexp" => whole_word = true,
+                "line-regexp" => whole_line = true,
+                "multiline" => multiline = true,
+                "invert-match" => invert = true,
+                "replace" => replace = Some(Replacement::parse(&value.unwrap_or_default())),
+                "in-place" => in_place = true,
+                "dry-run" => dry_run = true,
+                "only-matching" => output.mode = OutputMode::OnlyMatching,
+                "count" => output.mode = OutputMode::Count,
+                "files-with-matches" => output.mode = OutputMode::FilesWithMatches,
+                "files-without-match" => output.mode = OutputMode::FilesWithoutMatch,
+                "json" => output.mode = OutputMode::Json,
+                "binary-files" => {
+                    output.binary_files = BinaryFiles::parse(&value.unwrap_or_default())?
+                }
+                "text" => output.binary_files = BinaryFiles::Text,
+                "skip-binary" => output.binary_files = BinaryFiles::WithoutMatch,
+                "search-zip" => decompress = true,
+                "with-filename" => with_filename = Some(true),
+                "no-filename" => with_filename = Some(false),
+                "color" => color = ColorChoice::parse(&value.unwrap_or_default())?,
+                "show-pattern" => output.show_pattern = true,
+                "line-number" => output.line_number = true,
+                "byte-offset" => output.byte_offset = true,
+                // a column on its own isn't much use for jumping to a location
+                "column" => {
+                    output.column = true;
+                    output.line_number = true;
+                }
+                "after-context" => output.after_context = cli::number(name, value)?,
+                "before-context" => output.before_context = cli::number(name, value)?,
+                "context" => {
+                    let lines = cli::number(name, value)?;
+                    output.before_context = lines;
+                    output.after_context = lines;
+                }
+                "follow" => walk.follow_links = true,
+                "no-follow" => walk.follow_links = false,
+                "max-depth" => walk.max_depth = Some(cli::number(name, value)?),
+                "hidden" => walk.hidden = true,
+                "no-ignore" => walk.no_ignore = true,
+                // -j 0 is the same as not saying anything
+                "threads" => threads = Some(cli::number(name, value)?).filter(|&n| n > 0),
+                _ => unreachable!("option --{name} is in cli::OPTIONS but not handled"),
+            }
         }
 
-        let query = args[1].clone();
-        let file_path = args[2].clone();
+        if expression.is_some() {
+            if matcher == MatcherKind::Regex {
+                return Err("--query can't be used with --regex".into());
+            }
+            if patterns.is_some() {
+                return Err("--query can't be used with -e or -f".into());
+            }
+            if fuzzy.is_some() {
+                return Err("--query can't be used with --fuzzy".into());
+            }
+            if multiline {
+                return Err("--query can't be used with -U".into());
+            }
+            matcher = MatcherKind::Query;
+        }
 
-        let ignore_case = env::var("IGNORE_CASE").is_ok();
+        let mut positional = positional.into_iter();
+        let (query, mut paths) = match (action, expression) {
+            // --help and --version don't need anything else
+            (Action::Help | Action::Version, _) => (String::new(), Vec::new()),
+            (Action::Index, _) => (String::new(), positional.collect()),
+            // --query is the pattern, so every positional argument is a path
+            (Action::Search | Action::Query, Some(expression)) => {
+                (expression, positional.collect())
+            }
+            // with -e or -f the patterns are already known
+            (Action::Search | Action::Query, None) if patterns.is_some() => {
+                (String::new(), positional.collect())
+            }
+            (Action::Search | Action::Query, None) => {
+                let query = positional.next().ok_or("missing PATTERN argument")?;
+                (query, positional.collect())
+            }
+        };
+        // `minigrep index notes.txt`, `minigrep index a/ b/` and `... | minigrep index` are searches for
+        // "index" like they always were, the word is only a subcommand in front of exactly one directory
+        let one_dir = matches!(paths.as_slice(), [dir] if Path::new(dir).is_dir());
+        if matches!(action, Action::Index | Action::Query) && !one_dir {
+            return Config::build_with(all, false);
+        }
+        // no file means standard input, so minigrep can sit at the end of a pipe
+        if paths.is_empty() {
+            paths.push(STDIN.to_string());
+        }
+
+        if fuzzy.is_some() && matcher == MatcherKind::Regex {
+            return Err("--fuzzy can't be used with --regex".into());
+        }
+        if fuzzy.is_some()
+            && patterns
+                .as_ref()
+                .is_some_and(|patterns| patterns.len() != 1)
+        {
+            return Err("--fuzzy needs exactly one pattern".into());
+        }
+        if (in_place || dry_run) && replace.is_none() {
+            return Err("--in-place and --dry-run need --replace".into());
+        }
+        if (in_place || dry_run) && invert {
+            return Err("--in-place and --dry-run can't be used with -v".into());
+        }
+        if (in_place || dry_run) && decompress {
+            return Err("--in-place and --dry-run can't be used with -z".into());
+        }
+        if (in_place || dry_run) && multiline {
+            return Err("--in-place and --dry-run can't be used with -U".into());
+        }
+
+        // like grep, only say which file a line came from when there's more than one file it could be
+        output.with_filename = with_filename.unwrap_or_else(|| searches_many(&paths));
+        // MINIGREP_COLORS is only looked at when there's going to be color, so a typo in it doesn't even
+        // get a warning from `minigrep ... > file`
+        let searches = matches!(action, Action::Search | Action::Query);
+        if searches && output.mode != OutputMode::Json && color.enabled() {
+            output.colors = Some(Colors::from_env());
+        }
 
         Ok(Config {
+            action,
             query,
-            file_path,
+            patterns,
+            paths,
             ignore_case,
+            smart_case,
+            whole_word,
+            whole_line,
+            matcher,
+            fuzzy,
+            invert,
+            replace,
+            in_place,
+            dry_run,
+            decompress,
+            multiline,
+            walk,
+            threads,
+            output,
         })
     }
-}
 
-pub fn run(config: Config) -> Result<(), Box<dyn Error>> {
-    // https://doc.rust-lang.org/reference/expressions/operator-expr.html#the-question-mark-operator
-    let contents = fs::read_to_string(config.file_path)?;
+    // everything about what to look for, the printer gets the rest from output
+    pub fn searcher(&self) -> SearcherBuilder {
+        let case = if self.smart_case {
+            CaseMode::Smart
+        } else if self.ignore_case {
+            CaseMode::Insensitive
+        } else {
+            CaseMode::Sensitive
+        };
+        // -x wins over -w like in grep, a whole line is always whole words
+        let bounds = if self.whole_line {
+            Bounds::Line
+        } else if self.whole_word {
+            Bounds::Word
+        } else {
+            Bounds::Anywhere
+        };
+        let patterns = match &self.patterns {
+            Some(patterns) => patterns.iter().map(String::as_str).collect(),
+            None => vec![self.query.as_str()],
+        };
+        let mut builder = patterns
+            .into_iter()
+            .fold(SearcherBuilder::new(), SearcherBuilder::pattern)
+            .kind(self.matcher)
+            .case(case)
+            .bounds(bounds)
+            .context(self.output.before_context, self.output.after_context)
+            .invert(self.invert)
+            .decompress(self.decompress)
+            .multiline(self.multiline);
+        if let Some(max_distance) = self.fuzzy {
+            builder = builder.fuzzy(max_distance);
+        }
+        if let Some(replacement) = &self.replace {
+            builder = builder.replace(replacement.clone());
+        }
+        builder
+    }
+}
 
-    let results = if config.ignore_case {
-        search_case_insensitive(&config.query, &contents)
+// -f, one pattern per line. `-f -` reads them from standard input
+fn read_patterns(path: &str) -> Result<Vec<String>, MinigrepError> {
+    let contents = if path == STDIN {
+        let mut contents = String::new();
+        io::stdin().read_to_string(&mut contents).map(|_| contents)
     } else {
-        search(&config.query, &contents)
+        fs::read_to_string(path)
     };
+    let contents = contents.map_err(|e| MinigrepError::reading(path, e))?;
+    Ok(lines_with_offsets(&contents)
+        .map(|(_, line)| line.to_string())
+        .collect())
+}
+
+// true unless the paths are a single file (or standard input)
+fn searches_many(paths: &[String]) -> bool {
+    paths.len() > 1
+        || paths.iter().any(|path| {
+            let path_ref = Path::new(path);
+            path_ref.is_dir() || (glob::is_glob(path) && !path_ref.exists())
+        })
+}
+
+// what a search found, main turns this into the exit code
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct Summary {
+    pub matched: bool,
+    // problems with single files that were reported on stderr while the search carried on
+    pub errors: usize,
+    // how many files (or standard input) were searched, and how many of those had a selected line
+    pub searched: usize,
+    pub files_matched: usize,
+}
 
-    for line in results {
-        println!("{line}");
+impl Summary {
+    pub(crate) fn add_file(&mut self, matched: bool) {
+        self.matched |= matched;
+        self.searched += 1;
+        self.files_matched += usize::from(matched);
     }
 
-    Ok(())
+    // the same exit codes as grep: 0 when something matched, 1 when nothing did and 2 when something went wrong
+    pub fn exit_code(&self) -> i32 {
+        if self.errors > 0 {
+            2
+        } else if self.matched {
+            0
+        } else {
+            1
+        }
+    }
+}
+
+pub fn run(mut config: Config) -> Result<Summary, MinigrepError> {
+    let mut summary = Summary::default();
+    match config.action {
+        Action::Help => {
+            print!("{}", cli::help());
+            summary.matched = true;
+            return Ok(summary);
+        }
+        Action::Version => {
+            println!("{}", cli::version());
+            summary.matched = true;
+            return Ok(summary);
+        }
+        Action::Index => {
+            let dir = Path::new(&config.paths[0]);
+            let updated = index::update(dir, config.walk, &mut summary)?;
+            println!(
+                "{}: {} files, {} read, {} removed",
+                dir.join(index::INDEX_FILE).display(),
+                updated.files,
+                updated.read,
+                updated.removed
+            );
+            summary.matched = true;
+            return Ok(summary);
+        }
+        Action::Search | Action::Query => {}
+    }
+
+    // build the matcher before touching the file so a bad pattern is reported right away
+    let searcher = config.searcher().build()?;
+    let matcher = searcher.matcher();
+    if config.action == Action::Query {
+        // these have something to say about the files without a match too
+        let every_file = config.invert
+            || matches!(
+                config.output.mode,
+                OutputMode::Count | OutputMode::FilesWithoutMatch
+            );
+        let required = if every_file { None } else { matcher.required() };
+        let dir = Path::new(&config.paths[0]);
+        let candidates =
+            index::candidates(dir, required, config.decompress, config.walk, &mut summary)?;
+        if candidates.unindexed > 0 {
+            eprintln!(
+                "minigrep: {}: the index doesn't know about {} of the files, run 'minigrep index {}' to add them",
+                dir.display(),
+                candidates.unindexed,
+                dir.display()
+            );
+        }
+        config.paths = candidates.paths;
+    }
+    if let (Some(replacement), true) = (&config.replace, config.in_place || config.dry_run) {
+        return rewrite(&config, matcher, replacement, summary);
+    }
+
+    // locking stdout once is a lot faster than println! locking it for every line
+    let stdout = io::stdout();
+
+    // one file can't be split up between threads, and searching it straight to stdout keeps the output
+    // streaming instead of waiting for the whole file to be searched
+    let threads = config.threads.unwrap_or_else(|| {
+        if searches_many(&config.paths) {
+            thread::available_parallelism().map_or(1, |n| n.get())
+        } else {
+            1
+        }
+    });
+    if threads > 1 {
+        parallel::search_parallel(&config, &searcher, threads, &mut summary, stdout.lock())?;
+    } else {
+        let mut printer = Printer::new(stdout.lock(), config.output);
+        // a missing or unreadable file is reported and the search carries on with the rest
+        for input in inputs(&config.paths, config.walk) {
+            let searched = input.and_then(|input| searcher.search_input(&input, &mut printer));
+            match searched {
+                Ok(matched) => summary.add_file(matched),
+                // not being able to print is the end of the road, a bad file is not
+                Err(e @ MinigrepError::Output(_)) => return Err(e),
+                Err(e) => report(&mut summary, e),
+            }
+        }
+    }
+
+    if config.output.mode == OutputMode::Json {
+        writeln!(stdout.lock(), "{}", json::summary(&summary)).map_err(MinigrepError::Output)?;
+    }
+    Ok(summary)
+}
+
+// --in-place and --dry-run, one file at a time so two files never get written at once
+fn rewrite(
+    config: &Config,
+    matcher: &Matcher,
+    replacement: &Replacement,
+    mut summary: Summary,
+) -> Result<Summary, MinigrepError> {
+    let stdout = io::stdout();
+    let mut out = stdout.lock();
+    for input in inputs(&config.paths, config.walk) {
+        let rewritten = input.and_then(|input| match input {
+            Input::Stdin => Err("standard input can't be rewritten in place".into()),
+            Input::File(path) => {
+                replace::rewrite_file(matcher, replacement, &path, config.dry_run, &mut out)
+            }
+        });
+        match rewritten {
+            Ok(matched) => summary.add_file(matched),
+            Err(e @ MinigrepError::Output(_)) => return Err(e),
+            Err(e) => report(&mut summary, e),
+        }
+    }
+    Ok(summary)
+}
+
+pub(crate) fn report(summary: &mut Summary, e: MinigrepError) {
+    eprintln!("minigrep: {e}");
+    summary.errors += 1;
+}
+
+// a matching line and where it was found, so callers can jump straight to it
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Match<'a> {
+    // counting from 1 like editors do
+    pub line_number: usize,
+    // where the line starts in the searched text, counting from 0
+    pub byte_offset: usize,
+    // the bytes of the line as they are in the input, which don't have to be valid UTF-8. With --replace
+    // it's the line after replacing instead
+    pub line: &'a [u8],
+    // byte ranges inside line of every occurrence of the query
+    pub ranges: Vec<Range<usize>>,
+    // which pattern each of those ranges matched, for when there's more than one
+    pub patterns: Vec<&'a str>,
+    // with --fuzzy, how many typos each of those ranges has. Empty otherwise
+    pub distances: Vec<usize>,
+}
+
+impl Match<'_> {
+    // with -U a match can take up several lines, line then has the newlines between them in it
+    pub fn last_line_number(&self) -> usize {
+        self.line_number + self.line.iter().filter(|&&b| b == b'\n').count()
+    }
+}
+
+// same as contents.lines(), but also says where each line starts
+pub fn lines_with_offsets(contents: &str) -> impl Iterator<Item = (usize, &str)> {
+    let mut offset = 0;
+    contents.split_inclusive('\n').map(move |piece| {
+        let start = offset;
+        offset += piece.len();
+        let line = piece.strip_suffix('\n').unwrap_or(piece);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        (start, line)
+    })
+}
+
+// like search_with but keeps track of where every match is
+pub fn find_matches<'a>(matcher: &'a Matcher, contents: &'a str) -> Vec<Match<'a>> {
+    let mut results = Vec::new();
+
+    for (i, (byte_offset, line)) in lines_with_offsets(contents).enumerate() {
+        let found = matcher.find_patterns(line);
+        if matcher.selects(line, &found) {
+            let distances = found
+                .iter()
+                .filter_map(|(range, _)| matcher.distance(&line[range.clone()]))
+                .collect();
+            let (ranges, patterns) = found.into_iter().unzip();
+            results.push(Match {
+                line_number: i + 1,
+                byte_offset,
+                line: line.as_bytes(),
+                ranges,
+                patterns,
+                distances,
+            });
+        }
+    }
+
+    results
 }
 
 // defining search just enough for the test to compile
@@ -64,11 +599,11 @@ pub fn search<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
 
 // `IGNORE_CASE=1 cargo run -- to poem.txt``
 pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
-    let query = query.to_lowercase(); // to_lowercase creates new data (String) rather than referencing existing data. Also would need more work to handle unicode.
+    let query = casefold::fold_str(query); // fold_str creates new data (String) rather than referencing existing data. Case folding rather than to_lowercase, so 'ß' matches "SS".
     let mut results = Vec::new();
 
     for line in contents.lines() {
-        if line.to_lowercase().contains(&query) {
+        if casefold::find(line, &query, 0).is_some() {
             results.push(line);
         }
     }
@@ -76,6 +611,84 @@ pub fn search_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a st
     results
 }
 
+// the same lines as search, but without going line by line: the finder jumps straight to the next
+// occurrence anywhere in contents, and only then do we look for where that line starts and ends
+// `cargo bench` compares the two
+pub fn search_literal<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    search_buffer(&Finder::new(query.as_bytes()), contents, None)
+}
+
+// the same lines as search_case_insensitive. Ignoring the case of ASCII letters is done by the finder, but a
+// few non-ASCII letters fold to ASCII ones (K, ſ, ß, ﬀ...), so lines with non-ASCII text in them that the
+// finder skipped still get a proper case folded look
+pub fn search_literal_case_insensitive<'a>(query: &str, contents: &'a str) -> Vec<&'a str> {
+    let query = casefold::fold_str(query);
+    if !query.is_ascii() {
+        return search_case_insensitive(&query, contents);
+    }
+    search_buffer(
+        &Finder::ignoring_ascii_case(query.as_bytes()),
+        contents,
+        Some(&query),
+    )
+}
+
+// the lines of contents (like contents.lines()) that finder finds something in
+// with folded, lines that aren't all ASCII are also checked with casefold::find
+fn search_buffer<'a>(finder: &Finder, contents: &'a str, folded: Option<&str>) -> Vec<&'a str> {
+    let mut results = Vec::new();
+    // a line can't have a line break in it
+    if finder.needle().contains(&b'\n') {
+        return results;
+    }
+    let bytes = contents.as_bytes();
+    // lines before pos are done with, from is where the finder carries on
+    let mut pos = 0;
+    let mut from = 0;
+    while pos < bytes.len() {
+        let hit = finder.find(bytes, from);
+        let line_start = match hit {
+            Some(hit) => literal::memrchr(b'\n', &bytes[pos..hit]).map_or(pos, |i| pos + i + 1),
+            None => bytes.len(),
+        };
+        if let Some(query) = folded {
+            if !contents[pos..line_start].is_ascii() {
+                results.extend(
+                    contents[pos..line_start].lines().filter(|line| {
+                        !line.is_ascii() && casefold::find(line, query, 0).is_some()
+                    }),
+                );
+            }
+        }
+        let Some(hit) = hit else {
+            break;
+        };
+
+        let line_end = literal::memchr(b'\n', &bytes[hit..]).map_or(bytes.len(), |i| hit + i);
+        let line = &contents[line_start..line_end];
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        // only a query ending in \r can run into the line ending, it might still be somewhere else in the line
+        if hit + finder.needle().len() > line_start + line.len() {
+            pos = line_start;
+            from = hit + 1;
+            continue;
+        }
+        results.push(line);
+        pos = line_end + 1;
+        from = pos;
+    }
+    results
+}
+
+// the library version of what run does, works with any kind of matcher
+// `cargo run -- --regex "^(To|Then) " poem.txt`
+pub fn search_with<'a>(matcher: &Matcher, contents: &'a str) -> Vec<&'a str> {
+    contents
+        .lines()
+        .filter(|line| matcher.is_match(line))
+        .collect()
+}
+
 // Test Driven Development
 // 1. Write a test that fails and run to make sure it fails for the reason you expect
 // 2. Write or modify just enough code to make the new test pass
@@ -112,4 +725,411 @@ Trust me.";
             search_case_insensitive(query, contents)
         );
     }
+
+    #[test]
+    fn case_insensitive_unicode() {
+        let contents = "\
+Die Straße ist lang.
+DIE STRASSE IST LANG.
+ΟΔΥΣΣΕΥΣ
+İstanbul";
+
+        assert_eq!(
+            vec!["Die Straße ist lang.", "DIE STRASSE IST LANG."],
+            search_case_insensitive("STRASSE", contents)
+        );
+        assert_eq!(
+            vec!["ΟΔΥΣΣΕΥΣ"],
+            search_case_insensitive("οδυσσευς", contents)
+        );
+        assert_eq!(
+            vec!["İstanbul"],
+            search_case_insensitive("İSTANBUL", contents)
+        );
+    }
+
+    #[test]
+    fn literal_search_finds_the_same_lines() {
+        let contents = "\
+Rust:\r
+safe, fast, productive.
+Pick three.
+Duct tape.\r
+Die Straße ist lang.
+The Kelvin sign: 100 K
+wait, the ſ is a long s
+
+";
+        for query in [
+            "", "duct", "Duct", "t", "e.", "ss", "k", "ST", "\r", "e\r", "\n", "nowhere",
+        ] {
+            assert_eq!(
+                search(query, contents),
+                search_literal(query, contents),
+                "{query:?}"
+            );
+            assert_eq!(
+                search_case_insensitive(query, contents),
+                search_literal_case_insensitive(query, contents),
+                "{query:?}"
+            );
+        }
+        assert_eq!(
+            vec!["Die Straße ist lang.", "wait, the ſ is a long s"],
+            search_literal_case_insensitive("S", contents)
+                .into_iter()
+                .filter(|line| !line.is_ascii())
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[test]
+    fn regex_search() {
+        let contents = "\
+Rust:
+safe, fast, productive.
+Pick three.
+Trust me.";
+
+        let matcher = Matcher::Regex(regex::Regex::new(r"^\w+:$|me\.$").unwrap());
+        assert_eq!(vec!["Rust:", "Trust me."], search_with(&matcher, contents));
+
+        let matcher = Matcher::literal("rUsT", true);
+        assert_eq!(
+            search_case_insensitive("rUsT", contents),
+            search_with(&matcher, contents)
+        );
+    }
+
+    fn args(args: &[&str]) -> Vec<String> {
+        args.iter().map(|s| s.to_string()).collect()
+    }
+
+    #[test]
+    fn build_with_regex_flag() {
+        let argv = args(&["minigrep", "--regex", "a|b", "poem.txt"]);
+        let config = Config::build(&argv).unwrap();
+        assert_eq!(MatcherKind::Regex, config.matcher);
+        assert_eq!("a|b", config.query);
+        assert_eq!(vec!["poem.txt"], config.paths);
+        assert_eq!(WalkOptions::default(), config.walk);
+
+        let mut config = config;
+        config.query = "a(b".to_string();
+        assert!(config.searcher().build().is_err());
+    }
+
+    #[test]
+    fn build_with_query_expression() {
+        let argv = args(&["minigrep", "--query", "a AND NOT b", "poem.txt", "more.txt"]);
+        let config = Config::build(&argv).unwrap();
+        assert_eq!(MatcherKind::Query, config.matcher);
+        assert_eq!("a AND NOT b", config.query);
+        assert_eq!(vec!["poem.txt", "more.txt"], config.paths);
+
+        let argv = args(&["minigrep", "--query", "a", "--regex", "poem.txt"]);
+        assert!(Config::build(&argv).is_err());
+        let argv = args(&["minigrep", "--query", "a OR", "poem.txt"]);
+        let config = Config::build(&argv).unwrap();
+        assert!(matches!(
+            config.searcher().build(),
+            Err(MinigrepError::Query(_))
+        ));
+    }
+
+    #[test]
+    fn match_positions() {
+        let contents = "\
+Rust:\r
+safe, fast, productive.
+Pick three.
+Duct tape, duct tape.";
+
+        let matcher = Matcher::literal("duct", true);
+        let matches = find_matches(&matcher, contents);
+        assert_eq!(2, matches.len());
+        assert_eq!(
+            Match {
+                line_number: 2,
+                byte_offset: 7,
+                line: b"safe, fast, productive.",
+                ranges: vec![Range { start: 15, end: 19 }],
+                patterns: vec!["duct"],
+                distances: Vec::new(),
+            },
+            matches[0]
+        );
+        assert_eq!(4, matches[1].line_number);
+        assert_eq!(43, matches[1].byte_offset);
+        assert_eq!(vec![0..4, 11..15], matches[1].ranges);
+
+        let lines: Vec<&str> = lines_with_offsets(contents).map(|(_, line)| line).collect();
+        assert_eq!(contents.lines().collect::<Vec<_>>(), lines);
+    }
+
+    #[test]
+    fn build_with_options() {
+        let argv = args(&["minigrep", "to", "src", "--follow", "--max-depth", "2"]);
+        let config = Config::build(&argv).unwrap();
+        assert!(config.walk.follow_links);
+        assert_eq!(Some(2), config.walk.max_depth);
+
+        let argv = args(&["minigrep", "-C", "2", "to", "src", "-A", "3", "-j4"]);
+        let config = Config::build(&argv).unwrap();
+        assert_eq!(2, config.output.before_context);
+        assert_eq!(3, config.output.after_context);
+        assert_eq!(vec!["src"], config.paths);
+        assert_eq!(Some(4), config.threads);
+        assert!(config.output.with_filename);
+
+        let argv = args(&["minigrep", "-v", "-c", "--no-filename", "to", "src"]);
+        let config = Config::build(&argv).unwrap();
+        assert!(config.invert);
+        assert_eq!(OutputMode::Count, config.output.mode);
+        assert!(!config.output.with_filename);
+
+        let argv = args(&["minigrep", "-wx", "to", "poem.txt"]);
+        let config = Config::build(&argv).unwrap();
+        assert!(config.whole_word);
+        assert!(config.whole_line);
+
+        let argv = args(&["minigrep", "to", "src", "--max-depth", "deep"]);
+        assert!(Config::build(&argv).is_err());
+    }
+
+    #[test]
+    fn build_with_flags_overriding_the_environment() {
+        let config = Config::build(&args(&["minigrep", "-in", "--", "-v", "poem.txt"])).unwrap();
+        assert!(config.ignore_case);
+        assert!(config.output.line_number);
+        assert!(!config.invert);
+        assert_eq!("-v", config.query);
+
+        let config = Config::build(&args(&["minigrep", "-i", "-s", "to", "poem.txt"])).unwrap();
+        assert!(!config.ignore_case);
+
+        let config = Config::build(&args(&["minigrep", "-S", "to", "poem.txt"])).unwrap();
+        assert!(config.smart_case);
+        let config = Config::build(&args(&["minigrep", "-S", "-i", "to", "poem.txt"])).unwrap();
+        assert!(!config.smart_case);
+        assert!(config.ignore_case);
+
+        let config = Config::build(&args(&["minigrep", "to"])).unwrap();
+        assert_eq!(vec![STDIN], config.paths);
+        assert!(!config.output.with_filename);
+
+        let config = Config::build(&args(&["minigrep", "to", "poem.txt", "src/*.rs"])).unwrap();
+        assert_eq!(vec!["poem.txt", "src/*.rs"], config.paths);
+        assert!(config.output.with_filename);
+
+        let config = Config::build(&args(&["minigrep", "--help"])).unwrap();
+        assert_eq!(Action::Help, config.action);
+    }
+
+    #[test]
+    fn build_with_several_patterns() {
+        let argv = args(&["minigrep", "-e", "frog", "--regexp=bog", "poem.txt", "-"]);
+        let config = Config::build(&argv).unwrap();
+        assert_eq!(
+            Some(vec!["frog".to_string(), "bog".to_string()]),
+            config.patterns
+        );
+        assert_eq!(vec!["poem.txt", "-"], config.paths);
+
+        let file = env::temp_dir().join(format!("minigrep-patterns-{}", std::process::id()));
+        fs::write(&file, "frog\r\nbog\n").unwrap();
+        let argv = args(&["minigrep", "-f", &file.display().to_string(), "-e", "us"]);
+        let config = Config::build(&argv).unwrap();
+        fs::remove_file(&file).unwrap();
+        let patterns = config.patterns.clone().unwrap();
+        assert_eq!(vec!["frog", "bog", "us"], patterns);
+        assert_eq!(vec![STDIN], config.paths);
+
+        let searcher = config.searcher().build().unwrap();
+        let poem = include_str!("../poem.txt");
+        let matches = find_matches(searcher.matcher(), poem);
+        let lines: Vec<usize> = matches.iter().map(|m| m.line_number).collect();
+        assert_eq!(vec![3, 4, 7, 9], lines);
+        assert_eq!(vec!["us"], matches[1].patterns);
+
+        let argv = args(&["minigrep", "-f", "no-such-patterns.txt", "poem.txt"]);
+        assert!(matches!(
+            Config::build(&argv),
+            Err(MinigrepError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn build_errors_name_the_problem() {
+        let message = |args: &[&str]| match Config::build(&self::args(args)) {
+            Err(MinigrepError::Args(message)) => message,
+            _ => panic!("expected an argument error"),
+        };
+        assert_eq!("missing PATTERN argument", message(&["minigrep"]));
+        assert_eq!(
+            "unrecognized option '-q'",
+            message(&["minigrep", "-q", "to", "poem.txt"])
+        );
+    }
+
+    #[test]
+    fn build_subcommands() {
+        let config = Config::build(&args(&["minigrep", "index", "."])).unwrap();
+        assert_eq!(Action::Index, config.action);
+        assert_eq!(vec!["."], config.paths);
+        // with no DIR it's standard input that gets searched, with two it's both of them
+        let config = Config::build(&args(&["minigrep", "index"])).unwrap();
+        assert_eq!(Action::Search, config.action);
+        assert_eq!("index", config.query);
+        assert_eq!(vec![STDIN], config.paths);
+        let config = Config::build(&args(&["minigrep", "query", "here"])).unwrap();
+        assert_eq!(Action::Search, config.action);
+        assert_eq!(vec!["here"], config.paths);
+        let config = Config::build(&args(&["minigrep", "index", "src", "tests"])).unwrap();
+        assert_eq!(Action::Search, config.action);
+        assert_eq!("index", config.query);
+        assert_eq!(vec!["src", "tests"], config.paths);
+        let config = Config::build(&args(&["minigrep", "query", "to", "src", "tests"])).unwrap();
+        assert_eq!(Action::Search, config.action);
+        assert_eq!(vec!["to", "src", "tests"], config.paths);
+
+        let config = Config::build(&args(&["minigrep", "query", "-i", "to", "src"])).unwrap();
+        assert_eq!(Action::Query, config.action);
+        assert_eq!("to", config.query);
+        assert_eq!(vec!["src"], config.paths);
+        assert!(config.output.with_filename);
+
+        // only as the first argument
+        let config = Config::build(&args(&["minigrep", "-i", "index", "src"])).unwrap();
+        assert_eq!(Action::Search, config.action);
+        assert_eq!("index", config.query);
+        // and only in front of a directory, otherwise it's what to search for
+        let config = Config::build(&args(&["minigrep", "index", "poem.txt"])).unwrap();
+        assert_eq!(Action::Search, config.action);
+        assert_eq!("index", config.query);
+        assert_eq!(vec!["poem.txt"], config.paths);
+        let config = Config::build(&args(&["minigrep", "query", "-n", "to", "poem.txt"])).unwrap();
+        assert_eq!(Action::Search, config.action);
+        assert_eq!("query", config.query);
+        assert_eq!(vec!["to", "poem.txt"], config.paths);
+        assert!(config.output.line_number);
+    }
+
+    #[test]
+    fn search_reader_streams_lines() {
+        let input = "Rust:\r\nsafe, fast, productive.\nPick three.\nDuct tape.";
+        let options = PrintOptions {
+            line_number: true,
+            byte_offset: true,
+            ..PrintOptions::default()
+        };
+        let mut printer = Printer::new(Vec::new(), options);
+        let searcher = SearcherBuilder::new()
+            .pattern("duct")
+            .case(CaseMode::Insensitive)
+            .build()
+            .unwrap();
+
+        let matched = searcher
+            .search_reader(Path::new(STDIN_NAME), input.as_bytes(), &mut printer)
+            .unwrap();
+        assert!(matched);
+        assert_eq!(
+            "2:7:safe, fast, productive.\n4:43:Duct tape.\n",
+            String::from_utf8(printer.into_inner()).unwrap()
+        );
+    }
+
+    #[test]
+    fn search_reader_takes_any_bytes() {
+        let searcher = SearcherBuilder::new()
+            .matcher(Matcher::literal("duct", true))
+            .build()
+            .unwrap();
+        let search = |bytes: &[u8], options: PrintOptions| {
+            let mut printer = Printer::new(Vec::new(), options);
+            searcher
+                .search_reader(Path::new("odd.txt"), bytes, &mut printer)
+                .unwrap();
+            String::from_utf8(printer.into_inner()).unwrap()
+        };
+        let options = PrintOptions {
+            line_number: true,
+            ..PrintOptions::default()
+        };
+
+        // bytes that aren't UTF-8 don't stop the search, offsets still count the bytes in the file
+        let latin1 = b"caf\xe9\nproduct \xff\nDuct tape.\n";
+        assert_eq!(
+            "2:product \u{FFFD}\n3:Duct tape.\n",
+            search(latin1, options)
+        );
+        // and so do the offsets of the matches, the � that's searched is longer than the é in the file
+        let latin1 = b"caf\xe9 product\n";
+        let only = PrintOptions {
+            mode: OutputMode::OnlyMatching,
+            byte_offset: true,
+            column: true,
+            ..options
+        };
+        assert_eq!("1:9:8:duct\n", search(latin1, only));
+        let json = PrintOptions {
+            mode: OutputMode::Json,
+            ..options
+        };
+        // -U reads the whole input in, the offsets are still the file's
+        let mut printer = Printer::new(Vec::new(), only);
+        let multiline = SearcherBuilder::new()
+            .matcher(Matcher::literal("duct", true))
+            .multiline(true)
+            .build()
+            .unwrap();
+        multiline
+            .search_reader(
+                Path::new("odd.txt"),
+                &b"\xe9t\xe9\ncaf\xe9 product\n"[..],
+                &mut printer,
+            )
+            .unwrap();
+        assert_eq!(
+            "2:9:12:duct\n",
+            String::from_utf8(printer.into_inner()).unwrap()
+        );
+        assert!(search(latin1, json).contains(
+            r#""line":{"bytes":"Y2Fm6SBwcm9kdWN0"},"submatches":[{"match":{"text":"duct"},"start":8,"end":12,"#
+        ));
+
+        let binary = b"\x7fELF\x00\x00duct\nduct\n";
+        assert_eq!(
+            "minigrep: odd.txt: binary file matches\n",
+            search(binary, options)
+        );
+        let count = PrintOptions {
+            mode: OutputMode::Count,
+            binary_files: BinaryFiles::WithoutMatch,
+            ..options
+        };
+        assert_eq!("0\n", search(binary, count));
+        let text = PrintOptions {
+            binary_files: BinaryFiles::Text,
+            ..options
+        };
+        assert_eq!("1:\x7fELF\x00\x00duct\n2:duct\n", search(binary, text));
+        // when the NUL isn't in the first buffer, the text lines before it are printed as usual
+        let mut printer = Printer::new(Vec::new(), options);
+        let late = io::BufReader::with_capacity(4, &b"duct\n\x00duct\n"[..]);
+        searcher
+            .search_reader(Path::new("odd.txt"), late, &mut printer)
+            .unwrap();
+        assert_eq!(
+            "1:duct\nminigrep: odd.txt: binary file matches\n",
+            String::from_utf8(printer.into_inner()).unwrap()
+        );
+
+        let utf16: Vec<u8> = "\u{FEFF}Rust:\r\nDuct tape.\r\n"
+            .encode_utf16()
+            .flat_map(u16::to_le_bytes)
+            .collect();
+        assert_eq!("2:Duct tape.\n", search(&utf16, options));
+    }
 }
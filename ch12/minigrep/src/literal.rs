@@ -0,0 +1,284 @@
+// fast search for one fixed string, what the literal matcher and search_literal use instead of str::find.
+// The searcher uses it too, to jump over every line without the literal in a whole buffer at once
+// first we look for the rarest byte of the needle, 8 bytes of the haystack at a time, and only compare the
+// whole needle where that byte turns up. For most needles in most text that skips nearly everything. When
+// the rare byte keeps turning up where the needle isn't (searching "eee" in "eeeeeeee..."), we switch to
+// Boyer-Moore-Horspool instead, which after every mismatch jumps ahead by up to the length of the needle.
+// Neither one allocates, and both work on a whole buffer as easily as on one line.
+
+// a prefilter hit that isn't a match is a false positive, after this many of them we look at how much
+// they cost and maybe give up on the prefilter
+const PREFILTER_GRACE: usize = 16;
+
+#[derive(Debug, Clone)]
+pub struct Finder {
+    needle: Vec<u8>,
+    // compare ASCII letters without caring about case, the needle is stored lowercased
+    ignore_ascii_case: bool,
+    // where the rarest byte is in the needle
+    rare: usize,
+    // Horspool's bad character table: how far we can move on when the last byte of the window is this one
+    shift: [usize; 256],
+}
+
+impl Finder {
+    pub fn new(needle: &[u8]) -> Finder {
+        Finder::build(needle, false)
+    }
+
+    // for needles where only ASCII letters need their case ignored, non-ASCII bytes are compared as they are
+    pub fn ignoring_ascii_case(needle: &[u8]) -> Finder {
+        Finder::build(&needle.to_ascii_lowercase(), true)
+    }
+
+    fn build(needle: &[u8], ignore_ascii_case: bool) -> Finder {
+        let rare = (0..needle.len())
+            .min_by_key(|&i| commonness(needle[i], ignore_ascii_case))
+            .unwrap_or(0);
+        let mut shift = [needle.len().max(1); 256];
+        for (i, &byte) in needle
+            .iter()
+            .enumerate()
+            .take(needle.len().saturating_sub(1))
+        {
+            let distance = needle.len() - 1 - i;
+            shift[byte as usize] = distance;
+            if ignore_ascii_case {
+                shift[byte.to_ascii_uppercase() as usize] = distance;
+            }
+        }
+        Finder {
+            needle: needle.to_vec(),
+            ignore_ascii_case,
+            rare,
+            shift,
+        }
+    }
+
+    pub fn needle(&self) -> &[u8] {
+        &self.needle
+    }
+
+    // where the first occurrence at or after start begins
+    pub fn find(&self, haystack: &[u8], start: usize) -> Option<usize> {
+        let n = self.needle.len();
+        if start > haystack.len() || haystack.len() - start < n {
+            return None;
+        }
+        if n == 0 {
+            return Some(start);
+        }
+
+        let rare_byte = self.needle[self.rare];
+        let mut pos = start;
+        let mut false_positives = 0;
+        // the rare byte can't be any later than this and still have room for the rest of the needle after it
+        let last = haystack.len() - (n - 1 - self.rare);
+        while pos + n <= haystack.len() {
+            let from = pos + self.rare;
+            let found = if self.ignore_ascii_case && rare_byte.is_ascii_lowercase() {
+                memchr2(
+                    rare_byte,
+                    rare_byte.to_ascii_uppercase(),
+                    &haystack[from..last],
+                )
+            } else {
+                memchr(rare_byte, &haystack[from..last])
+            };
+            let candidate = from + found? - self.rare;
+            if self.matches_at(haystack, candidate) {
+                return Some(candidate);
+            }
+            pos = candidate + 1;
+            false_positives += 1;
+            // a false positive every few bytes means the prefilter costs more than it saves
+            if false_positives > PREFILTER_GRACE && pos - start < false_positives * 8 {
+                return self.horspool(haystack, pos);
+            }
+        }
+        None
+    }
+
+    fn horspool(&self, haystack: &[u8], mut pos: usize) -> Option<usize> {
+        let n = self.needle.len();
+        while pos + n <= haystack.len() {
+            if self.matches_at(haystack, pos) {
+                return Some(pos);
+            }
+            pos += self.shift[haystack[pos + n - 1] as usize];
+        }
+        None
+    }
+
+    fn matches_at(&self, haystack: &[u8], pos: usize) -> bool {
+        let window = &haystack[pos..pos + self.needle.len()];
+        if self.ignore_ascii_case {
+            window.eq_ignore_ascii_case(&self.needle)
+        } else {
+            window == self.needle.as_slice()
+        }
+    }
+}
+
+// a rough guess of how often a byte shows up in text and source code, higher is more common
+// only the order matters, it's what picks the byte the prefilter looks for
+fn commonness(byte: u8, ignore_ascii_case: bool) -> u8 {
+    const COMMON: &[u8] = b" etaoinsrhldcumfpgwybvkxjqz";
+    let byte = if ignore_ascii_case {
+        byte.to_ascii_lowercase()
+    } else {
+        byte
+    };
+    match byte {
+        // letters get rarer down the list, lowercase is more common than uppercase
+        b'a'..=b'z' | b' ' => {
+            let rank = COMMON
+                .iter()
+                .position(|&c| c == byte)
+                .unwrap_or(COMMON.len());
+            let score = 250 - rank as u8 * 4;
+            // looking for either case of a letter finds both, so it's a bit more common again
+            if ignore_ascii_case && byte != b' ' {
+                score.saturating_add(2)
+            } else {
+                score
+            }
+        }
+        b'A'..=b'Z' => 140,
+        b'\n' | b'\t' | b'.' | b',' | b'_' | b'(' | b')' | b';' | b'"' | b'=' => 150,
+        b'0'..=b'9' => 120,
+        // other punctuation and control characters
+        0..=127 => 90,
+        // the bytes of non-ASCII UTF-8: lead bytes are rare, continuation bytes less so
+        0x80..=0xBF => 60,
+        _ => 30,
+    }
+}
+
+const LO: u64 = 0x0101_0101_0101_0101;
+const HI: u64 = 0x8080_8080_8080_8080;
+
+// the lowest byte of word that is zero, the usual trick: subtracting 1 from every byte only sets a byte's
+// top bit where that byte was zero (or had its top bit set already, which the `!word` rules out). Borrows
+// only ever move up, so the lowest flagged byte is always a real zero.
+fn first_zero_byte(word: u64) -> Option<usize> {
+    let found = word.wrapping_sub(LO) & !word & HI;
+    if found == 0 {
+        None
+    } else {
+        Some(found.trailing_zeros() as usize / 8)
+    }
+}
+
+// the first position of byte in haystack, comparing 8 bytes at a time
+pub fn memchr(byte: u8, haystack: &[u8]) -> Option<usize> {
+    let repeated = LO * byte as u64;
+    let mut chunks = haystack.chunks_exact(8);
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        if let Some(at) = first_zero_byte(word ^ repeated) {
+            return Some(i * 8 + at);
+        }
+    }
+    let tail = haystack.len() - chunks.remainder().len();
+    chunks
+        .remainder()
+        .iter()
+        .position(|&b| b == byte)
+        .map(|at| tail + at)
+}
+
+// the first position of either byte
+pub fn memchr2(a: u8, b: u8, haystack: &[u8]) -> Option<usize> {
+    let (repeated_a, repeated_b) = (LO * a as u64, LO * b as u64);
+    let mut chunks = haystack.chunks_exact(8);
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        let word = u64::from_le_bytes(chunk.try_into().unwrap());
+        let at = match (
+            first_zero_byte(word ^ repeated_a),
+            first_zero_byte(word ^ repeated_b),
+        ) {
+            (Some(x), Some(y)) => Some(x.min(y)),
+            (x, y) => x.or(y),
+        };
+        if let Some(at) = at {
+            return Some(i * 8 + at);
+        }
+    }
+    let tail = haystack.len() - chunks.remainder().len();
+    chunks
+        .remainder()
+        .iter()
+        .position(|&c| c == a || c == b)
+        .map(|at| tail + at)
+}
+
+// the last position of byte in haystack
+pub fn memrchr(byte: u8, haystack: &[u8]) -> Option<usize> {
+    let repeated = LO * byte as u64;
+    let mut chunks = haystack.rchunks_exact(8);
+    for (i, chunk) in chunks.by_ref().enumerate() {
+        // big endian puts the last byte of the chunk lowest, so the lowest zero byte is the last match
+        let word = u64::from_be_bytes(chunk.try_into().unwrap());
+        if let Some(at) = first_zero_byte(word ^ repeated) {
+            return Some(haystack.len() - i * 8 - 1 - at);
+        }
+    }
+    chunks.remainder().iter().rposition(|&b| b == byte)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn byte_scans() {
+        let text = b"safe, fast, productive.\nPick three.";
+        for byte in [b's', b'\n', b'.', b'P', b'z'] {
+            assert_eq!(text.iter().position(|&b| b == byte), memchr(byte, text));
+            assert_eq!(text.iter().rposition(|&b| b == byte), memrchr(byte, text));
+        }
+        assert_eq!(Some(5), memchr2(b'a', b'P', &text[2..]));
+        assert_eq!(Some(21), memchr2(b'P', b'\n', &text[2..]));
+        assert_eq!(None, memchr(b'x', b""));
+        // a 0x80 byte next to the one we want mustn't fool the zero byte trick
+        assert_eq!(
+            Some(9),
+            memchr(0x01, b"\x80\x80\x80\x80\x80\x80\x80\x80\x80\x01")
+        );
+    }
+
+    #[test]
+    fn finds_like_str_find() {
+        let haystack = "Rust:\nsafe, fast, productive.\nPick three.\nDuct tape, duct tape.";
+        for needle in [
+            "duct", "Duct", "t", "", "tape.", "e.\nP", "nowhere", "Rust:",
+        ] {
+            let finder = Finder::new(needle.as_bytes());
+            for start in 0..=haystack.len() {
+                let expected = haystack[start..].find(needle).map(|i| start + i);
+                assert_eq!(
+                    expected,
+                    finder.find(haystack.as_bytes(), start),
+                    "{needle:?} {start}"
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn falls_back_to_horspool() {
+        let haystack = format!("{}b", "a".repeat(1000));
+        let finder = Finder::new(b"aab");
+        assert_eq!(Some(998), finder.find(haystack.as_bytes(), 0));
+        assert_eq!(None, Finder::new(b"aac").find(haystack.as_bytes(), 0));
+    }
+
+    #[test]
+    fn ascii_case() {
+        let finder = Finder::ignoring_ascii_case(b"DUCT");
+        assert_eq!(Some(15), finder.find(b"safe, fast, proDUCtive.", 0));
+        assert_eq!(Some(0), finder.find(b"Duct tape", 0));
+        assert_eq!(None, finder.find(b"Dukt tape", 0));
+    }
+}
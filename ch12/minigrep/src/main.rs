@@ -25,17 +25,22 @@ fn main() {
                                                        // why use stderror?
                                                        // because errors do not belong in stdout. That is general information
                                                        // we can still have both stdout and stderror. Print what we know in stdout and print what went wrong with what we know in stderr
-        process::exit(1);
+        eprintln!("Try 'minigrep --help' for more information.");
+        process::exit(2);
     });
 
-    println!("Searching for {}", config.query);
-    println!("In file {}", config.file_path);
+    // no "Searching for ..." banner anymore, stdout is only for results so other programs can read them
 
     // reading a file
-    // use if let here because we don't care about the returned value, (). better to only handle the error case with if let
-    if let Err(e) = minigrep::run(config) {
-        eprintln!("Application error: {e}");
-        process::exit(1);
+    // the exit code is how shell scripts find out what happened: 0 matched, 1 no match, 2 error (same as grep)
+    match minigrep::run(config) {
+        Ok(summary) => process::exit(summary.exit_code()),
+        // nobody is reading anymore, so there's nobody to tell
+        Err(e) if e.is_broken_pipe() => process::exit(0),
+        Err(e) => {
+            eprintln!("Application error: {e}");
+            process::exit(2);
+        }
     }
 
     // let contents =
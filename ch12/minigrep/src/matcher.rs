@@ -0,0 +1,408 @@
+// picks how a line is tested against the query: plain substring search or a regular expression
+// with -e or -f there can be any number of patterns, a line matches when any one of them does
+
+use std::ops::Range;
+
+use crate::aho_corasick::AhoCorasick;
+use crate::casefold;
+use crate::fuzzy::Fuzzy;
+use crate::literal::Finder;
+use crate::query::Query;
+use crate::regex::{Bounds, Regex};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum MatcherKind {
+    #[default]
+    Literal,
+    Regex,
+    // --query, a boolean expression over plain text terms
+    Query,
+}
+
+#[derive(Debug, Clone)]
+pub enum Matcher {
+    // the query is case folded up front when ignoring case, same as search_case_insensitive
+    // pattern is the query the way it was given, that's what gets reported as the pattern that matched
+    Literal {
+        pattern: String,
+        query: String,
+        // looks for query, ignoring ASCII case when ignore_case is set. Boxed for its shift table
+        finder: Box<Finder>,
+        ignore_case: bool,
+        bounds: Bounds,
+    },
+    // -w and -x are compiled into the regex
+    Regex(Regex),
+    // several literal patterns, all looked for in one pass over the line
+    Literals {
+        patterns: Vec<String>,
+        // boxed, the automaton is a lot bigger than the other variants
+        automaton: Box<AhoCorasick>,
+        ignore_case: bool,
+        bounds: Bounds,
+    },
+    // several regexes, each one runs over the line and the leftmost match wins
+    Regexes(Vec<Regex>),
+    // --fuzzy, the pattern with a few typos, see fuzzy.rs
+    Fuzzy {
+        pattern: String,
+        fuzzy: Box<Fuzzy>,
+        bounds: Bounds,
+    },
+    // --query, see query.rs
+    Query(Box<Query>),
+}
+
+impl Matcher {
+    pub fn literal(query: &str, ignore_case: bool) -> Matcher {
+        Matcher::literal_bounded(query, ignore_case, Bounds::Anywhere)
+    }
+
+    pub fn literal_bounded(query: &str, ignore_case: bool, bounds: Bounds) -> Matcher {
+        let pattern = query.to_string();
+        let (query, finder) = if ignore_case {
+            let folded = casefold::fold_str(query);
+            let finder = Box::new(Finder::ignoring_ascii_case(folded.as_bytes()));
+            (folded, finder)
+        } else {
+            (query.to_string(), Box::new(Finder::new(query.as_bytes())))
+        };
+        Matcher::Literal {
+            pattern,
+            query,
+            finder,
+            ignore_case,
+            bounds,
+        }
+    }
+
+    // any number of literal patterns, with no patterns at all nothing matches
+    pub fn literals<S: AsRef<str>>(patterns: &[S], ignore_case: bool, bounds: Bounds) -> Matcher {
+        let patterns: Vec<String> = patterns.iter().map(|p| p.as_ref().to_string()).collect();
+        let automaton = if ignore_case {
+            let folded: Vec<String> = patterns.iter().map(|p| casefold::fold_str(p)).collect();
+            Box::new(AhoCorasick::new(&folded))
+        } else {
+            Box::new(AhoCorasick::new(&patterns))
+        };
+        Matcher::Literals {
+            patterns,
+            automaton,
+            ignore_case,
+            bounds,
+        }
+    }
+
+    pub fn fuzzy(pattern: &str, max_distance: usize, ignore_case: bool, bounds: Bounds) -> Matcher {
+        Matcher::Fuzzy {
+            pattern: pattern.to_string(),
+            fuzzy: Box::new(Fuzzy::new(pattern, max_distance, ignore_case)),
+            bounds,
+        }
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        match self {
+            Matcher::Literal {
+                finder,
+                ignore_case: false,
+                bounds: Bounds::Anywhere,
+                ..
+            } => finder.find(line.as_bytes(), 0).is_some(),
+            Matcher::Regex(regex) => regex.is_match(line),
+            Matcher::Query(query) => query.is_match(line),
+            _ => self.find_at(line, 0).is_some(),
+        }
+    }
+
+    // whether a line with found in it (from find_patterns) is one that matches. That's the same as finding
+    // something, except for a query that matches a line for what isn't in it
+    pub fn selects(&self, line: &str, found: &[(Range<usize>, &str)]) -> bool {
+        match self {
+            Matcher::Query(query) => query.is_match(line),
+            _ => !found.is_empty(),
+        }
+    }
+
+    // something every matching line has in it, so the searcher can jump over the lines without it in one go
+    // instead of searching them one by one. Only a single literal has that. With ignore_case the bool is
+    // true, the finder then only speaks for ASCII text: a few other letters fold to ASCII ones (K, ſ...)
+    pub(crate) fn prefilter(&self) -> Option<(&Finder, bool)> {
+        match self {
+            // a line with invalid UTF-8 is searched with � in it, which isn't in its bytes
+            Matcher::Literal { query, .. } if query.contains(char::REPLACEMENT_CHARACTER) => None,
+            Matcher::Literal {
+                finder,
+                ignore_case: false,
+                ..
+            } => Some((finder, false)),
+            Matcher::Literal { query, finder, .. } if query.is_ascii() => Some((finder, true)),
+            _ => None,
+        }
+    }
+
+    // byte range of the first match that starts at or after start
+    pub fn find_at(&self, line: &str, start: usize) -> Option<Range<usize>> {
+        self.find_pattern_at(line, start).map(|(range, _)| range)
+    }
+
+    // like find_at, but also says which pattern matched
+    pub fn find_pattern_at(&self, line: &str, start: usize) -> Option<(Range<usize>, &str)> {
+        match self {
+            Matcher::Literal {
+                pattern,
+                query,
+                finder,
+                ignore_case,
+                bounds,
+            } => {
+                // an occurrence in the middle of a word doesn't count, but there could be a good one after it
+                let mut from = start;
+                loop {
+                    let m = find_literal(line, query, finder, *ignore_case, from)?;
+                    if bounds.allows(line, &m) {
+                        return Some((m, pattern.as_str()));
+                    }
+                    from = m.start + line[m.start..].chars().next()?.len_utf8();
+                }
+            }
+            Matcher::Regex(regex) => regex
+                .find_at(line, start)
+                .map(|range| (range, regex.as_str())),
+            Matcher::Literals {
+                patterns,
+                automaton,
+                ignore_case: false,
+                bounds,
+            } => automaton
+                .find_at(line.as_bytes(), start, |range, _| {
+                    bounds.allows(line, range)
+                })
+                .map(|(range, i)| (range, patterns[i].as_str())),
+            Matcher::Literals {
+                patterns,
+                automaton,
+                ignore_case: true,
+                bounds,
+            } => {
+                // search the folded line, then map the match back onto the original one
+                let (folded, offsets) = casefold::fold_with_offsets(&line[start..]);
+                let original = |range: &Range<usize>| {
+                    Some(start + offsets[range.start]?..start + offsets[range.end]?)
+                };
+                let (range, i) = automaton.find_at(folded.as_bytes(), 0, |range, _| {
+                    original(range).is_some_and(|range| bounds.allows(line, &range))
+                })?;
+                Some((original(&range)?, patterns[i].as_str()))
+            }
+            Matcher::Regexes(regexes) => regexes
+                .iter()
+                .filter_map(|regex| Some((regex.find_at(line, start)?, regex.as_str())))
+                // leftmost, then longest, then whichever pattern came first
+                .min_by_key(|(range, _)| (range.start, usize::MAX - range.end)),
+            Matcher::Fuzzy {
+                pattern,
+                fuzzy,
+                bounds,
+            } => {
+                let mut from = start;
+                loop {
+                    let (m, _) = fuzzy.find_at(line, from)?;
+                    if bounds.allows(line, &m) {
+                        return Some((m, pattern.as_str()));
+                    }
+                    from = m.start + line[m.start..].chars().next()?.len_utf8();
+                }
+            }
+            Matcher::Query(query) => query.find_at(line, start),
+        }
+    }
+
+    // how many typos a match has, only --fuzzy matches have any
+    pub fn distance(&self, matched: &str) -> Option<usize> {
+        match self {
+            Matcher::Fuzzy { fuzzy, .. } => Some(fuzzy.distance(matched)),
+            _ => None,
+        }
+    }
+
+    // for each pattern, the case folded strings a line has to contain for that pattern to match in it, so
+    // the index can rule files out without reading them. None when any line could match, like with --fuzzy
+    pub fn required(&self) -> Option<Vec<Vec<String>>> {
+        let required = match self {
+            Matcher::Literal { pattern, .. } => vec![vec![casefold::fold_str(pattern)]],
+            Matcher::Literals { patterns, .. } => patterns
+                .iter()
+                .map(|pattern| vec![casefold::fold_str(pattern)])
+                .collect(),
+            Matcher::Regex(regex) => vec![regex.required().to_vec()],
+            Matcher::Regexes(regexes) => regexes
+                .iter()
+                .map(|regex| regex.required().to_vec())
+                .collect(),
+            // a NOT or an OR anywhere would make working out what has to be there a lot harder than
+            // it's worth, the index just doesn't rule anything out
+            Matcher::Fuzzy { .. } | Matcher::Query(_) => return None,
+        };
+        Some(required)
+    }
+
+    // every non-overlapping match in the line, left to right
+    pub fn find_iter(&self, line: &str) -> Vec<Range<usize>> {
+        self.find_patterns(line)
+            .into_iter()
+            .map(|(range, _)| range)
+            .collect()
+    }
+
+    // like find_iter, with the pattern behind each match
+    pub fn find_patterns(&self, line: &str) -> Vec<(Range<usize>, &str)> {
+        let mut found: Vec<(Range<usize>, &str)> = Vec::new();
+        let mut start = 0;
+        while start <= line.len() {
+            let (m, pattern) = match self.find_pattern_at(line, start) {
+                Some(found) => found,
+                None => break,
+            };
+            if m.is_empty() {
+                // an empty match touching the previous one doesn't count, and we have to step past it
+                start = m.end + line[m.end..].chars().next().map_or(1, char::len_utf8);
+                if found.last().is_some_and(|(last, _)| last.end == m.end) {
+                    continue;
+                }
+            } else {
+                start = m.end;
+            }
+            found.push((m, pattern));
+        }
+        found
+    }
+}
+
+// the finder is enough unless case folding has to deal with more than ASCII
+fn find_literal(
+    line: &str,
+    query: &str,
+    finder: &Finder,
+    ignore_case: bool,
+    start: usize,
+) -> Option<Range<usize>> {
+    if ignore_case && !(query.is_ascii() && line[start..].is_ascii()) {
+        casefold::find(line, query, start)
+    } else {
+        let i = finder.find(line.as_bytes(), start)?;
+        Some(i..i + query.len())
+    }
+}
+
+// what --smart-case looks at. In a regex the letter after a backslash is syntax, not text, so \W or \S don't
+// make the search case sensitive
+pub(crate) fn has_uppercase(query: &str, kind: MatcherKind) -> bool {
+    let mut chars = query.chars();
+    while let Some(c) = chars.next() {
+        if c == '\\' && kind == MatcherKind::Regex {
+            chars.next();
+        } else if c.is_uppercase() {
+            return true;
+        }
+    }
+    false
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn find_iter_literal() {
+        let matcher = Matcher::literal("to", false);
+        assert_eq!(vec![0..2, 6..8, 11..13], matcher.find_iter("to be told to"));
+
+        // 'İ' folds to two characters, the range still covers the original bytes
+        let matcher = Matcher::literal("İs", true);
+        assert_eq!(
+            vec![Range { start: 4, end: 7 }],
+            matcher.find_iter("ABC İS")
+        );
+
+        // full case folding, and the ranges are still in bytes of the original line
+        let matcher = Matcher::literal("strasse", true);
+        assert_eq!(
+            vec![Range { start: 4, end: 11 }],
+            matcher.find_iter("Die Straße")
+        );
+
+        let matcher = Matcher::literal("", false);
+        assert_eq!(vec![0..0, 1..1, 2..2], matcher.find_iter("ab"));
+    }
+
+    #[test]
+    fn whole_words_and_lines() {
+        let matcher = Matcher::literal_bounded("duct", false, Bounds::Word);
+        assert!(!matcher.is_match("safe, fast, productive."));
+        assert_eq!(vec![11..15], matcher.find_iter("Duct tape, duct tape."));
+
+        let matcher = Matcher::literal_bounded("duct", true, Bounds::Word);
+        assert_eq!(
+            vec![0..4, 11..15],
+            matcher.find_iter("Duct tape, duct tape.")
+        );
+        // the first "to" is inside a word, the second one isn't
+        let matcher = Matcher::literal_bounded("to", true, Bounds::Word);
+        assert_eq!(
+            vec![Range { start: 7, end: 9 }],
+            matcher.find_iter("Toast, to")
+        );
+
+        let matcher = Matcher::literal_bounded("pick three.", true, Bounds::Line);
+        assert!(matcher.is_match("Pick three."));
+        assert!(!matcher.is_match("Pick three. Four."));
+    }
+
+    #[test]
+    fn smart_case() {
+        assert!(!has_uppercase("duct", MatcherKind::Literal));
+        assert!(has_uppercase("Duct", MatcherKind::Literal));
+        assert!(has_uppercase(r"\Wduct", MatcherKind::Literal));
+        assert!(!has_uppercase(r"\Wduct\S", MatcherKind::Regex));
+        assert!(has_uppercase(r"\WDuct", MatcherKind::Regex));
+    }
+
+    #[test]
+    fn fuzzy() {
+        let matcher = Matcher::fuzzy("frog", 1, true, Bounds::Word);
+        // "frog" in "froggy" is close enough, but not a whole word
+        assert_eq!(
+            vec![13..17, 21..24],
+            matcher.find_iter("froggy, then FROG or frg")
+        );
+        assert_eq!(Some(1), matcher.distance("frg"));
+        assert_eq!(None, Matcher::literal("frog", false).distance("frog"));
+    }
+
+    #[test]
+    fn many_patterns() {
+        let blocklist = ["frog", "bog", "nobody", "body"];
+        let matcher = Matcher::literals(&blocklist, false, Bounds::Anywhere);
+        assert_eq!(
+            vec![(0..4, "frog"), (6..12, "nobody"), (18..21, "bog")],
+            matcher.find_patterns("frog, nobody, somebog")
+        );
+        assert!(!matcher.is_match("Then there's a pair of us"));
+        assert!(!Matcher::literals::<&str>(&[], false, Bounds::Anywhere).is_match("anything"));
+
+        // ranges are still in the original line when case folding changes lengths
+        let matcher = Matcher::literals(&["strasse", "body"], true, Bounds::Word);
+        assert_eq!(
+            vec![(4..11, "strasse")],
+            matcher.find_patterns("Die Straße, SOMEBODY")
+        );
+
+        let regexes = vec![Regex::new("b.g").unwrap(), Regex::new("no+").unwrap()];
+        let matcher = Matcher::Regexes(regexes);
+        assert_eq!(
+            vec![(0..2, "no+"), (8..11, "b.g")],
+            matcher.find_patterns("nobody, bog")
+        );
+    }
+}
@@ -0,0 +1,183 @@
+// searches many files at once on a pool of worker threads
+// the main thread finds the files and hands them out over a channel, each worker searches a whole file into
+// its own buffer and sends it back. Buffers can come back in any order, so they wait in `pending` until
+// every file before them has been written, which keeps the output identical to a single threaded search.
+
+use std::collections::BTreeMap;
+use std::io::Write;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{mpsc, Mutex};
+use std::thread;
+
+use crate::error::MinigrepError;
+use crate::input::{inputs, Input};
+use crate::printer::Printer;
+use crate::searcher::Searcher;
+use crate::{report, Config, Summary};
+
+// what a worker sends back for one file: everything it printed, and how the search went
+type Searched = (Vec<u8>, Result<bool, MinigrepError>);
+
+// writes finished files out in the order they were handed out
+struct Ordered<W: Write> {
+    out: W,
+    pending: BTreeMap<usize, Searched>,
+    next: usize,
+    // grep puts `--` between the context groups of different files too, this is that line when it's needed
+    separator: Option<String>,
+    wrote_anything: bool,
+}
+
+impl<W: Write> Ordered<W> {
+    fn add(
+        &mut self,
+        index: usize,
+        searched: Searched,
+        summary: &mut Summary,
+    ) -> Result<(), MinigrepError> {
+        self.pending.insert(index, searched);
+        while let Some((output, result)) = self.pending.remove(&self.next) {
+            self.next += 1;
+            if !output.is_empty() {
+                if let Some(separator) = self.separator.as_ref().filter(|_| self.wrote_anything) {
+                    self.out
+                        .write_all(separator.as_bytes())
+                        .map_err(MinigrepError::Output)?;
+                }
+                self.out.write_all(&output).map_err(MinigrepError::Output)?;
+                self.wrote_anything = true;
+            }
+            match result {
+                Ok(matched) => summary.add_file(matched),
+                Err(e) => report(summary, e),
+            }
+        }
+        Ok(())
+    }
+}
+
+pub fn search_parallel<W: Write>(
+    config: &Config,
+    searcher: &Searcher,
+    threads: usize,
+    summary: &mut Summary,
+    out: W,
+) -> Result<(), MinigrepError> {
+    let (job_sender, jobs) = mpsc::channel::<(usize, Input)>();
+    let jobs = Mutex::new(jobs);
+    let (result_sender, results) = mpsc::channel::<(usize, Searched)>();
+    // set when the output is gone, so the workers don't bother with the files still queued
+    let cancelled = AtomicBool::new(false);
+
+    let mut ordered = Ordered {
+        out,
+        pending: BTreeMap::new(),
+        next: 0,
+        separator: config
+            .output
+            .uses_separators()
+            .then(|| config.output.group_separator()),
+        wrote_anything: false,
+    };
+
+    thread::scope(|scope| {
+        for _ in 0..threads {
+            let result_sender = result_sender.clone();
+            let (jobs, cancelled) = (&jobs, &cancelled);
+            scope.spawn(move || loop {
+                // the lock is only held while taking the next job, not while searching it
+                let job = jobs.lock().unwrap().recv();
+                let (index, input) = match job {
+                    Ok(job) => job,
+                    Err(_) => break,
+                };
+                if cancelled.load(Ordering::Relaxed) {
+                    break;
+                }
+                let mut printer = Printer::new(Vec::new(), config.output);
+                let result = searcher.search_input(&input, &mut printer);
+                if result_sender
+                    .send((index, (printer.into_inner(), result)))
+                    .is_err()
+                {
+                    break;
+                }
+            });
+        }
+        // only the workers hold senders now, so results ends once they're all done
+        drop(result_sender);
+
+        let written = (|| {
+            for (job, input) in inputs(&config.paths, config.walk).enumerate() {
+                match input {
+                    Ok(input) => job_sender
+                        .send((job, input))
+                        .expect("workers outlive the jobs"),
+                    // problems finding files take their turn in the output like everything else
+                    Err(e) => ordered.add(job, (Vec::new(), Err(e)), summary)?,
+                }
+                // write out whatever is ready while the walk carries on
+                while let Ok((index, searched)) = results.try_recv() {
+                    ordered.add(index, searched, summary)?;
+                }
+            }
+            drop(job_sender);
+            for (index, searched) in results.iter() {
+                ordered.add(index, searched, summary)?;
+            }
+            Ok(())
+        })();
+
+        if written.is_err() {
+            cancelled.store(true, Ordering::Relaxed);
+        }
+        written
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+    use std::fs;
+
+    fn search(config: &Config, threads: usize) -> (Summary, String) {
+        let searcher = config.searcher().build().unwrap();
+        let mut summary = Summary::default();
+        let mut out = Vec::new();
+        search_parallel(config, &searcher, threads, &mut summary, &mut out).unwrap();
+        (summary, String::from_utf8(out).unwrap())
+    }
+
+    #[test]
+    fn output_order_does_not_depend_on_threads() {
+        let root = env::temp_dir().join(format!("minigrep-parallel-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(&root).unwrap();
+        for i in 0..40 {
+            // files of very different sizes, so they finish in a different order than they start
+            let lines = "somebody\nanybody\n".repeat(1 + (i * 37) % 200);
+            fs::write(root.join(format!("{i:02}.txt")), lines).unwrap();
+        }
+        fs::write(root.join("zz.txt"), "nothing here\n").unwrap();
+
+        let args = ["minigrep", "-n", "-A1", "some", &root.display().to_string()];
+        let args: Vec<String> = args.iter().map(|s| s.to_string()).collect();
+        let config = Config::build(&args).unwrap();
+
+        let (one, single) = search(&config, 1);
+        let (many, parallel) = search(&config, 8);
+        assert_eq!(single, parallel);
+        assert_eq!(one, many);
+        assert!(many.matched);
+        // every file gets a separator before it except the first, and zz.txt has nothing to print
+        assert_eq!(39, single.lines().filter(|line| *line == "--").count());
+        assert!(single
+            .lines()
+            .next()
+            .unwrap()
+            .ends_with("00.txt:1:somebody"));
+
+        fs::remove_dir_all(root).unwrap();
+    }
+}
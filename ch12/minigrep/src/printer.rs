@@ -0,0 +1,560 @@
+// everything about how results end up on the screen lives here, so lib.rs only has to decide what matched
+// output looks like grep's: `path:line:column:offset:text`, where each prefix is only there when asked for
+// context lines use `-` instead of `:` and groups of lines that aren't next to each other are split by `--`
+// with -U one match can cover several lines, they're printed together behind one prefix with a line range like `7-8`
+// -c, -l and -L don't print lines at all, they print one summary line per file once the file is done
+// --json prints one JSON object per event instead, see json.rs
+// with --color every part of that gets its own color, see color.rs
+// a binary file gets one `minigrep: path: binary file matches` line instead of its matching lines, like grep
+
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::{Path, PathBuf};
+
+use crate::color::Colors;
+use crate::searcher::Sink;
+use crate::{json, Match};
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum OutputMode {
+    // print every matching line, the normal grep output
+    #[default]
+    Lines,
+    // -o, print just the matched part of each line, one per line
+    OnlyMatching,
+    // -c, print how many lines matched in each file
+    Count,
+    // -l, print the names of files with at least one match
+    FilesWithMatches,
+    // -L, print the names of files without any match
+    FilesWithoutMatch,
+    // --json, JSON Lines for other programs to read
+    Json,
+}
+
+// --binary-files, what to do with a file once it turns out to be binary
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum BinaryFiles {
+    // say that it matches instead of printing the lines
+    #[default]
+    Binary,
+    // -I, act like it has no matches
+    WithoutMatch,
+    // -a, print the lines like in any other file
+    Text,
+}
+
+impl BinaryFiles {
+    pub fn parse(value: &str) -> Result<BinaryFiles, String> {
+        match value {
+            "binary" => Ok(BinaryFiles::Binary),
+            "without-match" => Ok(BinaryFiles::WithoutMatch),
+            "text" => Ok(BinaryFiles::Text),
+            _ => Err(format!(
+                "invalid value '{value}' for '--binary-files': expected binary, without-match or text"
+            )),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct PrintOptions {
+    pub mode: OutputMode,
+    // -H, start every line with the path of the file it came from
+    pub with_filename: bool,
+    // -n, the 1-based line number
+    pub line_number: bool,
+    // -b, the byte offset of the start of the line in the file
+    pub byte_offset: bool,
+    // --column, the 1-based byte column of the first match in the line
+    pub column: bool,
+    // -B, how many lines to show before each match
+    pub before_context: usize,
+    // -A, how many lines to show after each match
+    pub after_context: usize,
+    // --color, None prints plain text
+    pub colors: Option<Colors>,
+    // --show-pattern, print which pattern matched after the other prefixes
+    pub show_pattern: bool,
+    pub binary_files: BinaryFiles,
+}
+
+impl PrintOptions {
+    // `--` only goes between groups of lines when context is turned on, without it grep doesn't separate
+    // anything either
+    pub fn uses_separators(&self) -> bool {
+        self.mode == OutputMode::Lines && (self.before_context > 0 || self.after_context > 0)
+    }
+
+    // the `--` line between groups, colored like the other separators
+    pub fn group_separator(&self) -> String {
+        let colors = self.colors.unwrap_or_else(Colors::plain);
+        format!("{}\n", colors.separator.paint("--"))
+    }
+}
+
+// where the match in a printed line is, context lines don't have one
+struct Hit<'a> {
+    // 1-based, in bytes
+    column: usize,
+    pattern: Option<&'a str>,
+    // with --fuzzy, how many typos the match has
+    distance: Option<usize>,
+}
+
+pub struct Printer<W: Write> {
+    out: W,
+    options: PrintOptions,
+    colors: Colors,
+    path: PathBuf,
+    // matching lines in the current file
+    count: usize,
+    // and how many matches there were in them, only --json reports this
+    matches: usize,
+    // the current file has a NUL byte in it
+    binary: bool,
+    // the last line printed in the current file, to know if the next one follows straight on from it
+    last_printed: Option<usize>,
+    printed_anything: bool,
+}
+
+impl<W: Write> Printer<W> {
+    pub fn new(out: W, options: PrintOptions) -> Printer<W> {
+        Printer {
+            out,
+            options,
+            colors: options.colors.unwrap_or_else(Colors::plain),
+            path: PathBuf::new(),
+            count: 0,
+            matches: 0,
+            binary: false,
+            last_printed: None,
+            printed_anything: false,
+        }
+    }
+
+    // prints `--` when the next line doesn't carry on from the last printed one
+    fn separate(&mut self, next_line: usize) -> io::Result<()> {
+        let adjacent = self.last_printed.is_some_and(|last| last + 1 == next_line);
+        if self.options.uses_separators() && self.printed_anything && !adjacent {
+            write!(self.out, "{}", self.options.group_separator())?;
+        }
+        Ok(())
+    }
+
+    fn write_line(
+        &mut self,
+        line_number: usize,
+        byte_offset: usize,
+        hit: Option<Hit>,
+        separator: &str,
+        // what's in the input, anything that isn't UTF-8 is shown as �
+        line: &[u8],
+        // the parts of line to highlight
+        ranges: &[Range<usize>],
+    ) -> io::Result<()> {
+        let colors = &self.colors;
+        let separator = colors.separator.paint(separator);
+        // with -U, the line a match ends on
+        let last_line = line_number + line.iter().filter(|&&b| b == b'\n').count();
+        if self.options.with_filename {
+            let path = self.path.display().to_string();
+            write!(self.out, "{}{separator}", colors.path.paint(&path))?;
+        }
+        if self.options.line_number {
+            let line_number = match last_line > line_number {
+                true => format!("{line_number}-{last_line}"),
+                false => line_number.to_string(),
+            };
+            write!(
+                self.out,
+                "{}{separator}",
+                colors.line_number.paint(&line_number)
+            )?;
+        }
+        if self.options.column {
+            if let Some(hit) = &hit {
+                let column = hit.column.to_string();
+                write!(self.out, "{}{separator}", colors.column.paint(&column))?;
+            }
+        }
+        if self.options.byte_offset {
+            let byte_offset = byte_offset.to_string();
+            write!(
+                self.out,
+                "{}{separator}",
+                colors.byte_offset.paint(&byte_offset)
+            )?;
+        }
+        if let Some(hit) = hit {
+            if let (true, Some(pattern)) = (self.options.show_pattern, hit.pattern) {
+                write!(self.out, "{pattern}{separator}")?;
+            }
+            // the ~ keeps it from being mistaken for a line number or a column
+            if let Some(distance) = hit.distance {
+                write!(self.out, "~{distance}{separator}")?;
+            }
+        }
+        let text = |range: Range<usize>| String::from_utf8_lossy(&line[range]);
+        let mut written = 0;
+        for range in ranges {
+            let matched = text(range.clone());
+            let matched = colors.matched.paint(&matched);
+            write!(self.out, "{}{matched}", text(written..range.start))?;
+            written = range.end;
+        }
+        writeln!(self.out, "{}", text(written..line.len()))?;
+        self.last_printed = Some(last_line);
+        self.printed_anything = true;
+        Ok(())
+    }
+
+    pub fn into_inner(self) -> W {
+        self.out
+    }
+}
+
+// 1-based and in bytes like grep, counting from the start of the line at is on
+fn column(text: &[u8], at: usize) -> usize {
+    let line_start = text[..at]
+        .iter()
+        .rposition(|&b| b == b'\n')
+        .map_or(0, |i| i + 1);
+    at - line_start + 1
+}
+
+// the printer is the sink minigrep's own searches go to
+impl<W: Write> Sink for Printer<W> {
+    fn begin(&mut self, path: &Path) -> io::Result<()> {
+        self.path = path.to_path_buf();
+        self.count = 0;
+        self.matches = 0;
+        self.binary = false;
+        self.last_printed = None;
+        Ok(())
+    }
+
+    // returns false when the binary file shouldn't be searched any further
+    fn binary(&mut self) -> bool {
+        match self.options.binary_files {
+            BinaryFiles::Binary => {
+                self.binary = true;
+                true
+            }
+            BinaryFiles::WithoutMatch => false,
+            BinaryFiles::Text => true,
+        }
+    }
+
+    fn matched(&mut self, m: &Match) -> io::Result<()> {
+        self.count += 1;
+        self.matches += m.ranges.len();
+        match self.options.mode {
+            OutputMode::Lines | OutputMode::OnlyMatching if self.binary => {
+                // is_done stops the search after this, the one line is all there is to say
+                let path = self.path.display();
+                return writeln!(self.out, "minigrep: {path}: binary file matches");
+            }
+            OutputMode::Lines => {}
+            OutputMode::Json => {
+                if self.count == 1 {
+                    writeln!(self.out, "{}", json::begin_file(&self.path))?;
+                }
+                return writeln!(self.out, "{}", json::matched(&self.path, m));
+            }
+            OutputMode::OnlyMatching => {
+                for (i, range) in m.ranges.iter().enumerate() {
+                    // like grep -o, there's nothing to show for a match that's empty
+                    if range.is_empty() {
+                        continue;
+                    }
+                    let byte_offset = m.byte_offset + range.start;
+                    // with -U the match can be a few lines into m.line
+                    let line_number = m.line_number
+                        + m.line[..range.start]
+                            .iter()
+                            .filter(|&&b| b == b'\n')
+                            .count();
+                    let text = &m.line[range.clone()];
+                    let hit = Hit {
+                        column: column(m.line, range.start),
+                        pattern: m.patterns.get(i).copied(),
+                        distance: m.distances.get(i).copied(),
+                    };
+                    // the whole of text is the match
+                    let whole = [Range {
+                        start: 0,
+                        end: text.len(),
+                    }];
+                    self.write_line(line_number, byte_offset, Some(hit), ":", text, &whole)?;
+                }
+                return Ok(());
+            }
+            // the summary modes only need the count
+            _ => return Ok(()),
+        }
+
+        self.separate(m.line_number)?;
+        let hit = Hit {
+            column: column(m.line, m.ranges.first().map_or(0, |range| range.start)),
+            pattern: m.patterns.first().copied(),
+            // the closest match is the one that counts for the line
+            distance: m.distances.iter().min().copied(),
+        };
+        self.write_line(
+            m.line_number,
+            m.byte_offset,
+            Some(hit),
+            ":",
+            m.line,
+            &m.ranges,
+        )
+    }
+
+    // the searcher decides which lines are context, only the normal output shows them
+    fn context(&mut self, line_number: usize, byte_offset: usize, line: &str) -> io::Result<()> {
+        if self.options.mode != OutputMode::Lines || self.binary {
+            return Ok(());
+        }
+        self.separate(line_number)?;
+        self.write_line(line_number, byte_offset, None, "-", line.as_bytes(), &[])
+    }
+
+    // prints the per file summary for -c, -l and -L
+    fn finish(&mut self) -> io::Result<()> {
+        let path = self.path.display().to_string();
+        let path = self.colors.path.paint(&path);
+        match self.options.mode {
+            OutputMode::Count if self.options.with_filename => {
+                let separator = self.colors.separator.paint(":");
+                writeln!(self.out, "{path}{separator}{}", self.count)
+            }
+            OutputMode::Count => writeln!(self.out, "{}", self.count),
+            OutputMode::FilesWithMatches if self.count > 0 => writeln!(self.out, "{path}"),
+            OutputMode::FilesWithoutMatch if self.count == 0 => writeln!(self.out, "{path}"),
+            OutputMode::Json if self.count > 0 => {
+                let end = json::end_file(&self.path, self.count, self.matches);
+                writeln!(self.out, "{end}")
+            }
+            _ => Ok(()),
+        }
+    }
+
+    // once -l or -L has seen one match the answer for this file can't change anymore, and neither can
+    // "binary file matches"
+    fn is_done(&self) -> bool {
+        let listing = match self.options.mode {
+            OutputMode::FilesWithMatches | OutputMode::FilesWithoutMatch => true,
+            OutputMode::Lines | OutputMode::OnlyMatching => self.binary,
+            _ => false,
+        };
+        listing && self.count > 0
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::SearcherBuilder;
+    use std::ops::Range;
+
+    #[test]
+    fn prefixes_come_in_grep_order() {
+        let m = Match {
+            line_number: 2,
+            byte_offset: 6,
+            line: b"safe, fast, productive.",
+            ranges: vec![Range { start: 15, end: 19 }],
+            patterns: vec!["duct"],
+            distances: Vec::new(),
+        };
+
+        let mut printer = Printer::new(Vec::new(), PrintOptions::default());
+        printer.matched(&m).unwrap();
+        assert_eq!(
+            "safe, fast, productive.\n",
+            String::from_utf8(printer.into_inner()).unwrap()
+        );
+
+        let options = PrintOptions {
+            with_filename: true,
+            line_number: true,
+            byte_offset: true,
+            column: true,
+            ..PrintOptions::default()
+        };
+        let mut printer = Printer::new(Vec::new(), options);
+        printer.begin(Path::new("poem.txt")).unwrap();
+        printer.matched(&m).unwrap();
+        assert_eq!(
+            "poem.txt:2:16:6:safe, fast, productive.\n",
+            String::from_utf8(printer.into_inner()).unwrap()
+        );
+    }
+
+    // searches the poem for query, with the context the options ask for
+    fn print_poem(query: &str, options: PrintOptions) -> String {
+        let poem = include_str!("../poem.txt");
+        let searcher = SearcherBuilder::new()
+            .pattern(query)
+            .context(options.before_context, options.after_context)
+            .build()
+            .unwrap();
+        let mut printer = Printer::new(Vec::new(), options);
+        searcher
+            .search_reader(Path::new("poem.txt"), poem.as_bytes(), &mut printer)
+            .unwrap();
+        String::from_utf8(printer.into_inner()).unwrap()
+    }
+
+    #[test]
+    fn context_windows_merge_and_separate() {
+        let options = PrintOptions {
+            line_number: true,
+            before_context: 1,
+            after_context: 1,
+            ..PrintOptions::default()
+        };
+        // the windows around lines 1 and 2 merge, 6 and 9 are close enough to join up too
+        assert_eq!(
+            "\
+1:I'm nobody! Who are you?
+2:Are you nobody, too?
+3-Then there's a pair of us - don't tell!
+--
+5-
+6:How dreary to be somebody!
+7-How public, like a frog
+8-To tell your name the livelong day
+9:To an admiring bog!
+",
+            print_poem("bo", options)
+        );
+    }
+
+    #[test]
+    fn adjacent_groups_have_no_separator() {
+        let options = PrintOptions {
+            after_context: 2,
+            ..PrintOptions::default()
+        };
+        assert_eq!(
+            "\
+How dreary to be somebody!
+How public, like a frog
+To tell your name the livelong day
+To an admiring bog!
+",
+            print_poem("How", options)
+        );
+    }
+
+    #[test]
+    fn summary_modes() {
+        let count = PrintOptions {
+            mode: OutputMode::Count,
+            ..PrintOptions::default()
+        };
+        assert_eq!("4\n", print_poem("bo", count));
+
+        let files = PrintOptions {
+            mode: OutputMode::FilesWithMatches,
+            ..PrintOptions::default()
+        };
+        assert_eq!("poem.txt\n", print_poem("bo", files));
+        assert_eq!("", print_poem("frog!", files));
+
+        let files = PrintOptions {
+            mode: OutputMode::FilesWithoutMatch,
+            ..PrintOptions::default()
+        };
+        assert_eq!("poem.txt\n", print_poem("frog!", files));
+    }
+
+    #[test]
+    fn only_matching() {
+        let options = PrintOptions {
+            mode: OutputMode::OnlyMatching,
+            column: true,
+            before_context: 2,
+            ..PrintOptions::default()
+        };
+        // the context lines are left out, there's no matched part to show for them
+        assert_eq!("7:bo\n11:bo\n22:bo\n16:bo\n", print_poem("bo", options));
+        // an empty pattern matches every line, but has nothing to print
+        assert_eq!("", print_poem("", options));
+    }
+
+    #[test]
+    fn colors() {
+        let options = PrintOptions {
+            with_filename: true,
+            line_number: true,
+            colors: Some(Colors::default()),
+            ..PrintOptions::default()
+        };
+        let colored = print_poem("frog", options);
+        assert_eq!(
+            "\x1b[35mpoem.txt\x1b[0m\x1b[36m:\x1b[0m\x1b[32m7\x1b[0m\x1b[36m:\x1b[0m\
+How public, like a \x1b[1;31mfrog\x1b[0m\n",
+            colored
+        );
+    }
+
+    #[test]
+    fn multiline_matches_show_line_ranges() {
+        let m = Match {
+            line_number: 8,
+            byte_offset: 0,
+            line: b"To tell your name the livelong day\nTo an admiring bog!",
+            ranges: vec![Range { start: 31, end: 40 }],
+            patterns: vec!["day\nTo an"],
+            distances: Vec::new(),
+        };
+        let options = PrintOptions {
+            line_number: true,
+            column: true,
+            ..PrintOptions::default()
+        };
+        let mut printer = Printer::new(Vec::new(), options);
+        printer.matched(&m).unwrap();
+        let only = PrintOptions {
+            mode: OutputMode::OnlyMatching,
+            ..options
+        };
+        let mut only_matching = Printer::new(Vec::new(), only);
+        only_matching.matched(&m).unwrap();
+        assert_eq!(
+            "8-9:32:To tell your name the livelong day\nTo an admiring bog!\n8-9:32:day\nTo an\n",
+            String::from_utf8(printer.into_inner()).unwrap()
+                + &String::from_utf8(only_matching.into_inner()).unwrap()
+        );
+    }
+
+    #[test]
+    fn multiline_newlines_of_crlf_lines() {
+        // the \n of a \r\n isn't part of the line, what's left of the match is empty
+        let searcher = SearcherBuilder::new()
+            .pattern(r"\n")
+            .multiline(true)
+            .build()
+            .unwrap();
+        let search = |options: PrintOptions| {
+            let mut printer = Printer::new(Vec::new(), options);
+            searcher
+                .search_reader(Path::new("crlf.txt"), &b"a\r\nb\n"[..], &mut printer)
+                .unwrap();
+            String::from_utf8(printer.into_inner()).unwrap()
+        };
+        let only = PrintOptions {
+            mode: OutputMode::OnlyMatching,
+            ..PrintOptions::default()
+        };
+        assert_eq!("", search(only));
+        let colored = PrintOptions {
+            colors: Some(Colors::default()),
+            ..PrintOptions::default()
+        };
+        assert_eq!("a\nb\n", search(colored));
+    }
+}
@@ -0,0 +1,418 @@
+// --query, lines are picked by a boolean expression over plain text terms instead of a single pattern:
+//
+//     error AND (disk OR network) AND NOT retry
+//
+// NOT binds tightest, then AND, then OR, and parentheses group. The operators have to be in capitals,
+// `and` is just another word to look for. A term is a word or a "quoted phrase" (with \" and \\ inside the
+// quotes), and it's looked for anywhere in the line the way search and search_case_insensitive do, only
+// with the literal matcher so the query isn't folded again for every line. A term can start with i: to
+// ignore case or c: to respect it, like `i:error AND c:"Disk Full"`, otherwise -i, -s and -S decide the
+// same as they do for a pattern. The terms that aren't under a NOT are the ones that get highlighted.
+
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use crate::matcher::{has_uppercase, Matcher, MatcherKind};
+use crate::regex::Bounds;
+use crate::searcher::CaseMode;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct QueryError {
+    pub query: String,
+    // counting characters from 1, like an editor's column
+    pub column: usize,
+    pub message: String,
+}
+
+impl fmt::Display for QueryError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // the same layout as a regex error
+        writeln!(f, "query parse error:")?;
+        writeln!(f, "    {}", self.query)?;
+        writeln!(f, "    {}^", " ".repeat(self.column - 1))?;
+        write!(f, "error: {} at column {}", self.message, self.column)
+    }
+}
+
+impl Error for QueryError {}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Token {
+    And,
+    Or,
+    Not,
+    Open,
+    Close,
+    // ignore_case is None when the term had no i: or c: in front of it
+    Term {
+        text: String,
+        ignore_case: Option<bool>,
+    },
+}
+
+impl fmt::Display for Token {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        match self {
+            Token::And => write!(f, "AND"),
+            Token::Or => write!(f, "OR"),
+            Token::Not => write!(f, "NOT"),
+            Token::Open => write!(f, "("),
+            Token::Close => write!(f, ")"),
+            Token::Term { text, .. } => write!(f, "{text:?}"),
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Expr {
+    // index into Query::terms
+    Term(usize),
+    Not(Box<Expr>),
+    And(Box<Expr>, Box<Expr>),
+    Or(Box<Expr>, Box<Expr>),
+}
+
+#[derive(Debug, Clone)]
+struct Term {
+    matcher: Matcher,
+    // somewhere under a NOT, so it's never what made a line match
+    negated: bool,
+}
+
+#[derive(Debug, Clone)]
+pub struct Query {
+    source: String,
+    expr: Expr,
+    terms: Vec<Term>,
+}
+
+impl Query {
+    // case is what the terms without a flag get, bounds is -w or -x for every term
+    pub fn parse(source: &str, case: CaseMode, bounds: Bounds) -> Result<Query, QueryError> {
+        let tokens = tokenize(source)?;
+        let mut parser = Parser {
+            source,
+            tokens,
+            next: 0,
+            case,
+            bounds,
+            terms: Vec::new(),
+            negated: 0,
+        };
+        let expr = parser.or()?;
+        if let Some((column, token)) = parser.tokens.get(parser.next) {
+            let message = match token {
+                Token::Close => "unmatched )".to_string(),
+                token => format!("expected AND, OR or the end of the query, found {token}"),
+            };
+            return Err(parser.error(*column, message));
+        }
+        Ok(Query {
+            source: source.to_string(),
+            expr,
+            terms: parser.terms,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.source
+    }
+
+    pub fn is_match(&self, line: &str) -> bool {
+        self.eval(&self.expr, line)
+    }
+
+    fn eval(&self, expr: &Expr, line: &str) -> bool {
+        match expr {
+            Expr::Term(i) => self.terms[*i].matcher.is_match(line),
+            Expr::Not(expr) => !self.eval(expr, line),
+            Expr::And(left, right) => self.eval(left, line) && self.eval(right, line),
+            Expr::Or(left, right) => self.eval(left, line) || self.eval(right, line),
+        }
+    }
+
+    // the first term found at or after start in a line the query matches, leftmost and then longest.
+    // Only the terms that aren't negated are ever found, so a line picked for what's not in it (NOT frog)
+    // has nothing to find at all, see Matcher::selects
+    pub fn find_at(&self, line: &str, start: usize) -> Option<(Range<usize>, &str)> {
+        if !self.is_match(line) {
+            return None;
+        }
+        self.terms
+            .iter()
+            .filter(|term| !term.negated)
+            .filter_map(|term| term.matcher.find_pattern_at(line, start))
+            .min_by_key(|(range, _)| (range.start, usize::MAX - range.end))
+    }
+}
+
+// splits the query into tokens, each with the column it starts at
+fn tokenize(source: &str) -> Result<Vec<(usize, Token)>, QueryError> {
+    let chars: Vec<char> = source.chars().collect();
+    let error = |column: usize, message: &str| QueryError {
+        query: source.to_string(),
+        column,
+        message: message.to_string(),
+    };
+    // what ends a word
+    let special = |c: char| c.is_whitespace() || matches!(c, '(' | ')' | '"');
+    let mut tokens = Vec::new();
+    let mut i = 0;
+    while i < chars.len() {
+        let column = i + 1;
+        let c = chars[i];
+        if c.is_whitespace() {
+            i += 1;
+            continue;
+        }
+        if c == '(' || c == ')' {
+            tokens.push((column, if c == '(' { Token::Open } else { Token::Close }));
+            i += 1;
+            continue;
+        }
+        // i: and c: only count when there's a term right after them, `i:` on its own is a word
+        let ignore_case = match (c, chars.get(i + 1), chars.get(i + 2)) {
+            ('i' | 'c', Some(':'), Some(&next)) if next == '"' || !special(next) => {
+                i += 2;
+                Some(c == 'i')
+            }
+            _ => None,
+        };
+        let mut text = String::new();
+        let quoted = chars[i] == '"';
+        if quoted {
+            let quote = i + 1;
+            i += 1;
+            loop {
+                match (chars.get(i), chars.get(i + 1)) {
+                    (None, _) => return Err(error(quote, "unclosed quote")),
+                    (Some('"'), _) => break,
+                    (Some('\\'), Some(&escaped @ ('"' | '\\'))) => {
+                        text.push(escaped);
+                        i += 1;
+                    }
+                    (Some(&c), _) => text.push(c),
+                }
+                i += 1;
+            }
+            i += 1;
+            // an empty phrase would be in every line
+            if text.is_empty() {
+                return Err(error(quote, "empty phrase"));
+            }
+        } else {
+            while i < chars.len() && !special(chars[i]) {
+                text.push(chars[i]);
+                i += 1;
+            }
+        }
+        let token = match (text.as_str(), ignore_case, quoted) {
+            ("AND", None, false) => Token::And,
+            ("OR", None, false) => Token::Or,
+            ("NOT", None, false) => Token::Not,
+            _ => Token::Term { text, ignore_case },
+        };
+        tokens.push((column, token));
+    }
+    Ok(tokens)
+}
+
+// recursive descent, one function per level of precedence
+struct Parser<'a> {
+    source: &'a str,
+    tokens: Vec<(usize, Token)>,
+    next: usize,
+    case: CaseMode,
+    bounds: Bounds,
+    terms: Vec<Term>,
+    // how many NOTs the parser is inside of
+    negated: usize,
+}
+
+impl Parser<'_> {
+    fn error(&self, column: usize, message: String) -> QueryError {
+        QueryError {
+            query: self.source.to_string(),
+            column,
+            message,
+        }
+    }
+
+    // takes the next token when it's the one asked for
+    fn eat(&mut self, token: Token) -> bool {
+        let found = self
+            .tokens
+            .get(self.next)
+            .is_some_and(|(_, next)| *next == token);
+        if found {
+            self.next += 1;
+        }
+        found
+    }
+
+    fn or(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.and()?;
+        while self.eat(Token::Or) {
+            left = Expr::Or(Box::new(left), Box::new(self.and()?));
+        }
+        Ok(left)
+    }
+
+    fn and(&mut self) -> Result<Expr, QueryError> {
+        let mut left = self.not()?;
+        while self.eat(Token::And) {
+            left = Expr::And(Box::new(left), Box::new(self.not()?));
+        }
+        Ok(left)
+    }
+
+    fn not(&mut self) -> Result<Expr, QueryError> {
+        if !self.eat(Token::Not) {
+            return self.primary();
+        }
+        self.negated += 1;
+        let expr = self.not();
+        self.negated -= 1;
+        Ok(Expr::Not(Box::new(expr?)))
+    }
+
+    fn primary(&mut self) -> Result<Expr, QueryError> {
+        let Some((column, token)) = self.tokens.get(self.next).cloned() else {
+            let end = self.source.chars().count() + 1;
+            return Err(self.error(end, "expected a term, the query ends here".to_string()));
+        };
+        self.next += 1;
+        match token {
+            Token::Open => {
+                let expr = self.or()?;
+                if !self.eat(Token::Close) {
+                    return Err(self.error(column, "unclosed (".to_string()));
+                }
+                Ok(expr)
+            }
+            Token::Term { text, ignore_case } => {
+                let ignore_case = ignore_case.unwrap_or(match self.case {
+                    CaseMode::Sensitive => false,
+                    CaseMode::Insensitive => true,
+                    CaseMode::Smart => !has_uppercase(&text, MatcherKind::Literal),
+                });
+                self.terms.push(Term {
+                    matcher: Matcher::literal_bounded(&text, ignore_case, self.bounds),
+                    negated: self.negated > 0,
+                });
+                Ok(Expr::Term(self.terms.len() - 1))
+            }
+            token => Err(self.error(column, format!("expected a term, found {token}"))),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn parse(query: &str) -> Query {
+        Query::parse(query, CaseMode::Sensitive, Bounds::Anywhere).unwrap()
+    }
+
+    fn selected<'a>(query: &Query, contents: &'a str) -> Vec<&'a str> {
+        contents
+            .lines()
+            .filter(|line| query.is_match(line))
+            .collect()
+    }
+
+    #[test]
+    fn precedence_and_parentheses() {
+        let log = "\
+error: disk full
+error: network down, retry
+warning: disk slow
+error: cpu hot";
+        let query = parse("error AND (disk OR network) AND NOT retry");
+        assert_eq!(vec!["error: disk full"], selected(&query, log));
+
+        // AND before OR, NOT before AND
+        let query = parse("warning OR error AND NOT disk AND NOT network");
+        assert_eq!(
+            vec!["warning: disk slow", "error: cpu hot"],
+            selected(&query, log)
+        );
+        let query = parse("NOT NOT cpu");
+        assert_eq!(vec!["error: cpu hot"], selected(&query, log));
+    }
+
+    #[test]
+    fn phrases_and_case_flags() {
+        let log = "Disk Full\ndisk full\n\"quoted\" AND\n";
+        let query = parse(r#"i:"DISK FULL" AND NOT c:"disk""#);
+        assert_eq!(vec!["Disk Full"], selected(&query, log));
+        // inside quotes the operators are words, and \" is a quote
+        let query = parse(r#""\"quoted\" AND""#);
+        assert_eq!(vec!["\"quoted\" AND"], selected(&query, log));
+        // lowercase operators are words too
+        assert!(parse("and").is_match("this and that"));
+
+        let smart = Query::parse("disk AND Full", CaseMode::Smart, Bounds::Anywhere).unwrap();
+        assert_eq!(vec!["Disk Full"], selected(&smart, log));
+    }
+
+    #[test]
+    fn highlights_the_terms_that_are_not_negated() {
+        let query = parse("frog OR bog AND NOT public");
+        assert_eq!(
+            Some((19..23, "frog")),
+            query.find_at("How public, like a frog", 0)
+        );
+        assert_eq!(
+            Some((15..18, "bog")),
+            query.find_at("To an admiring bog!", 0)
+        );
+        assert_eq!(None, query.find_at("To an admiring bog!", 16));
+
+        // the line matches, but there's nothing in it to highlight
+        let query = parse("NOT frog");
+        assert!(query.is_match("Are you nobody?"));
+        assert_eq!(None, query.find_at("Are you nobody?", 0));
+    }
+
+    #[test]
+    fn errors_point_at_the_column() {
+        let error = |query: &str| {
+            let e = Query::parse(query, CaseMode::Sensitive, Bounds::Anywhere).unwrap_err();
+            (e.column, e.message)
+        };
+        assert_eq!(
+            (11, "unclosed (".to_string()),
+            error("error AND (disk OR net")
+        );
+        assert_eq!((7, "unmatched )".to_string()), error("error )"));
+        assert_eq!(
+            (11, "expected a term, the query ends here".to_string()),
+            error("error AND ")
+        );
+        assert_eq!(
+            (
+                7,
+                "expected AND, OR or the end of the query, found \"disk\"".to_string()
+            ),
+            error("error disk")
+        );
+        assert_eq!(
+            (11, "expected a term, found OR".to_string()),
+            error("error AND OR")
+        );
+        assert_eq!((3, "unclosed quote".to_string()), error("a \"disk"));
+        assert_eq!(
+            (1, "expected a term, the query ends here".to_string()),
+            error("")
+        );
+
+        let e = Query::parse("a AND (b", CaseMode::Sensitive, Bounds::Anywhere).unwrap_err();
+        assert_eq!(
+            "query parse error:\n    a AND (b\n          ^\nerror: unclosed ( at column 7",
+            e.to_string()
+        );
+    }
+}
@@ -0,0 +1,1298 @@
+// a small regular expression engine so minigrep doesn't need any crates
+// the pattern is parsed into a syntax tree, compiled into a list of instructions and then run with a
+// Pike VM. The VM walks every possible path through the pattern at the same time, so matching takes
+// time proportional to (length of text) * (size of pattern) and can never blow up exponentially the way
+// a backtracking engine can on patterns like (a*)*b.
+//
+// supported syntax:
+// literals, `.`, `[...]` / `[^...]` classes (ranges, \d \w \s and [:alpha:] style POSIX classes),
+// `^` `$` (line anchors), `\A` `\z` (text anchors), `\b` `\B`, alternation `|`, repetition `*` `+` `?`
+// `{n}` `{n,}` `{n,m}` `{,m}` (add `?` for the lazy version), capture groups `(...)`, named groups `(?<name>...)`,
+// non-capturing groups `(?:...)` and the flags `(?i)` (ignore case) and `(?s)` (`.` matches newline)
+
+use std::error::Error;
+use std::fmt;
+use std::ops::Range;
+
+use crate::casefold;
+
+// upper bounds so a pattern like a{1000}{1000} can't make us allocate forever
+const MAX_REPEAT: u32 = 1000;
+const MAX_PROGRAM: usize = 100_000;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegexError {
+    pub pattern: String,
+    // character offset into the pattern where the problem was found
+    pub position: usize,
+    pub message: String,
+}
+
+impl fmt::Display for RegexError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        // point at the problem the same way rustc does
+        writeln!(f, "regex parse error:")?;
+        writeln!(f, "    {}", self.pattern)?;
+        writeln!(f, "    {}^", " ".repeat(self.position))?;
+        write!(f, "error: {} at position {}", self.message, self.position)
+    }
+}
+
+impl Error for RegexError {}
+
+// \w, \b and -w use this, it's the unicode-aware version of [0-9A-Za-z_]
+// unicode counts combining marks and connector punctuation as part of a word too, so the accent in a
+// decomposed "cafe\u{301}" doesn't end the word early. std can't tell us which characters are marks, the
+// ranges here are the blocks of combining diacritics
+pub(crate) fn is_word_char(c: char) -> bool {
+    c.is_alphanumeric()
+        || matches!(c,
+            '_' | '\u{203F}' | '\u{2040}' | '\u{2054}' | '\u{FE33}' | '\u{FE34}' | '\u{FE4D}'..='\u{FE4F}'
+            | '\u{FF3F}' | '\u{200C}' | '\u{200D}'
+            | '\u{0300}'..='\u{036F}' | '\u{1AB0}'..='\u{1AFF}' | '\u{1DC0}'..='\u{1DFF}'
+            | '\u{20D0}'..='\u{20FF}' | '\u{FE20}'..='\u{FE2F}')
+}
+
+// case insensitive comparisons compare the folded version of both characters
+// the VM steps one character at a time, so this is simple folding: 'ſ' matches 's' and 'ς' matches 'Σ', but
+// 'ß' doesn't match "ss" the way it does in the literal search
+fn fold(c: char) -> char {
+    casefold::fold_simple(c)
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Look {
+    StartLine,
+    EndLine,
+    StartText,
+    EndText,
+    WordBoundary,
+    NotWordBoundary,
+    // the halves of a word boundary -w needs: no word character right before or right after
+    NoWordBefore,
+    NoWordAfter,
+}
+
+impl Look {
+    fn holds(self, text: &str, pos: usize) -> bool {
+        let prev = text[..pos].chars().next_back();
+        let next = text[pos..].chars().next();
+        match self {
+            Look::StartLine => prev.is_none() || prev == Some('\n'),
+            Look::EndLine => next.is_none() || next == Some('\n'),
+            Look::StartText => pos == 0,
+            Look::EndText => pos == text.len(),
+            Look::WordBoundary => prev.is_some_and(is_word_char) != next.is_some_and(is_word_char),
+            Look::NotWordBoundary => {
+                prev.is_some_and(is_word_char) == next.is_some_and(is_word_char)
+            }
+            Look::NoWordBefore => !prev.is_some_and(is_word_char),
+            Look::NoWordAfter => !next.is_some_and(is_word_char),
+        }
+    }
+}
+
+// the named classes that can appear as \d, \w, \s or [:name:]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+enum Named {
+    Digit,
+    Word,
+    Space,
+    Alpha,
+    Alnum,
+    Upper,
+    Lower,
+    Punct,
+    XDigit,
+}
+
+impl Named {
+    fn matches(self, c: char) -> bool {
+        match self {
+            Named::Digit => c.is_numeric(),
+            Named::Word => is_word_char(c),
+            Named::Space => c.is_whitespace(),
+            Named::Alpha => c.is_alphabetic(),
+            Named::Alnum => c.is_alphanumeric(),
+            Named::Upper => c.is_uppercase(),
+            Named::Lower => c.is_lowercase(),
+            Named::Punct => c.is_ascii_punctuation(),
+            Named::XDigit => c.is_ascii_hexdigit(),
+        }
+    }
+
+    fn posix(name: &str) -> Option<Named> {
+        match name {
+            "digit" => Some(Named::Digit),
+            "word" => Some(Named::Word),
+            "space" => Some(Named::Space),
+            "alpha" => Some(Named::Alpha),
+            "alnum" => Some(Named::Alnum),
+            "upper" => Some(Named::Upper),
+            "lower" => Some(Named::Lower),
+            "punct" => Some(Named::Punct),
+            "xdigit" => Some(Named::XDigit),
+            _ => None,
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum ClassItem {
+    Range(char, char),
+    Named(Named, bool), // bool: negated, like \D
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Class {
+    items: Vec<ClassItem>,
+    negated: bool,
+    case_insensitive: bool,
+}
+
+impl Class {
+    fn named(named: Named, negated: bool, case_insensitive: bool) -> Class {
+        Class {
+            items: vec![ClassItem::Named(named, negated)],
+            negated: false,
+            case_insensitive,
+        }
+    }
+
+    fn contains(&self, c: char) -> bool {
+        self.items.iter().any(|item| match *item {
+            ClassItem::Range(start, end) => start <= c && c <= end,
+            ClassItem::Named(named, negated) => named.matches(c) != negated,
+        })
+    }
+
+    fn matches(&self, c: char) -> bool {
+        let mut hit = self.contains(c);
+        if !hit && self.case_insensitive {
+            // [a-z] should also match 'Q' when ignoring case, so try the other cases of c too
+            hit = c
+                .to_lowercase()
+                .chain(c.to_uppercase())
+                .chain(Some(fold(c)))
+                .any(|other| self.contains(other));
+        }
+        hit != self.negated
+    }
+}
+
+#[derive(Debug, Clone)]
+enum Node {
+    Empty,
+    Char(char, bool), // bool: case insensitive
+    Any(bool),        // bool: also matches '\n'
+    Class(Class),
+    Look(Look),
+    Group(Box<Node>, usize),
+    Concat(Vec<Node>),
+    Alternate(Vec<Node>),
+    Repeat {
+        node: Box<Node>,
+        min: u32,
+        max: Option<u32>,
+        greedy: bool,
+    },
+}
+
+// flags set with (?i) or (?s) last until the end of the group they appear in
+#[derive(Debug, Clone, Copy, Default)]
+struct Flags {
+    case_insensitive: bool,
+    dot_all: bool,
+}
+
+struct Parser<'p> {
+    pattern: &'p str,
+    chars: Vec<char>,
+    pos: usize,
+    names: Vec<Option<String>>,
+}
+
+impl<'p> Parser<'p> {
+    fn new(pattern: &'p str) -> Parser<'p> {
+        Parser {
+            pattern,
+            chars: pattern.chars().collect(),
+            pos: 0,
+            // group 0 is the whole match and has no name
+            names: vec![None],
+        }
+    }
+
+    fn error(&self, position: usize, message: &str) -> RegexError {
+        RegexError {
+            pattern: self.pattern.to_string(),
+            position,
+            message: message.to_string(),
+        }
+    }
+
+    fn peek(&self) -> Option<char> {
+        self.chars.get(self.pos).copied()
+    }
+
+    fn peek_at(&self, offset: usize) -> Option<char> {
+        self.chars.get(self.pos + offset).copied()
+    }
+
+    fn eat(&mut self, c: char) -> bool {
+        if self.peek() == Some(c) {
+            self.pos += 1;
+            true
+        } else {
+            false
+        }
+    }
+
+    fn parse(mut self, mut flags: Flags) -> Result<(Node, Vec<Option<String>>), RegexError> {
+        let node = self.parse_alternation(&mut flags)?;
+        if self.peek() == Some(')') {
+            return Err(self.error(self.pos, "unopened group"));
+        }
+        Ok((node, self.names))
+    }
+
+    fn parse_alternation(&mut self, flags: &mut Flags) -> Result<Node, RegexError> {
+        let mut branches = vec![self.parse_concat(flags)?];
+        while self.eat('|') {
+            branches.push(self.parse_concat(flags)?);
+        }
+        if branches.len() == 1 {
+            Ok(branches.pop().unwrap())
+        } else {
+            Ok(Node::Alternate(branches))
+        }
+    }
+
+    fn parse_concat(&mut self, flags: &mut Flags) -> Result<Node, RegexError> {
+        let mut items = Vec::new();
+        while let Some(c) = self.peek() {
+            if c == '|' || c == ')' {
+                break;
+            }
+            // a bare flag group like (?i) changes the flags but doesn't produce a node
+            if let Some(atom) = self.parse_atom(flags)? {
+                let atom = self.parse_repetition(atom)?;
+                items.push(atom);
+            }
+        }
+        match items.len() {
+            0 => Ok(Node::Empty),
+            1 => Ok(items.pop().unwrap()),
+            _ => Ok(Node::Concat(items)),
+        }
+    }
+
+    fn parse_repetition(&mut self, mut node: Node) -> Result<Node, RegexError> {
+        loop {
+            let start = self.pos;
+            let (min, max) = match self.peek() {
+                Some('*') => (0, None),
+                Some('+') => (1, None),
+                Some('?') => (0, Some(1)),
+                Some('{') => match self.parse_counted()? {
+                    Some(range) => range,
+                    // not a counted repetition, the '{' is just a literal
+                    None => return Ok(node),
+                },
+                _ => return Ok(node),
+            };
+            if self.pos == start {
+                self.pos += 1;
+            }
+            let greedy = !self.eat('?');
+            node = Node::Repeat {
+                node: Box::new(node),
+                min,
+                max,
+                greedy,
+            };
+        }
+    }
+
+    // parses {n}, {n,}, {n,m} or {,m}. Returns None (without moving) when the brace doesn't start with a digit,
+    // or a comma and a digit
+    fn parse_counted(&mut self) -> Result<Option<(u32, Option<u32>)>, RegexError> {
+        let open = self.pos;
+        let digit = |c: Option<char>| c.is_some_and(|c| c.is_ascii_digit());
+        // {,m} is the same as {0,m}
+        let no_min = self.peek_at(1) == Some(',') && digit(self.peek_at(2));
+        if !digit(self.peek_at(1)) && !no_min {
+            return Ok(None);
+        }
+        self.pos += 1;
+        let min = self.parse_number(open)?;
+        let max = if self.eat(',') {
+            if self.peek().is_some_and(|c| c.is_ascii_digit()) {
+                Some(self.parse_number(open)?)
+            } else {
+                None
+            }
+        } else {
+            Some(min)
+        };
+        if !self.eat('}') {
+            return Err(self.error(open, "unclosed counted repetition"));
+        }
+        if max.is_some_and(|max| max < min) {
+            return Err(self.error(
+                open,
+                "invalid repetition range, the minimum is larger than the maximum",
+            ));
+        }
+        Ok(Some((min, max)))
+    }
+
+    fn parse_number(&mut self, open: usize) -> Result<u32, RegexError> {
+        let mut value: u32 = 0;
+        while let Some(digit) = self.peek().and_then(|c| c.to_digit(10)) {
+            value = value.saturating_mul(10).saturating_add(digit);
+            self.pos += 1;
+        }
+        if value > MAX_REPEAT {
+            return Err(self.error(open, "repetition count is too large"));
+        }
+        Ok(value)
+    }
+
+    fn parse_atom(&mut self, flags: &mut Flags) -> Result<Option<Node>, RegexError> {
+        let c = self.peek().unwrap();
+        let node = match c {
+            '(' => return self.parse_group(flags),
+            '[' => Node::Class(self.parse_class(flags)?),
+            '\\' => self.parse_escape(flags)?,
+            '*' | '+' | '?' => {
+                return Err(self.error(self.pos, "repetition operator missing expression"));
+            }
+            '.' => {
+                self.pos += 1;
+                Node::Any(flags.dot_all)
+            }
+            '^' => {
+                self.pos += 1;
+                Node::Look(Look::StartLine)
+            }
+            '$' => {
+                self.pos += 1;
+                Node::Look(Look::EndLine)
+            }
+            _ => {
+                self.pos += 1;
+                Node::Char(c, flags.case_insensitive)
+            }
+        };
+        Ok(Some(node))
+    }
+
+    fn parse_group(&mut self, flags: &mut Flags) -> Result<Option<Node>, RegexError> {
+        let open = self.pos;
+        self.pos += 1;
+        let mut inner_flags = *flags;
+        let mut index = None;
+
+        if self.eat('?') {
+            if self.peek() == Some('<')
+                || (self.peek() == Some('P') && self.peek_at(1) == Some('<'))
+            {
+                if self.peek() == Some('P') {
+                    self.pos += 1;
+                }
+                self.pos += 1;
+                let name = self.parse_group_name()?;
+                index = Some(self.names.len());
+                self.names.push(Some(name));
+            } else {
+                // a list of flags, ended by ')' to set them for the rest of this group or by ':' to start a
+                // non-capturing group that uses them
+                let mut enable = true;
+                loop {
+                    match self.peek() {
+                        Some('i') => inner_flags.case_insensitive = enable,
+                        Some('s') => inner_flags.dot_all = enable,
+                        Some('-') if enable => enable = false,
+                        Some(':') => {
+                            self.pos += 1;
+                            break;
+                        }
+                        Some(')') => {
+                            self.pos += 1;
+                            *flags = inner_flags;
+                            return Ok(None);
+                        }
+                        Some(_) => return Err(self.error(self.pos, "unrecognized flag")),
+                        None => return Err(self.error(open, "unclosed group")),
+                    }
+                    self.pos += 1;
+                }
+            }
+        } else {
+            index = Some(self.names.len());
+            self.names.push(None);
+        }
+
+        let inner = self.parse_alternation(&mut inner_flags)?;
+        if !self.eat(')') {
+            return Err(self.error(open, "unclosed group"));
+        }
+        Ok(Some(match index {
+            Some(index) => Node::Group(Box::new(inner), index),
+            None => inner,
+        }))
+    }
+
+    fn parse_group_name(&mut self) -> Result<String, RegexError> {
+        let start = self.pos;
+        let mut name = String::new();
+        loop {
+            match self.peek() {
+                Some('>') => break,
+                Some(c) if is_word_char(c) => name.push(c),
+                Some(_) => return Err(self.error(self.pos, "invalid character in group name")),
+                None => return Err(self.error(start, "unclosed group name")),
+            }
+            self.pos += 1;
+        }
+        self.pos += 1;
+        if name.is_empty() {
+            return Err(self.error(start, "empty group name"));
+        }
+        if self
+            .names
+            .iter()
+            .flatten()
+            .any(|existing| *existing == name)
+        {
+            return Err(self.error(start, "duplicate group name"));
+        }
+        Ok(name)
+    }
+
+    fn parse_escape(&mut self, flags: &Flags) -> Result<Node, RegexError> {
+        let start = self.pos;
+        match self.parse_escape_item(flags)? {
+            EscapeItem::Char(c) => Ok(Node::Char(c, flags.case_insensitive)),
+            EscapeItem::Class(class) => Ok(Node::Class(class)),
+            EscapeItem::Look(look) => Ok(Node::Look(look)),
+            EscapeItem::Invalid => Err(self.error(start, "unrecognized escape sequence")),
+        }
+    }
+
+    // shared between escapes on their own and escapes inside [...]
+    fn parse_escape_item(&mut self, flags: &Flags) -> Result<EscapeItem, RegexError> {
+        let start = self.pos;
+        self.pos += 1;
+        let c = match self.peek() {
+            Some(c) => c,
+            None => return Err(self.error(start, "incomplete escape sequence")),
+        };
+        self.pos += 1;
+        let ci = flags.case_insensitive;
+        let item = match c {
+            'd' => EscapeItem::Class(Class::named(Named::Digit, false, ci)),
+            'D' => EscapeItem::Class(Class::named(Named::Digit, true, ci)),
+            'w' => EscapeItem::Class(Class::named(Named::Word, false, ci)),
+            'W' => EscapeItem::Class(Class::named(Named::Word, true, ci)),
+            's' => EscapeItem::Class(Class::named(Named::Space, false, ci)),
+            'S' => EscapeItem::Class(Class::named(Named::Space, true, ci)),
+            'b' => EscapeItem::Look(Look::WordBoundary),
+            'B' => EscapeItem::Look(Look::NotWordBoundary),
+            'A' => EscapeItem::Look(Look::StartText),
+            'z' => EscapeItem::Look(Look::EndText),
+            'n' => EscapeItem::Char('\n'),
+            't' => EscapeItem::Char('\t'),
+            'r' => EscapeItem::Char('\r'),
+            'f' => EscapeItem::Char('\x0C'),
+            'v' => EscapeItem::Char('\x0B'),
+            '0' => EscapeItem::Char('\0'),
+            'x' | 'u' => EscapeItem::Char(self.parse_hex(start, c)?),
+            c if !c.is_alphanumeric() => EscapeItem::Char(c),
+            _ => EscapeItem::Invalid,
+        };
+        Ok(item)
+    }
+
+    // \xHH, \uHHHH or the braced form \x{H...}
+    fn parse_hex(&mut self, start: usize, kind: char) -> Result<char, RegexError> {
+        let mut digits = String::new();
+        if self.eat('{') {
+            while let Some(c) = self.peek() {
+                if c == '}' {
+                    break;
+                }
+                digits.push(c);
+                self.pos += 1;
+            }
+            if !self.eat('}') {
+                return Err(self.error(start, "unclosed hex escape"));
+            }
+        } else {
+            let width = if kind == 'x' { 2 } else { 4 };
+            for _ in 0..width {
+                match self.peek() {
+                    Some(c) => digits.push(c),
+                    None => break,
+                }
+                self.pos += 1;
+            }
+        }
+        u32::from_str_radix(&digits, 16)
+            .ok()
+            .and_then(char::from_u32)
+            .ok_or_else(|| self.error(start, "invalid hex escape"))
+    }
+
+    fn parse_class(&mut self, flags: &Flags) -> Result<Class, RegexError> {
+        let open = self.pos;
+        self.pos += 1;
+        let negated = self.eat('^');
+        let mut items = Vec::new();
+        let mut first = true;
+        loop {
+            let c = match self.peek() {
+                Some(c) => c,
+                None => return Err(self.error(open, "unclosed character class")),
+            };
+            // a ']' right at the start is a literal, so []] and [^]] work like in grep
+            if c == ']' && !first {
+                self.pos += 1;
+                break;
+            }
+            first = false;
+
+            if c == '[' && self.peek_at(1) == Some(':') {
+                items.push(self.parse_posix_class()?);
+                continue;
+            }
+
+            let item_start = self.pos;
+            let start = match self.parse_class_char(flags)? {
+                Ok(c) => c,
+                Err(class) => {
+                    items.extend(class.items);
+                    continue;
+                }
+            };
+            if self.peek() == Some('-') && self.peek_at(1).is_some_and(|c| c != ']') {
+                self.pos += 1;
+                let end = match self.parse_class_char(flags)? {
+                    Ok(c) => c,
+                    Err(_) => return Err(self.error(item_start, "invalid character class range")),
+                };
+                if end < start {
+                    return Err(self.error(
+                        item_start,
+                        "invalid character class range, the start is larger than the end",
+                    ));
+                }
+                items.push(ClassItem::Range(start, end));
+            } else {
+                items.push(ClassItem::Range(start, start));
+            }
+        }
+        Ok(Class {
+            items,
+            negated,
+            case_insensitive: flags.case_insensitive,
+        })
+    }
+
+    // a single character inside a class, or a nested class like \d
+    fn parse_class_char(&mut self, flags: &Flags) -> Result<Result<char, Class>, RegexError> {
+        let c = self.peek().unwrap();
+        if c != '\\' {
+            self.pos += 1;
+            return Ok(Ok(c));
+        }
+        let start = self.pos;
+        match self.parse_escape_item(flags)? {
+            EscapeItem::Char(c) => Ok(Ok(c)),
+            // \b means backspace inside a class in most engines, but that's so rare that an error is more useful
+            EscapeItem::Class(class) => Ok(Err(class)),
+            EscapeItem::Look(_) | EscapeItem::Invalid => {
+                Err(self.error(start, "unrecognized escape sequence in character class"))
+            }
+        }
+    }
+
+    fn parse_posix_class(&mut self) -> Result<ClassItem, RegexError> {
+        let start = self.pos;
+        self.pos += 2;
+        let negated = self.eat('^');
+        let mut name = String::new();
+        while let Some(c) = self.peek() {
+            if c == ':' {
+                break;
+            }
+            name.push(c);
+            self.pos += 1;
+        }
+        if !(self.eat(':') && self.eat(']')) {
+            return Err(self.error(start, "unclosed POSIX class"));
+        }
+        match Named::posix(&name) {
+            Some(named) => Ok(ClassItem::Named(named, negated)),
+            None => Err(self.error(start, "unknown POSIX class")),
+        }
+    }
+}
+
+enum EscapeItem {
+    Char(char),
+    Class(Class),
+    Look(Look),
+    Invalid,
+}
+
+#[derive(Debug, Clone)]
+enum Inst {
+    Char(char),
+    CharFold(char), // holds the already folded character
+    Any,
+    AnyNewline,
+    Class(Box<Class>),
+    Look(Look),
+    Split(usize, usize), // try the first branch before the second
+    Jmp(usize),
+    Save(usize),
+    Match,
+}
+
+struct Compiler<'p> {
+    pattern: &'p str,
+    insts: Vec<Inst>,
+}
+
+impl Compiler<'_> {
+    fn emit(&mut self, inst: Inst) -> Result<usize, RegexError> {
+        if self.insts.len() >= MAX_PROGRAM {
+            return Err(RegexError {
+                pattern: self.pattern.to_string(),
+                position: 0,
+                message: "pattern is too large once compiled".to_string(),
+            });
+        }
+        self.insts.push(inst);
+        Ok(self.insts.len() - 1)
+    }
+
+    fn split(&mut self, at: usize, next: usize, other: usize, greedy: bool) {
+        self.insts[at] = if greedy {
+            Inst::Split(next, other)
+        } else {
+            Inst::Split(other, next)
+        };
+    }
+
+    fn compile(&mut self, node: &Node) -> Result<(), RegexError> {
+        match node {
+            Node::Empty => {}
+            Node::Char(c, false) => {
+                self.emit(Inst::Char(*c))?;
+            }
+            Node::Char(c, true) => {
+                self.emit(Inst::CharFold(fold(*c)))?;
+            }
+            Node::Any(false) => {
+                self.emit(Inst::Any)?;
+            }
+            Node::Any(true) => {
+                self.emit(Inst::AnyNewline)?;
+            }
+            Node::Class(class) => {
+                self.emit(Inst::Class(Box::new(class.clone())))?;
+            }
+            Node::Look(look) => {
+                self.emit(Inst::Look(*look))?;
+            }
+            Node::Group(inner, index) => {
+                self.emit(Inst::Save(index * 2))?;
+                self.compile(inner)?;
+                self.emit(Inst::Save(index * 2 + 1))?;
+            }
+            Node::Concat(items) => {
+                for item in items {
+                    self.compile(item)?;
+                }
+            }
+            Node::Alternate(branches) => {
+                // split to the first branch or the next split, every branch jumps to the end when done
+                let mut jumps = Vec::new();
+                for (i, branch) in branches.iter().enumerate() {
+                    if i + 1 < branches.len() {
+                        let split = self.emit(Inst::Split(0, 0))?;
+                        self.compile(branch)?;
+                        jumps.push(self.emit(Inst::Jmp(0))?);
+                        let next = self.insts.len();
+                        self.split(split, split + 1, next, true);
+                    } else {
+                        self.compile(branch)?;
+                    }
+                }
+                let end = self.insts.len();
+                for jump in jumps {
+                    self.insts[jump] = Inst::Jmp(end);
+                }
+            }
+            Node::Repeat {
+                node,
+                min,
+                max,
+                greedy,
+            } => {
+                for _ in 0..*min {
+                    self.compile(node)?;
+                }
+                match max {
+                    None => {
+                        let split = self.emit(Inst::Split(0, 0))?;
+                        self.compile(node)?;
+                        self.emit(Inst::Jmp(split))?;
+                        let end = self.insts.len();
+                        self.split(split, split + 1, end, *greedy);
+                    }
+                    Some(max) => {
+                        // x{2,4} becomes xx(x(x)?)?, every optional copy can skip straight to the end
+                        let mut splits = Vec::new();
+                        for _ in *min..*max {
+                            splits.push(self.emit(Inst::Split(0, 0))?);
+                            self.compile(node)?;
+                        }
+                        let end = self.insts.len();
+                        for split in splits {
+                            self.split(split, split + 1, end, *greedy);
+                        }
+                    }
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+// the set of threads alive at one position in the text
+// a sparse set gives O(1) insert, lookup and clear while keeping the threads in priority order
+struct Threads {
+    dense: Vec<usize>,
+    sparse: Vec<usize>,
+    slots: Vec<Option<usize>>,
+    slots_per_thread: usize,
+}
+
+impl Threads {
+    fn new(len: usize, slots_per_thread: usize) -> Threads {
+        Threads {
+            dense: Vec::with_capacity(len),
+            sparse: vec![0; len],
+            slots: vec![None; len * slots_per_thread],
+            slots_per_thread,
+        }
+    }
+
+    fn contains(&self, pc: usize) -> bool {
+        let i = self.sparse[pc];
+        i < self.dense.len() && self.dense[i] == pc
+    }
+
+    fn insert(&mut self, pc: usize) -> usize {
+        let i = self.dense.len();
+        self.sparse[pc] = i;
+        self.dense.push(pc);
+        i
+    }
+
+    fn slots(&self, i: usize) -> &[Option<usize>] {
+        &self.slots[i * self.slots_per_thread..(i + 1) * self.slots_per_thread]
+    }
+
+    fn slots_mut(&mut self, i: usize) -> &mut [Option<usize>] {
+        &mut self.slots[i * self.slots_per_thread..(i + 1) * self.slots_per_thread]
+    }
+}
+
+enum Job {
+    Pc(usize),
+    Restore(usize, Option<usize>),
+}
+
+// where a match is allowed to start and end, this is -w and -x
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum Bounds {
+    #[default]
+    Anywhere,
+    // not touching a word character on either side
+    Word,
+    // from the start of a line to its end
+    Line,
+}
+
+impl Bounds {
+    fn looks(self) -> Option<(Look, Look)> {
+        match self {
+            Bounds::Anywhere => None,
+            Bounds::Word => Some((Look::NoWordBefore, Look::NoWordAfter)),
+            Bounds::Line => Some((Look::StartLine, Look::EndLine)),
+        }
+    }
+
+    // whether a match found without bounds is allowed to be at range in text
+    pub fn allows(self, text: &str, range: &Range<usize>) -> bool {
+        self.looks().is_none_or(|(before, after)| {
+            before.holds(text, range.start) && after.holds(text, range.end)
+        })
+    }
+}
+
+// collects the runs of plain characters the pattern can't match without. A character that ignores case and
+// isn't ASCII ends a run, it could match something that folds to more (or fewer) characters
+fn required_strings(node: &Node, required: &mut Vec<String>) {
+    fn end_run(run: &mut String, required: &mut Vec<String>) {
+        if !run.is_empty() {
+            required.push(std::mem::take(run));
+        }
+    }
+    match node {
+        Node::Concat(nodes) => {
+            let mut run = String::new();
+            for node in nodes {
+                match node {
+                    Node::Char(c, ignore_case) if !ignore_case || c.is_ascii() => {
+                        run.extend(casefold::fold(*c))
+                    }
+                    // ^ and \b don't take up any room, what's either side of them is still next to each other
+                    Node::Look(_) => {}
+                    _ => {
+                        end_run(&mut run, required);
+                        required_strings(node, required);
+                    }
+                }
+            }
+            end_run(&mut run, required);
+        }
+        Node::Char(c, ignore_case) if !ignore_case || c.is_ascii() => {
+            required.push(casefold::fold(*c).collect())
+        }
+        Node::Group(node, _) => required_strings(node, required),
+        Node::Repeat { node, min, .. } if *min > 0 => required_strings(node, required),
+        // either side of a | could be the one that matches
+        _ => {}
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct Regex {
+    pattern: String,
+    insts: Vec<Inst>,
+    names: Vec<Option<String>>,
+    // case folded strings that every match contains, see required
+    required: Vec<String>,
+}
+
+impl Regex {
+    pub fn new(pattern: &str) -> Result<Regex, RegexError> {
+        Regex::build(pattern, false)
+    }
+
+    // same as new, but as if the whole pattern was wrapped in (?i:...)
+    pub fn build(pattern: &str, case_insensitive: bool) -> Result<Regex, RegexError> {
+        Regex::build_bounded(pattern, case_insensitive, Bounds::Anywhere)
+    }
+
+    // same as build, but every match also has to fit bounds. The checks are compiled in around the whole
+    // pattern, so with -w `duct|ducts` still finds "ducts" instead of giving up on "duct" followed by an 's'
+    pub fn build_bounded(
+        pattern: &str,
+        case_insensitive: bool,
+        bounds: Bounds,
+    ) -> Result<Regex, RegexError> {
+        let flags = Flags {
+            case_insensitive,
+            ..Flags::default()
+        };
+        let (node, names) = Parser::new(pattern).parse(flags)?;
+
+        let mut compiler = Compiler {
+            pattern,
+            insts: Vec::new(),
+        };
+        compiler.emit(Inst::Save(0))?;
+        match bounds.looks() {
+            Some((before, after)) => {
+                compiler.emit(Inst::Look(before))?;
+                compiler.compile(&node)?;
+                compiler.emit(Inst::Look(after))?;
+            }
+            None => compiler.compile(&node)?,
+        }
+        compiler.emit(Inst::Save(1))?;
+        compiler.emit(Inst::Match)?;
+
+        let mut required = Vec::new();
+        required_strings(&node, &mut required);
+        Ok(Regex {
+            pattern: pattern.to_string(),
+            insts: compiler.insts,
+            names,
+            required,
+        })
+    }
+
+    pub fn as_str(&self) -> &str {
+        &self.pattern
+    }
+
+    // strings a line has to contain, once it's case folded, for the regex to match in it. Empty when
+    // there's nothing to go on, like for `\d+` or `frog|toad`
+    pub fn required(&self) -> &[String] {
+        &self.required
+    }
+
+    // number of capture groups, counting group 0 (the whole match)
+    pub fn captures_len(&self) -> usize {
+        self.names.len()
+    }
+
+    pub fn group_index(&self, name: &str) -> Option<usize> {
+        self.names.iter().position(|n| n.as_deref() == Some(name))
+    }
+
+    pub fn is_match(&self, text: &str) -> bool {
+        self.exec(text, 0, 2, true).is_some()
+    }
+
+    pub fn find(&self, text: &str) -> Option<Range<usize>> {
+        self.find_at(text, 0)
+    }
+
+    // like find, but starts looking at byte offset start. Anchors and \b can still see the text before start
+    pub fn find_at(&self, text: &str, start: usize) -> Option<Range<usize>> {
+        let slots = self.exec(text, start, 2, false)?;
+        Some(slots[0]?..slots[1]?)
+    }
+
+    pub fn find_iter<'r, 't>(&'r self, text: &'t str) -> FindIter<'r, 't> {
+        FindIter {
+            regex: self,
+            text,
+            pos: 0,
+            last_end: None,
+        }
+    }
+
+    pub fn captures(&self, text: &str) -> Option<Captures> {
+        self.captures_at(text, 0)
+    }
+
+    pub fn captures_at(&self, text: &str, start: usize) -> Option<Captures> {
+        let slots = self.exec(text, start, self.names.len() * 2, false)?;
+        Some(Captures { slots })
+    }
+
+    // runs the Pike VM. Returns the capture slots of the leftmost match, preferring earlier alternatives
+    // and greedier repetitions the same way a backtracking engine would
+    fn exec(
+        &self,
+        text: &str,
+        start: usize,
+        nslots: usize,
+        earliest: bool,
+    ) -> Option<Vec<Option<usize>>> {
+        let len = self.insts.len();
+        let mut clist = Threads::new(len, nslots);
+        let mut nlist = Threads::new(len, nslots);
+        let mut scratch = vec![None; nslots];
+        let mut stack = Vec::new();
+        let mut matched = None;
+        let mut pos = start;
+
+        loop {
+            // start a new attempt here unless we already found a match further left
+            if matched.is_none() {
+                scratch.fill(None);
+                self.add_thread(&mut clist, 0, pos, &mut scratch, text, &mut stack);
+            }
+            if clist.dense.is_empty() {
+                break;
+            }
+
+            let c = text[pos..].chars().next();
+            let next = pos + c.map_or(0, char::len_utf8);
+            for i in 0..clist.dense.len() {
+                let pc = clist.dense[i];
+                let step = match &self.insts[pc] {
+                    Inst::Char(expected) => c == Some(*expected),
+                    Inst::CharFold(expected) => c.is_some_and(|c| fold(c) == *expected),
+                    Inst::Any => c.is_some_and(|c| c != '\n'),
+                    Inst::AnyNewline => c.is_some(),
+                    Inst::Class(class) => c.is_some_and(|c| class.matches(c)),
+                    Inst::Match => {
+                        matched = Some(clist.slots(i).to_vec());
+                        if earliest {
+                            return matched;
+                        }
+                        // every thread after this one has a lower priority, so drop them
+                        break;
+                    }
+                    // the other instructions were already followed by add_thread
+                    _ => false,
+                };
+                if step && c.is_some() {
+                    scratch.copy_from_slice(clist.slots(i));
+                    self.add_thread(&mut nlist, pc + 1, next, &mut scratch, text, &mut stack);
+                }
+            }
+
+            if c.is_none() {
+                break;
+            }
+            std::mem::swap(&mut clist, &mut nlist);
+            nlist.dense.clear();
+            pos = next;
+        }
+        matched
+    }
+
+    // follows jumps, splits, saves and assertions from pc and adds every thread that ends up waiting on a
+    // character. Uses an explicit stack so huge patterns can't overflow the real one
+    fn add_thread(
+        &self,
+        list: &mut Threads,
+        pc: usize,
+        pos: usize,
+        slots: &mut [Option<usize>],
+        text: &str,
+        stack: &mut Vec<Job>,
+    ) {
+        stack.push(Job::Pc(pc));
+        while let Some(job) = stack.pop() {
+            let pc = match job {
+                Job::Pc(pc) => pc,
+                Job::Restore(slot, value) => {
+                    slots[slot] = value;
+                    continue;
+                }
+            };
+            if list.contains(pc) {
+                continue;
+            }
+            let i = list.insert(pc);
+            match &self.insts[pc] {
+                Inst::Jmp(to) => stack.push(Job::Pc(*to)),
+                Inst::Split(first, second) => {
+                    stack.push(Job::Pc(*second));
+                    stack.push(Job::Pc(*first));
+                }
+                Inst::Save(slot) => {
+                    if *slot < slots.len() {
+                        stack.push(Job::Restore(*slot, slots[*slot]));
+                        slots[*slot] = Some(pos);
+                    }
+                    stack.push(Job::Pc(pc + 1));
+                }
+                Inst::Look(look) => {
+                    if look.holds(text, pos) {
+                        stack.push(Job::Pc(pc + 1));
+                    }
+                }
+                _ => list.slots_mut(i).copy_from_slice(slots),
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Captures {
+    slots: Vec<Option<usize>>,
+}
+
+impl Captures {
+    // byte range of group i, or None if the group didn't take part in the match
+    pub fn get(&self, i: usize) -> Option<Range<usize>> {
+        Some(self.slots.get(i * 2).copied()??..self.slots.get(i * 2 + 1).copied()??)
+    }
+
+    pub fn len(&self) -> usize {
+        self.slots.len() / 2
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.slots.is_empty()
+    }
+}
+
+// iterator over every non-overlapping match, created by Regex::find_iter
+pub struct FindIter<'r, 't> {
+    regex: &'r Regex,
+    text: &'t str,
+    pos: usize,
+    last_end: Option<usize>,
+}
+
+impl Iterator for FindIter<'_, '_> {
+    type Item = Range<usize>;
+
+    fn next(&mut self) -> Option<Range<usize>> {
+        loop {
+            if self.pos > self.text.len() {
+                return None;
+            }
+            let m = self.regex.find_at(self.text, self.pos)?;
+            if m.is_empty() {
+                // step over one character so an empty match can't be found at the same place forever
+                self.pos = m.end + self.text[m.end..].chars().next().map_or(1, char::len_utf8);
+                // an empty match right where the previous match ended isn't interesting, a* on "aab" should
+                // only find "aa" and then "" before the b
+                if self.last_end == Some(m.end) {
+                    continue;
+                }
+            } else {
+                self.pos = m.end;
+            }
+            self.last_end = Some(m.end);
+            return Some(m);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn find(pattern: &str, text: &str) -> Option<String> {
+        let re = Regex::new(pattern).unwrap();
+        re.find(text).map(|m| text[m].to_string())
+    }
+
+    #[test]
+    fn classes_and_repetition() {
+        assert_eq!(
+            Some("2024-01-05".to_string()),
+            find(r"\d{4}-\d\d-\d+", "on 2024-01-05 we")
+        );
+        assert_eq!(Some("fast".to_string()), find("[a-f]+st", "safe, fast"));
+        assert_eq!(
+            Some("Pick".to_string()),
+            find("[[:upper:]][^ ]*", "Pick three.")
+        );
+        assert_eq!(Some("aaa".to_string()), find("a{2,3}", "aaaa"));
+        assert_eq!(Some("aa".to_string()), find("a{,2}", "aaaa"));
+        assert_eq!(Some("b".to_string()), find("ba{,2}", "b"));
+        // without a number on either side it's not a repetition at all
+        assert_eq!(Some("a{,}".to_string()), find("a{,}", "a{,}"));
+        assert_eq!(Some("<a>".to_string()), find("<.*?>", "<a><b>"));
+        assert_eq!(None, find("x+y", "xxxx"));
+    }
+
+    #[test]
+    fn alternation_prefers_the_first_branch() {
+        assert_eq!(Some("sam".to_string()), find("sam|samwise", "samwise"));
+        assert_eq!(Some("samwise".to_string()), find("samwise|sam", "samwise"));
+        assert_eq!(
+            Some("disk".to_string()),
+            find("net(work)?|disk", "disk failure")
+        );
+    }
+
+    #[test]
+    fn anchors_and_word_boundaries() {
+        assert!(Regex::new("^Rust").unwrap().is_match("Rust:"));
+        assert!(!Regex::new("^Rust").unwrap().is_match("Trust me."));
+        assert!(Regex::new(r"three\.$").unwrap().is_match("Pick three."));
+        assert_eq!(
+            Some("duct".to_string()),
+            find(r"\bduct\b", "productive duct tape")
+        );
+        let re = Regex::new("^b").unwrap();
+        assert_eq!(Some(2..3), re.find("a\nb"));
+    }
+
+    #[test]
+    fn capture_groups() {
+        let re = Regex::new(r"(?<key>\w+)=(\w+)?").unwrap();
+        let caps = re.captures("set name=ferris now").unwrap();
+        assert_eq!(Some(4..15), caps.get(0));
+        assert_eq!(Some(4..8), caps.get(re.group_index("key").unwrap()));
+        assert_eq!(Some(9..15), caps.get(2));
+
+        let caps = re.captures("flag= ").unwrap();
+        assert_eq!(None, caps.get(2));
+    }
+
+    #[test]
+    fn case_insensitive() {
+        let re = Regex::build("rust|[x-z]+", true).unwrap();
+        let text = "Trust me, XYZ";
+        let found: Vec<&str> = re.find_iter(text).map(|m| &text[m]).collect();
+        assert_eq!(vec!["rust", "XYZ"], found);
+        assert!(Regex::new("(?i)DUCT").unwrap().is_match("Duct tape."));
+        // simple folding knows about letters with more than two cases
+        assert!(Regex::build("σοφός", true).unwrap().is_match("ΣΟΦΌΣ"));
+        assert!(Regex::build("[σ]$", true).unwrap().is_match("σοφός"));
+    }
+
+    #[test]
+    fn bounded_matches() {
+        let re = Regex::build_bounded("duct|ducts", false, Bounds::Word).unwrap();
+        assert_eq!(None, re.find("safe, fast, productive."));
+        assert_eq!(Some(5..10), re.find("tape ducts"));
+        // the combining accent is part of the first word
+        let re = Regex::build_bounded("cafe", false, Bounds::Word).unwrap();
+        assert_eq!(Some(7..11), re.find("cafe\u{301} cafe"));
+
+        let re = Regex::build_bounded("pick|pick three", true, Bounds::Line).unwrap();
+        assert_eq!(Some(0..10), re.find("Pick three"));
+        assert!(!re.is_match("Pick three."));
+    }
+
+    #[test]
+    fn empty_matches_advance() {
+        let re = Regex::new("a*").unwrap();
+        let found: Vec<Range<usize>> = re.find_iter("aab").collect();
+        assert_eq!(vec![0..2, 3..3], found);
+    }
+
+    #[test]
+    fn errors_point_at_the_problem() {
+        let err = Regex::new("safe(, fast").unwrap_err();
+        assert_eq!(4, err.position);
+        assert_eq!("unclosed group", err.message);
+
+        let err = Regex::new("a|*b").unwrap_err();
+        assert_eq!(2, err.position);
+
+        let err = Regex::new("[z-a]").unwrap_err();
+        assert_eq!(1, err.position);
+
+        let err = Regex::new(r"\q").unwrap_err();
+        assert_eq!(0, err.position);
+        assert!(err.to_string().contains("    ^"));
+
+        assert!(Regex::new("a{3,1}").is_err());
+        assert!(Regex::new("ab)").is_err());
+    }
+
+    #[test]
+    fn required_strings() {
+        let required = |pattern: &str, ignore_case| {
+            Regex::build(pattern, ignore_case)
+                .unwrap()
+                .required()
+                .to_vec()
+        };
+        assert_eq!(
+            vec!["how public", "frog"],
+            required(r"^How public.*\bfrog", false)
+        );
+        assert_eq!(vec!["safe, ", "fast"], required("(?i)SAFE, (fast)+", false));
+        assert_eq!(
+            vec!["duct", " tape"],
+            required("duct( tape)?( tape)", false)
+        );
+        assert_eq!(vec!["stra", "e"], required("STRAßE", true));
+        assert!(required(r"frog|toad", false).is_empty());
+        assert!(required(r"\d+[a-z]*", false).is_empty());
+    }
+}
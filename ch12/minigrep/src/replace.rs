@@ -0,0 +1,419 @@
+// --replace, --in-place and --dry-run: changing what was found instead of just printing it
+// in the replacement `$0` is the whole match, `$1`, `$2`... are regex groups and `$name` or `${name}` are
+// named groups. `$$` is a plain `$`. A group that didn't take part in the match, or doesn't exist, is empty.
+// Without --in-place the replaced lines are only printed. With it every file that has a match is written
+// again: first to a temporary file next to it, which is then renamed over the original, so nobody ever sees
+// half a file. --dry-run prints what --in-place would change as a unified diff and doesn't touch anything.
+
+use std::fmt::Write as _;
+use std::fs::{self, File};
+use std::io::{self, Write};
+use std::ops::Range;
+use std::path::Path;
+use std::process;
+
+use crate::encoding;
+use crate::error::MinigrepError;
+use crate::matcher::Matcher;
+use crate::regex::Regex;
+
+// lines of context around every change in the diff, the same as `diff -u`
+const DIFF_CONTEXT: usize = 3;
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+enum Part {
+    Text(String),
+    Group(usize),
+    Named(String),
+}
+
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Replacement {
+    parts: Vec<Part>,
+}
+
+impl Replacement {
+    // any text is a valid replacement, a `$` that isn't followed by a group is just a `$`
+    pub fn parse(replacement: &str) -> Replacement {
+        let mut parts = Vec::new();
+        let mut text = String::new();
+        let mut rest = replacement;
+        while let Some(at) = rest.find('$') {
+            text.push_str(&rest[..at]);
+            rest = &rest[at + 1..];
+            let (name, len) = if let Some(braced) = rest.strip_prefix('{') {
+                match braced.find('}') {
+                    Some(end) => (&braced[..end], end + 2),
+                    None => ("", 0),
+                }
+            } else {
+                let end = rest
+                    .find(|c: char| !(c.is_ascii_alphanumeric() || c == '_'))
+                    .unwrap_or(rest.len());
+                (&rest[..end], end)
+            };
+            if name.is_empty() {
+                // `$$` is one `$`, and so is a `$` with nothing after it that could be a group
+                text.push('$');
+                if rest.starts_with('$') {
+                    rest = &rest[1..];
+                }
+                continue;
+            }
+            if !text.is_empty() {
+                parts.push(Part::Text(std::mem::take(&mut text)));
+            }
+            parts.push(match name.parse() {
+                Ok(index) => Part::Group(index),
+                Err(_) => Part::Named(name.to_string()),
+            });
+            rest = &rest[len..];
+        }
+        text.push_str(rest);
+        if !text.is_empty() {
+            parts.push(Part::Text(text));
+        }
+        Replacement { parts }
+    }
+
+    // the replacement for the match at range in line, literal patterns only have group 0
+    fn expand(&self, regex: Option<&Regex>, line: &str, range: &Range<usize>, out: &mut String) {
+        let captures = match regex {
+            Some(regex) if self.has_groups() => regex.captures_at(line, range.start),
+            _ => None,
+        };
+        let group = |index: usize| match index {
+            0 => Some(range.clone()),
+            _ => captures.as_ref()?.get(index),
+        };
+        for part in &self.parts {
+            let range = match part {
+                Part::Text(text) => {
+                    out.push_str(text);
+                    continue;
+                }
+                Part::Group(index) => group(*index),
+                Part::Named(name) => regex.and_then(|regex| group(regex.group_index(name)?)),
+            };
+            if let Some(range) = range {
+                out.push_str(&line[range]);
+            }
+        }
+    }
+
+    // running the regex again for its groups is only worth it when they're used
+    fn has_groups(&self) -> bool {
+        self.parts
+            .iter()
+            .any(|part| matches!(part, Part::Group(1..) | Part::Named(_)))
+    }
+}
+
+// line with every match in found replaced, and where the replacements ended up in it
+pub fn replace_line(
+    matcher: &Matcher,
+    replacement: &Replacement,
+    line: &str,
+    found: &[(Range<usize>, &str)],
+) -> (String, Vec<Range<usize>>) {
+    let mut replaced = String::with_capacity(line.len());
+    let mut ranges = Vec::with_capacity(found.len());
+    let mut written = 0;
+    for (range, pattern) in found {
+        replaced.push_str(&line[written..range.start]);
+        let start = replaced.len();
+        replacement.expand(regex_for(matcher, pattern), line, range, &mut replaced);
+        ranges.push(start..replaced.len());
+        written = range.end;
+    }
+    replaced.push_str(&line[written..]);
+    (replaced, ranges)
+}
+
+// the regex behind a match, so its groups can be looked up
+fn regex_for<'m>(matcher: &'m Matcher, pattern: &str) -> Option<&'m Regex> {
+    match matcher {
+        Matcher::Regex(regex) => Some(regex),
+        Matcher::Regexes(regexes) => regexes.iter().find(|regex| regex.as_str() == pattern),
+        Matcher::Literal { .. }
+        | Matcher::Literals { .. }
+        | Matcher::Fuzzy { .. }
+        | Matcher::Query(_) => None,
+    }
+}
+
+// --in-place for one file, with dry_run the diff is written to out instead. Returns whether anything
+// matched. Files that aren't plain UTF-8 text are left alone, writing them back could change bytes that
+// have nothing to do with the match.
+pub fn rewrite_file<W: Write>(
+    matcher: &Matcher,
+    replacement: &Replacement,
+    path: &Path,
+    dry_run: bool,
+    out: &mut W,
+) -> Result<bool, MinigrepError> {
+    let bytes = fs::read(path).map_err(|e| MinigrepError::reading(path, e))?;
+    let invalid = |reason| MinigrepError::Io {
+        path: path.to_path_buf(),
+        source: io::Error::new(io::ErrorKind::InvalidData, reason),
+    };
+    if encoding::is_binary(&bytes) {
+        return Err(invalid("binary file, not rewriting it"));
+    }
+    let contents =
+        std::str::from_utf8(&bytes).map_err(|_| invalid("not valid UTF-8, not rewriting it"))?;
+
+    let old: Vec<&str> = contents.split_inclusive('\n').collect();
+    let mut new = Vec::with_capacity(old.len());
+    let mut changed = Vec::new();
+    let mut matched = false;
+    for (i, piece) in old.iter().enumerate() {
+        // the line ending isn't part of the line, it goes back on after the replacing
+        let line = piece.strip_suffix('\n').unwrap_or(piece);
+        let line = line.strip_suffix('\r').unwrap_or(line);
+        let found = matcher.find_patterns(line);
+        if !matcher.selects(line, &found) {
+            new.push(piece.to_string());
+            continue;
+        }
+        matched = true;
+        let (replaced, _) = replace_line(matcher, replacement, line, &found);
+        let replaced = replaced + &piece[line.len()..];
+        // a match can be replaced with exactly what it was
+        if replaced != *piece {
+            changed.push(i);
+        }
+        new.push(replaced);
+    }
+    if changed.is_empty() {
+        return Ok(matched);
+    }
+
+    if dry_run {
+        let diff = unified_diff(path, &old, &new, &changed);
+        out.write_all(diff.as_bytes())
+            .map_err(MinigrepError::Output)?;
+    } else {
+        write_atomically(path, new.concat().as_bytes()).map_err(|source| MinigrepError::Io {
+            path: path.to_path_buf(),
+            source,
+        })?;
+    }
+    Ok(true)
+}
+
+// writes a temporary file in the same directory and renames it over path. A rename within one directory
+// either happens completely or not at all, so a crash halfway through leaves the old file as it was.
+// The temporary file is synced before the rename, or a crash right after could leave an empty file behind.
+// A symlink is followed like `sed -i --follow-symlinks`: the link stays and the file it points at changes.
+pub(crate) fn write_atomically(path: &Path, contents: &[u8]) -> io::Result<()> {
+    let target;
+    let path = match fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_symlink() => {
+            target = fs::canonicalize(path)?;
+            target.as_path()
+        }
+        _ => path,
+    };
+    let name = path.file_name().unwrap_or_default().to_string_lossy();
+    let temp = path.with_file_name(format!(".{name}.minigrep-{}", process::id()));
+    let written = File::create(&temp)
+        .and_then(|mut file| {
+            file.write_all(contents)?;
+            // the new file should look like the old one to everybody else, not like a fresh file
+            match fs::metadata(path) {
+                Ok(metadata) => file.set_permissions(metadata.permissions())?,
+                Err(e) if e.kind() == io::ErrorKind::NotFound => {}
+                Err(e) => return Err(e),
+            }
+            file.sync_all()
+        })
+        .and_then(|_| fs::rename(&temp, path));
+    if written.is_err() {
+        let _ = fs::remove_file(&temp);
+    }
+    written
+}
+
+// `diff -u` output for a file where only the lines in changed are different. new has one entry for every
+// line in old, a replacement with a line break in it makes that entry more than one line long
+fn unified_diff(path: &Path, old: &[&str], new: &[String], changed: &[usize]) -> String {
+    let mut diff = String::new();
+    let path = path.display();
+    let _ = writeln!(diff, "--- a/{path}\n+++ b/{path}");
+
+    // changes whose context touches or overlaps go in the same hunk
+    let mut hunks: Vec<Range<usize>> = Vec::new();
+    for &i in changed {
+        let start = i.saturating_sub(DIFF_CONTEXT);
+        let end = (i + DIFF_CONTEXT + 1).min(old.len());
+        match hunks.last_mut() {
+            Some(last) if start <= last.end => last.end = end,
+            _ => hunks.push(start..end),
+        }
+    }
+
+    let lines_in = |text: &str| text.split_inclusive('\n').count();
+    // how many more lines the new file has than the old one, before the hunk we're at
+    let mut shift: isize = 0;
+    let mut next_change = changed.iter().peekable();
+    for hunk in hunks {
+        let new_len: usize = hunk.clone().map(|i| lines_in(&new[i])).sum();
+        let _ = writeln!(
+            diff,
+            "@@ -{} +{} @@",
+            diff_range(hunk.start, hunk.len()),
+            diff_range((hunk.start as isize + shift) as usize, new_len)
+        );
+        for i in hunk.clone() {
+            if next_change.next_if_eq(&&i).is_none() {
+                push_diff_line(&mut diff, ' ', old[i]);
+                continue;
+            }
+            push_diff_line(&mut diff, '-', old[i]);
+            for line in new[i].split_inclusive('\n') {
+                push_diff_line(&mut diff, '+', line);
+            }
+        }
+        shift += new_len as isize - hunk.len() as isize;
+    }
+    diff
+}
+
+// `start,len` counting lines from 1, the way hunk headers want it
+fn diff_range(start: usize, len: usize) -> String {
+    match len {
+        1 => format!("{}", start + 1),
+        // an empty range names the line before it
+        0 => format!("{start},0"),
+        _ => format!("{},{len}", start + 1),
+    }
+}
+
+fn push_diff_line(diff: &mut String, marker: char, line: &str) {
+    diff.push(marker);
+    diff.push_str(line);
+    if !line.ends_with('\n') {
+        diff.push_str("\n\\ No newline at end of file\n");
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::regex::Bounds;
+    use std::env;
+
+    fn replace(matcher: &Matcher, replacement: &str, line: &str) -> String {
+        let found = matcher.find_patterns(line);
+        replace_line(matcher, &Replacement::parse(replacement), line, &found).0
+    }
+
+    #[test]
+    fn replacements_with_groups() {
+        let regex = Matcher::Regex(Regex::new(r"(?<first>\w+) (\w+)").unwrap());
+        assert_eq!(
+            "tape Duct, tape duct.",
+            replace(&regex, "$2 $first", "Duct tape, duct tape.")
+        );
+        assert_eq!("[Duct tape].", replace(&regex, "[${0}]", "Duct tape."));
+        assert_eq!("$5 .", replace(&regex, "$$5 $9", "Duct tape."));
+        assert_eq!("cost: $.", replace(&regex, "cost: $", "Duct tape."));
+
+        let literal = Matcher::literal_bounded("duct", true, Bounds::Word);
+        let (line, ranges) = replace_line(
+            &literal,
+            &Replacement::parse("<$0>"),
+            "Duct tape, productive",
+            &literal.find_patterns("Duct tape, productive"),
+        );
+        assert_eq!("<Duct> tape, productive", line);
+        assert_eq!(vec![0..6], ranges);
+    }
+
+    #[test]
+    fn diffs() {
+        let old = [
+            "a\n", "b\n", "c\n", "d\n", "e\n", "f\n", "g\n", "h\n", "i\n", "j",
+        ];
+        let mut new: Vec<String> = old.iter().map(|line| line.to_string()).collect();
+        new[1] = String::from("B\n");
+        new[9] = String::from("J1\nJ2");
+        let diff = unified_diff(Path::new("x.txt"), &old, &new, &[1, 9]);
+        assert_eq!(
+            "\
+--- a/x.txt
++++ b/x.txt
+@@ -1,5 +1,5 @@
+ a
+-b
++B
+ c
+ d
+ e
+@@ -7,4 +7,5 @@
+ g
+ h
+ i
+-j
+\\ No newline at end of file
++J1
++J2
+\\ No newline at end of file
+",
+            diff
+        );
+    }
+
+    #[test]
+    fn rewrites_files_in_place() {
+        let path = env::temp_dir().join(format!("minigrep-replace-{}.txt", process::id()));
+        fs::write(&path, "Rust:\r\nsafe, fast, productive.\nDuct tape.\n").unwrap();
+        let matcher = Matcher::literal("duct", true);
+        let replacement = Replacement::parse("glue");
+
+        let mut diff = Vec::new();
+        assert!(rewrite_file(&matcher, &replacement, &path, true, &mut diff).unwrap());
+        let diff = String::from_utf8(diff).unwrap();
+        assert!(diff.contains("-safe, fast, productive.\n+safe, fast, proglueive.\n"));
+        // a dry run leaves the file alone
+        assert!(fs::read_to_string(&path).unwrap().contains("Duct"));
+
+        assert!(rewrite_file(&matcher, &replacement, &path, false, &mut Vec::new()).unwrap());
+        assert_eq!(
+            "Rust:\r\nsafe, fast, proglueive.\nglue tape.\n",
+            fs::read_to_string(&path).unwrap()
+        );
+        assert!(!rewrite_file(&matcher, &replacement, &path, false, &mut Vec::new()).unwrap());
+        fs::remove_file(path).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn in_place_keeps_symlinks() {
+        let dir = env::temp_dir().join(format!("minigrep-symlink-{}", process::id()));
+        fs::create_dir_all(&dir).unwrap();
+        fs::write(dir.join("poem.txt"), "Duct tape.\n").unwrap();
+        std::os::unix::fs::symlink("poem.txt", dir.join("link.txt")).unwrap();
+
+        let matcher = Matcher::literal("duct", true);
+        let link = dir.join("link.txt");
+        assert!(rewrite_file(
+            &matcher,
+            &Replacement::parse("glue"),
+            &link,
+            false,
+            &mut Vec::new()
+        )
+        .unwrap());
+        assert!(fs::symlink_metadata(&link)
+            .unwrap()
+            .file_type()
+            .is_symlink());
+        assert_eq!(
+            "glue tape.\n",
+            fs::read_to_string(dir.join("poem.txt")).unwrap()
+        );
+        fs::remove_dir_all(dir).unwrap();
+    }
+}
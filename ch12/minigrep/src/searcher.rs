@@ -0,0 +1,878 @@
+// the search on its own, without the command line or the printer, for other tools that want to embed it
+// a SearcherBuilder collects the patterns and how to match them, build() compiles them into a Searcher.
+// The Searcher reads the input line by line and hands what it finds to a Sink: the matching lines, the
+// context lines around them and the end of each file. The Printer is the sink minigrep itself uses,
+// anything else that implements Sink can collect the matches instead, see the tests at the bottom.
+
+use std::collections::VecDeque;
+use std::fs::File;
+use std::io::{self, BufRead, BufReader};
+use std::ops::Range;
+use std::path::Path;
+
+use crate::encoding;
+use crate::error::MinigrepError;
+use crate::gzip;
+use crate::input::Input;
+use crate::literal::{self, Finder};
+use crate::matcher::{has_uppercase, Matcher, MatcherKind};
+use crate::query::Query;
+use crate::regex::{Bounds, Regex};
+use crate::replace::{self, Replacement};
+use crate::{Match, STDIN_NAME};
+
+// gets told what a search found, in the order it's found in. Only matched has to be written, a sink that
+// doesn't care about context or files can leave the rest alone
+pub trait Sink {
+    // before the first line of every file
+    fn begin(&mut self, _path: &Path) -> io::Result<()> {
+        Ok(())
+    }
+
+    // the current file has a NUL byte in it, return false to skip the rest of it
+    fn binary(&mut self) -> bool {
+        true
+    }
+
+    // a selected line, with invert that's one without a match
+    fn matched(&mut self, m: &Match) -> io::Result<()>;
+
+    // a line that wasn't selected but is close enough to one that was, only with before or after context
+    fn context(&mut self, _line_number: usize, _byte_offset: usize, _line: &str) -> io::Result<()> {
+        Ok(())
+    }
+
+    // asked before every line, true stops the search of the current file early
+    fn is_done(&self) -> bool {
+        false
+    }
+
+    // after the last line of every file, even when nothing matched
+    fn finish(&mut self) -> io::Result<()> {
+        Ok(())
+    }
+}
+
+// how the patterns treat upper and lower case
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub enum CaseMode {
+    #[default]
+    Sensitive,
+    // -i
+    Insensitive,
+    // -S, ignore case unless a pattern has an uppercase letter in it
+    Smart,
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct SearcherBuilder {
+    patterns: Vec<String>,
+    kind: MatcherKind,
+    case: CaseMode,
+    bounds: Bounds,
+    fuzzy: Option<usize>,
+    // a matcher that was put together some other way, the pattern settings above don't matter then
+    matcher: Option<Matcher>,
+    before_context: usize,
+    after_context: usize,
+    invert: bool,
+    replace: Option<Replacement>,
+    decompress: bool,
+    multiline: bool,
+}
+
+impl SearcherBuilder {
+    pub fn new() -> SearcherBuilder {
+        SearcherBuilder::default()
+    }
+
+    // adds a pattern, a line is selected when any one of them matches
+    pub fn pattern(mut self, pattern: &str) -> SearcherBuilder {
+        self.patterns.push(pattern.to_string());
+        self
+    }
+
+    // whether the patterns are plain text, the default, or regexes
+    pub fn kind(mut self, kind: MatcherKind) -> SearcherBuilder {
+        self.kind = kind;
+        self
+    }
+
+    pub fn case(mut self, case: CaseMode) -> SearcherBuilder {
+        self.case = case;
+        self
+    }
+
+    // -w and -x
+    pub fn bounds(mut self, bounds: Bounds) -> SearcherBuilder {
+        self.bounds = bounds;
+        self
+    }
+
+    // allow up to max_distance typos, only for a single plain text pattern
+    pub fn fuzzy(mut self, max_distance: usize) -> SearcherBuilder {
+        self.fuzzy = Some(max_distance);
+        self
+    }
+
+    // search with this matcher instead of building one from the patterns
+    pub fn matcher(mut self, matcher: Matcher) -> SearcherBuilder {
+        self.matcher = Some(matcher);
+        self
+    }
+
+    // how many lines before and after each selected line get passed to Sink::context
+    pub fn context(mut self, before: usize, after: usize) -> SearcherBuilder {
+        self.before_context = before;
+        self.after_context = after;
+        self
+    }
+
+    // -v, select the lines without a match
+    pub fn invert(mut self, invert: bool) -> SearcherBuilder {
+        self.invert = invert;
+        self
+    }
+
+    // the sink gets the lines with their matches replaced
+    pub fn replace(mut self, replacement: Replacement) -> SearcherBuilder {
+        self.replace = Some(replacement);
+        self
+    }
+
+    // -z, unpack compressed input on the way, see gzip.rs
+    pub fn decompress(mut self, decompress: bool) -> SearcherBuilder {
+        self.decompress = decompress;
+        self
+    }
+
+    // -U, let matches run over more than one line. Plain text patterns can use \n for a newline
+    pub fn multiline(mut self, multiline: bool) -> SearcherBuilder {
+        self.multiline = multiline;
+        self
+    }
+
+    // compiling the regexes is what usually fails, and the error says where the pattern is broken
+    pub fn build(mut self) -> Result<Searcher, MinigrepError> {
+        let matcher = match self.matcher.take() {
+            Some(matcher) => matcher,
+            None => self.build_matcher()?,
+        };
+        Ok(Searcher {
+            matcher,
+            before_context: self.before_context,
+            after_context: self.after_context,
+            invert: self.invert,
+            replace: self.replace,
+            decompress: self.decompress,
+            multiline: self.multiline,
+        })
+    }
+
+    fn build_matcher(&self) -> Result<Matcher, MinigrepError> {
+        let patterns = match (self.multiline, self.kind) {
+            (true, MatcherKind::Literal) => self.patterns.iter().map(|p| newlines(p)).collect(),
+            _ => self.patterns.clone(),
+        };
+        let ignore_case = match self.case {
+            CaseMode::Sensitive => false,
+            CaseMode::Insensitive => true,
+            CaseMode::Smart => !patterns
+                .iter()
+                .any(|pattern| has_uppercase(pattern, self.kind)),
+        };
+        let bounds = self.bounds;
+        if self.kind == MatcherKind::Query {
+            return match (self.fuzzy, self.multiline, patterns.as_slice()) {
+                (Some(_), _, _) => Err("fuzzy matching can't be used with a query".into()),
+                (_, true, _) => Err("a query can't be used with multiline".into()),
+                (None, false, [query]) => Ok(Matcher::Query(Box::new(Query::parse(
+                    query, self.case, bounds,
+                )?))),
+                _ => Err("a query has to be the only pattern".into()),
+            };
+        }
+        if let Some(max_distance) = self.fuzzy {
+            return match (self.kind, patterns.as_slice()) {
+                (MatcherKind::Regex, _) => Err("fuzzy matching can't be used with a regex".into()),
+                (_, [pattern]) => Ok(Matcher::fuzzy(pattern, max_distance, ignore_case, bounds)),
+                _ => Err("fuzzy matching needs exactly one pattern".into()),
+            };
+        }
+        match (self.kind, patterns.as_slice()) {
+            (MatcherKind::Literal, [pattern]) => {
+                Ok(Matcher::literal_bounded(pattern, ignore_case, bounds))
+            }
+            (MatcherKind::Regex, [pattern]) => Ok(Matcher::Regex(Regex::build_bounded(
+                pattern,
+                ignore_case,
+                bounds,
+            )?)),
+            (MatcherKind::Literal, patterns) => {
+                Ok(Matcher::literals(patterns, ignore_case, bounds))
+            }
+            (MatcherKind::Regex, patterns) => Ok(Matcher::Regexes(
+                patterns
+                    .iter()
+                    .map(|pattern| Regex::build_bounded(pattern, ignore_case, bounds))
+                    .collect::<Result<_, _>>()?,
+            )),
+            (MatcherKind::Query, _) => unreachable!("queries are built before the other matchers"),
+        }
+    }
+}
+
+// the \n in a plain text pattern, -U only. \\ is a backslash so a literal \n can still be searched for
+fn newlines(pattern: &str) -> String {
+    let mut unescaped = String::with_capacity(pattern.len());
+    let mut chars = pattern.chars();
+    while let Some(c) = chars.next() {
+        match (c, chars.clone().next()) {
+            ('\\', Some('n')) => unescaped.push('\n'),
+            ('\\', Some('\\')) => unescaped.push('\\'),
+            _ => {
+                unescaped.push(c);
+                continue;
+            }
+        }
+        chars.next();
+    }
+    unescaped
+}
+
+// a line held on to in case a selected line shows up in the next few
+struct ContextLine {
+    line_number: usize,
+    byte_offset: usize,
+    line: String,
+}
+
+// decides which of the lines that weren't selected go to the sink as context
+struct Window {
+    before: VecDeque<ContextLine>,
+    before_context: usize,
+    // how many more lines after the last selected one are context
+    after_left: usize,
+    after_context: usize,
+}
+
+impl Window {
+    fn new(before_context: usize, after_context: usize) -> Window {
+        Window {
+            before: VecDeque::new(),
+            before_context,
+            after_left: 0,
+            after_context,
+        }
+    }
+
+    fn unselected<S: Sink + ?Sized>(
+        &mut self,
+        sink: &mut S,
+        line_number: usize,
+        byte_offset: usize,
+        line: &str,
+    ) -> io::Result<()> {
+        if self.after_left > 0 {
+            self.after_left -= 1;
+            return sink.context(line_number, byte_offset, line);
+        }
+        if self.before_context > 0 {
+            if self.before.len() == self.before_context {
+                self.before.pop_front();
+            }
+            self.before.push_back(ContextLine {
+                line_number,
+                byte_offset,
+                line: line.to_string(),
+            });
+        }
+        Ok(())
+    }
+
+    fn selected<S: Sink + ?Sized>(&mut self, sink: &mut S, m: &Match) -> io::Result<()> {
+        for c in self.before.drain(..) {
+            sink.context(c.line_number, c.byte_offset, &c.line)?;
+        }
+        self.after_left = self.after_context;
+        sink.matched(m)
+    }
+}
+
+// one or more matches that touch the same lines, with -U
+struct Block<'a> {
+    // indexes into the lines of the text, last is part of the block too
+    first: usize,
+    last: usize,
+    found: Vec<(Range<usize>, &'a str)>,
+}
+
+// where a line of the text is, with -U
+struct TextLine {
+    start: usize,
+    // without the line ending
+    end: usize,
+    // in the input, which isn't the same as start once invalid UTF-8 has been replaced
+    byte_offset: usize,
+}
+
+// a selected line, or with -U the lines of a block, on its way to select
+struct Line<'a> {
+    number: usize,
+    byte_offset: usize,
+    // as it is in the input, what the sink gets
+    raw: &'a [u8],
+    // with invalid UTF-8 replaced, what gets searched
+    text: &'a str,
+    // from encoding::lossy, where each byte of text is in the input when that isn't the same place
+    offsets: Option<&'a [usize]>,
+}
+
+// doesn't change while searching, so one can be shared between threads
+#[derive(Debug, Clone)]
+pub struct Searcher {
+    matcher: Matcher,
+    before_context: usize,
+    after_context: usize,
+    invert: bool,
+    replace: Option<Replacement>,
+    decompress: bool,
+    multiline: bool,
+}
+
+impl Searcher {
+    pub fn matcher(&self) -> &Matcher {
+        &self.matcher
+    }
+
+    // opens the input and searches it, returns whether any line was selected
+    pub fn search_input<S: Sink + ?Sized>(
+        &self,
+        input: &Input,
+        sink: &mut S,
+    ) -> Result<bool, MinigrepError> {
+        match input {
+            Input::Stdin => {
+                let path = Path::new(STDIN_NAME);
+                let stdin: Box<dyn BufRead> = match self.decompress {
+                    true => gzip::decompress(path, io::stdin().lock())
+                        .map_err(|e| MinigrepError::reading(path, e))?,
+                    false => Box::new(io::stdin().lock()),
+                };
+                self.search_reader(path, stdin, sink)
+            }
+            Input::File(path) => {
+                let file = File::open(path).map_err(|e| MinigrepError::reading(path, e))?;
+                let reader: Box<dyn BufRead> = match self.decompress {
+                    true => gzip::decompress(path, BufReader::new(file))
+                        .map_err(|e| MinigrepError::reading(path, e))?,
+                    false => Box::new(BufReader::new(file)),
+                };
+                self.search_reader(path, reader, sink)
+            }
+        }
+    }
+
+    // searches anything that can be read line by line: a file, standard input or an in-memory buffer
+    // only one line is held in memory at a time (plus the before context), so this works on files far
+    // bigger than memory. With invert the lines without a match are the ones that get selected, and they
+    // have no ranges.
+    // Lines don't have to be valid UTF-8, the bad bytes are searched as U+FFFD but the Match still has the
+    // line's own bytes, with ranges that count them. UTF-16 files are turned into UTF-8 first, see encoding.rs.
+    // With replace the selected lines have their matches replaced, and the ranges point at the replacements.
+    // With multiline the whole input is read in first, see search_text.
+    // Returns whether any line was selected.
+    pub fn search_reader<R: BufRead, S: Sink + ?Sized>(
+        &self,
+        path: &Path,
+        reader: R,
+        sink: &mut S,
+    ) -> Result<bool, MinigrepError> {
+        sink.begin(path).map_err(MinigrepError::Output)?;
+        let reader = encoding::decode(reader).map_err(|e| MinigrepError::reading(path, e))?;
+        let selected = if self.multiline {
+            self.search_text(path, reader, sink)?
+        } else {
+            self.search_lines(path, reader, sink)?
+        };
+        sink.finish().map_err(MinigrepError::Output)?;
+        Ok(selected)
+    }
+
+    fn search_lines<R: BufRead, S: Sink + ?Sized>(
+        &self,
+        path: &Path,
+        mut reader: R,
+        sink: &mut S,
+    ) -> Result<bool, MinigrepError> {
+        let mut buffer = Vec::new();
+        let mut line_number = 0;
+        let mut byte_offset = 0;
+        let mut selected = false;
+        let mut window = Window::new(self.before_context, self.after_context);
+        // a NUL in the first buffer of the file decides it before anything gets to the sink, one further
+        // down still counts from that line on
+        let start = reader
+            .fill_buf()
+            .map_err(|e| MinigrepError::reading(path, e))?;
+        let mut binary = encoding::is_binary(start);
+        // -I, a binary file is searched as if it had nothing in it
+        let mut skip = binary && !sink.binary();
+        // the lines that can't match are skipped a whole buffer at a time, unless something wants to see them
+        let prefilter = match (self.invert, self.before_context, self.after_context) {
+            (false, 0, 0) => self.matcher.prefilter(),
+            _ => None,
+        };
+
+        while !skip && !sink.is_done() {
+            if let Some((finder, ascii_only)) = prefilter {
+                let start = reader
+                    .fill_buf()
+                    .map_err(|e| MinigrepError::reading(path, e))?;
+                let skipped = skippable(start, finder, ascii_only);
+                if skipped > 0 {
+                    let lines = start[..skipped].iter().filter(|&&b| b == b'\n').count();
+                    if !binary && encoding::is_binary(&start[..skipped]) {
+                        binary = true;
+                        skip = !sink.binary();
+                        if skip {
+                            break;
+                        }
+                    }
+                    reader.consume(skipped);
+                    line_number += lines;
+                    byte_offset += skipped;
+                    continue;
+                }
+            }
+            buffer.clear();
+            let read = reader
+                .read_until(b'\n', &mut buffer)
+                .map_err(|e| MinigrepError::reading(path, e))?;
+            if read == 0 {
+                break;
+            }
+            if !binary && encoding::is_binary(&buffer) {
+                binary = true;
+                skip = !sink.binary();
+                if skip {
+                    break;
+                }
+            }
+            line_number += 1;
+            // same line endings as str::lines
+            let raw = buffer.strip_suffix(b"\n").unwrap_or(&buffer);
+            let raw = raw.strip_suffix(b"\r").unwrap_or(raw);
+            let (text, offsets) = encoding::lossy(raw);
+
+            let found = self.matcher.find_patterns(&text);
+            let sent = if self.matcher.selects(&text, &found) == self.invert {
+                window.unselected(sink, line_number, byte_offset, &text)
+            } else {
+                selected = true;
+                let line = Line {
+                    number: line_number,
+                    byte_offset,
+                    raw,
+                    text: &text,
+                    offsets: offsets.as_deref(),
+                };
+                self.select(sink, &mut window, line, found)
+            };
+            sent.map_err(MinigrepError::Output)?;
+            byte_offset += read;
+        }
+        Ok(selected)
+    }
+
+    // -U, the whole input is read in so a match can run over as many lines as it likes. A match goes to
+    // the sink with every line it touches, matches that share a line go together as one Match. With
+    // invert it's the lines no match touches that get selected, one at a time like without -U.
+    fn search_text<R: BufRead, S: Sink + ?Sized>(
+        &self,
+        path: &Path,
+        mut reader: R,
+        sink: &mut S,
+    ) -> Result<bool, MinigrepError> {
+        let mut bytes = Vec::new();
+        reader
+            .read_to_end(&mut bytes)
+            .map_err(|e| MinigrepError::reading(path, e))?;
+        if encoding::is_binary(&bytes) && !sink.binary() {
+            return Ok(false);
+        }
+        // a newline is never part of invalid UTF-8, so the lines of the text are the lines of the input
+        let (text, offsets) = encoding::lossy(&bytes);
+        let raw_at = |at: usize| offsets.as_ref().map_or(at, |offsets| offsets[at]);
+        let mut lines = Vec::new();
+        let mut start = 0;
+        for piece in text.split_inclusive('\n') {
+            let line = piece.strip_suffix('\n').unwrap_or(piece);
+            let line = line.strip_suffix('\r').unwrap_or(line);
+            lines.push(TextLine {
+                start,
+                end: start + line.len(),
+                byte_offset: raw_at(start),
+            });
+            start += piece.len();
+        }
+
+        // which line a position in the text is on
+        let line_of = |at: usize| lines.partition_point(|line| line.start <= at) - 1;
+        let mut blocks: Vec<Block> = Vec::new();
+        for (range, pattern) in self.matcher.find_patterns(&text) {
+            // an empty match after the last newline isn't on any line
+            if lines.last().is_none_or(|last| range.start > last.end) {
+                continue;
+            }
+            let first = line_of(range.start);
+            // a match that ends with a newline doesn't touch the line after it
+            let last = line_of(range.end.saturating_sub(1).max(range.start));
+            match blocks.last_mut() {
+                Some(block) if block.last >= first => {
+                    block.last = block.last.max(last);
+                    block.found.push((range, pattern));
+                }
+                _ => blocks.push(Block {
+                    first,
+                    last,
+                    found: vec![(range, pattern)],
+                }),
+            }
+        }
+
+        let mut selected = false;
+        let mut window = Window::new(self.before_context, self.after_context);
+        let mut blocks = blocks.into_iter().peekable();
+        let mut i = 0;
+        while i < lines.len() && !sink.is_done() {
+            let mut next = i + 1;
+            let sent = match (blocks.next_if(|block| block.first == i), self.invert) {
+                (Some(block), false) => {
+                    let (first, last) = (&lines[block.first], &lines[block.last]);
+                    // the ranges are moved to count from the start of the block, and cut off at its end
+                    // when the match took the newline with it. A match of just the \n of a \r\n ends
+                    // up empty, at the end of its line
+                    let found = block
+                        .found
+                        .into_iter()
+                        .map(|(range, pattern)| {
+                            let start = range.start.min(last.end) - first.start;
+                            let end = range.end.min(last.end) - first.start;
+                            (start..end, pattern)
+                        })
+                        .collect();
+                    selected = true;
+                    next = block.last + 1;
+                    let line = Line {
+                        number: block.first + 1,
+                        byte_offset: first.byte_offset,
+                        raw: &bytes[first.byte_offset..raw_at(last.end)],
+                        text: &text[first.start..last.end],
+                        offsets: offsets.as_ref().map(|o| &o[first.start..=last.end]),
+                    };
+                    self.select(sink, &mut window, line, found)
+                }
+                (Some(block), true) => {
+                    next = block.last + 1;
+                    (block.first..=block.last).try_for_each(|n| {
+                        let line = &lines[n];
+                        let text = &text[line.start..line.end];
+                        window.unselected(sink, n + 1, line.byte_offset, text)
+                    })
+                }
+                (None, invert) => {
+                    let line = &lines[i];
+                    let (line_number, byte_offset) = (i + 1, line.byte_offset);
+                    if invert {
+                        selected = true;
+                        let line = Line {
+                            number: line_number,
+                            byte_offset,
+                            raw: &bytes[byte_offset..raw_at(line.end)],
+                            text: &text[line.start..line.end],
+                            offsets: None,
+                        };
+                        self.select(sink, &mut window, line, Vec::new())
+                    } else {
+                        let text = &text[line.start..line.end];
+                        window.unselected(sink, line_number, byte_offset, text)
+                    }
+                }
+            };
+            sent.map_err(MinigrepError::Output)?;
+            i = next;
+        }
+        Ok(selected)
+    }
+
+    // sends a selected line, or with -U the lines of a block, to the sink with the context before it.
+    // found is in the text of the line, the ranges in the Match are moved to where they are in the input
+    fn select<S: Sink + ?Sized>(
+        &self,
+        sink: &mut S,
+        window: &mut Window,
+        line: Line,
+        found: Vec<(Range<usize>, &str)>,
+    ) -> io::Result<()> {
+        let matcher = &self.matcher;
+        // the typos are counted in what was found, before any replacing
+        let distances = found
+            .iter()
+            .filter_map(|(range, _)| matcher.distance(&line.text[range.clone()]))
+            .collect();
+        let replaced;
+        let (bytes, ranges, patterns) = match &self.replace {
+            _ if self.invert => (line.raw, Vec::new(), Vec::new()),
+            // a replaced line is made from the text, so its ranges already fit
+            Some(replacement) => {
+                let (text, ranges) = replace::replace_line(matcher, replacement, line.text, &found);
+                replaced = text;
+                let patterns = found.into_iter().map(|(_, pattern)| pattern).collect();
+                (replaced.as_bytes(), ranges, patterns)
+            }
+            None => {
+                let raw_at = |at: usize| line.offsets.map_or(at, |o| o[at] - o[0]);
+                let (ranges, patterns) = found
+                    .into_iter()
+                    .map(|(range, pattern)| (raw_at(range.start)..raw_at(range.end), pattern))
+                    .unzip();
+                (line.raw, ranges, patterns)
+            }
+        };
+        let m = Match {
+            line_number: line.number,
+            byte_offset: line.byte_offset,
+            line: bytes,
+            ranges,
+            patterns,
+            distances,
+        };
+        window.selected(sink, &m)
+    }
+}
+
+// how much of buf is whole lines finder doesn't find anything in, the lines before the first one that
+// could match. With ascii_only the first line that isn't all ASCII has to be searched for real too
+fn skippable(buf: &[u8], finder: &Finder, ascii_only: bool) -> usize {
+    // the last line might go on in the next buffer
+    let Some(end) = literal::memrchr(b'\n', buf) else {
+        return 0;
+    };
+    let whole = &buf[..=end];
+    let line_start = |at: usize| literal::memrchr(b'\n', &whole[..at]).map_or(0, |i| i + 1);
+    let skip = finder.find(whole, 0).map_or(whole.len(), line_start);
+    match whole[..skip].iter().position(|b| !b.is_ascii()) {
+        Some(i) if ascii_only => line_start(i),
+        _ => skip,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    // keeps what the searcher sends, one string per call
+    #[derive(Default)]
+    struct Collect(Vec<String>);
+
+    impl Sink for Collect {
+        fn begin(&mut self, path: &Path) -> io::Result<()> {
+            self.0.push(format!("begin {}", path.display()));
+            Ok(())
+        }
+
+        fn matched(&mut self, m: &Match) -> io::Result<()> {
+            self.0.push(format!(
+                "{}:{}",
+                m.line_number,
+                String::from_utf8_lossy(m.line)
+            ));
+            Ok(())
+        }
+
+        fn context(
+            &mut self,
+            line_number: usize,
+            _byte_offset: usize,
+            line: &str,
+        ) -> io::Result<()> {
+            self.0.push(format!("{line_number}-{line}"));
+            Ok(())
+        }
+
+        fn finish(&mut self) -> io::Result<()> {
+            self.0.push("finish".to_string());
+            Ok(())
+        }
+    }
+
+    fn collect(searcher: &Searcher, text: &str) -> Vec<String> {
+        let mut sink = Collect::default();
+        searcher
+            .search_reader(Path::new("poem.txt"), text.as_bytes(), &mut sink)
+            .unwrap();
+        sink.0
+    }
+
+    #[test]
+    fn literals_skip_the_lines_they_cant_be_in() {
+        let poem = include_str!("../poem.txt");
+        let finder = Finder::new(b"frog");
+        let before_frog = poem.find("How public").unwrap();
+        assert_eq!(before_frog, skippable(poem.as_bytes(), &finder, false));
+        // a line that isn't finished yet is never skipped
+        assert_eq!(0, skippable(b"no toad", &finder, false));
+        // the Kelvin sign folds to k
+        let kelvin = "a\n\u{212A}\nb\n";
+        assert_eq!(2, skippable(kelvin.as_bytes(), &Finder::new(b"k"), true));
+        let searcher = SearcherBuilder::new()
+            .pattern("k")
+            .case(CaseMode::Insensitive)
+            .build()
+            .unwrap();
+        assert_eq!(
+            vec!["begin poem.txt", "2:\u{212A}", "finish"],
+            collect(&searcher, kelvin)
+        );
+
+        // however small the buffer, the skipped lines still count
+        let search = |kind: MatcherKind| {
+            let searcher = SearcherBuilder::new()
+                .pattern("to")
+                .kind(kind)
+                .build()
+                .unwrap();
+            let mut sink = Collect::default();
+            let reader = BufReader::with_capacity(16, poem.as_bytes());
+            searcher
+                .search_reader(Path::new("poem.txt"), reader, &mut sink)
+                .unwrap();
+            sink.0
+        };
+        assert_eq!(search(MatcherKind::Regex), search(MatcherKind::Literal));
+        assert_eq!(4, search(MatcherKind::Literal).len());
+    }
+
+    #[test]
+    fn sink_gets_matches_context_and_finish() {
+        let poem = include_str!("../poem.txt");
+        let searcher = SearcherBuilder::new()
+            .pattern("FROG")
+            .case(CaseMode::Insensitive)
+            .context(1, 1)
+            .build()
+            .unwrap();
+        assert_eq!(
+            vec![
+                "begin poem.txt",
+                "6-How dreary to be somebody!",
+                "7:How public, like a frog",
+                "8-To tell your name the livelong day",
+                "finish",
+            ],
+            collect(&searcher, poem)
+        );
+
+        // a line only counts as context once, and never when it's selected itself
+        let searcher = SearcherBuilder::new()
+            .pattern("How")
+            .context(2, 0)
+            .build()
+            .unwrap();
+        assert_eq!(
+            vec![
+                "begin poem.txt",
+                "4-They'd banish us, you know.",
+                "5-",
+                "6:How dreary to be somebody!",
+                "7:How public, like a frog",
+                "finish",
+            ],
+            collect(&searcher, poem)
+        );
+    }
+
+    #[test]
+    fn builder_settings() {
+        // smart case only ignores case while the patterns are all lowercase
+        let smart = |pattern| {
+            let searcher = SearcherBuilder::new()
+                .pattern(pattern)
+                .case(CaseMode::Smart)
+                .build()
+                .unwrap();
+            collect(&searcher, "Duct tape.\nproductive\n").len() - 2
+        };
+        assert_eq!(2, smart("duct"));
+        assert_eq!(1, smart("Duct"));
+
+        let searcher = SearcherBuilder::new()
+            .pattern("b.g")
+            .pattern("^To")
+            .kind(MatcherKind::Regex)
+            .bounds(Bounds::Word)
+            .invert(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            vec!["begin poem.txt", "1:frog", "finish"],
+            collect(&searcher, "frog\nbog\nTo\n")
+        );
+
+        assert!(SearcherBuilder::new()
+            .pattern("a(b")
+            .kind(MatcherKind::Regex)
+            .build()
+            .is_err());
+        let fuzzy = SearcherBuilder::new().pattern("a").pattern("b").fuzzy(1);
+        assert!(matches!(fuzzy.build(), Err(MinigrepError::Args(_))));
+    }
+
+    #[test]
+    fn multiline_matches_take_every_line_they_touch() {
+        let poem = include_str!("../poem.txt");
+        let searcher = SearcherBuilder::new()
+            .pattern(r"tell!\n")
+            .multiline(true)
+            .context(0, 1)
+            .build()
+            .unwrap();
+        // the newline at the end of the match doesn't pull in the next line, that one's context
+        assert_eq!(
+            vec![
+                "begin poem.txt",
+                "3:Then there's a pair of us - don't tell!",
+                "4-They'd banish us, you know.",
+                "finish",
+            ],
+            collect(&searcher, poem)
+        );
+
+        // matches that share a line go out together
+        let searcher = SearcherBuilder::new()
+            .pattern(r"frog\nTo.*\nTo")
+            .pattern(r"an admiring")
+            .kind(MatcherKind::Regex)
+            .multiline(true)
+            .build()
+            .unwrap();
+        assert_eq!(
+            vec![
+                "begin poem.txt",
+                "7:How public, like a frog\nTo tell your name the livelong day\nTo an admiring bog!",
+                "finish",
+            ],
+            collect(&searcher, poem)
+        );
+
+        let searcher = SearcherBuilder::new()
+            .pattern(r"day\nTo")
+            .multiline(true)
+            .invert(true)
+            .build()
+            .unwrap();
+        let inverted = collect(&searcher, "I\nday\nTo\nbog\n");
+        assert_eq!(vec!["begin poem.txt", "1:I", "4:bog", "finish"], inverted);
+    }
+}
@@ -0,0 +1,248 @@
+// walks a directory tree and hands back every regular file in it
+// directories are read in sorted order so the output is the same every time minigrep runs
+// hidden files and directories (a name starting with `.`) are skipped, and so is anything the ignore files
+// along the way rule out, see ignore.rs. --hidden and --no-ignore turn those off.
+
+use std::collections::HashSet;
+use std::error::Error;
+use std::fmt;
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::rc::Rc;
+
+use crate::ignore::Ignores;
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq)]
+pub struct WalkOptions {
+    // symlinks are skipped unless this is set, the root itself is always followed
+    pub follow_links: bool,
+    // how many directories deep to go, Some(1) only looks at the root's direct children
+    pub max_depth: Option<usize>,
+    // --hidden, also walk into files and directories whose names start with `.`
+    pub hidden: bool,
+    // --no-ignore, don't look at .gitignore, .ignore or .git/info/exclude
+    pub no_ignore: bool,
+}
+
+// an error that happened somewhere inside the tree, the walk keeps going after one of these
+#[derive(Debug)]
+pub struct WalkError {
+    pub path: PathBuf,
+    pub error: io::Error,
+}
+
+impl fmt::Display for WalkError {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        write!(f, "{}: {}", self.path.display(), self.error)
+    }
+}
+
+impl Error for WalkError {}
+
+pub struct Walk {
+    // paths still to visit with their depth and the ignore rules of the directory they're in, the next one
+    // to visit is at the end
+    stack: Vec<(PathBuf, usize, Option<Rc<Ignores>>)>,
+    options: WalkOptions,
+    // directories we've already been in, so a symlink pointing back up the tree can't loop forever
+    visited: HashSet<PathBuf>,
+}
+
+impl Walk {
+    pub fn new(root: impl AsRef<Path>, options: WalkOptions) -> Walk {
+        Walk {
+            stack: vec![(root.as_ref().to_path_buf(), 0, None)],
+            options,
+            visited: HashSet::new(),
+        }
+    }
+
+    fn push_dir(
+        &mut self,
+        dir: &Path,
+        depth: usize,
+        ignores: Option<Rc<Ignores>>,
+    ) -> Result<(), WalkError> {
+        let error = |error| WalkError {
+            path: dir.to_path_buf(),
+            error,
+        };
+        if self.options.follow_links {
+            let canonical = fs::canonicalize(dir).map_err(error)?;
+            if !self.visited.insert(canonical) {
+                return Ok(());
+            }
+        }
+
+        let mut children = Vec::new();
+        for entry in fs::read_dir(dir).map_err(error)? {
+            children.push(entry.map_err(error)?.path());
+        }
+        children.sort();
+        // the children are checked against the rules of the directory they're in
+        let ignores = match ignores {
+            _ if self.options.no_ignore => None,
+            Some(ignores) => Some(ignores.child(dir)),
+            None => Some(Ignores::root(dir)),
+        };
+        // reversed so the first child is popped first
+        for child in children.into_iter().rev() {
+            self.stack.push((child, depth + 1, ignores.clone()));
+        }
+        Ok(())
+    }
+}
+
+impl Iterator for Walk {
+    type Item = Result<PathBuf, WalkError>;
+
+    fn next(&mut self) -> Option<Self::Item> {
+        while let Some((path, depth, ignores)) = self.stack.pop() {
+            // the root is always walked, even when it's hidden itself
+            let hidden = path
+                .file_name()
+                .is_some_and(|name| name.as_encoded_bytes().starts_with(b"."));
+            if depth > 0 && hidden && !self.options.hidden {
+                continue;
+            }
+            let error = |error| WalkError {
+                path: path.clone(),
+                error,
+            };
+            let link = match fs::symlink_metadata(&path) {
+                Ok(metadata) => metadata,
+                Err(e) => return Some(Err(error(e))),
+            };
+            let metadata = if link.file_type().is_symlink() {
+                if depth > 0 && !self.options.follow_links {
+                    continue;
+                }
+                match fs::metadata(&path) {
+                    Ok(metadata) => metadata,
+                    Err(e) => return Some(Err(error(e))),
+                }
+            } else {
+                link
+            };
+
+            if ignores
+                .as_ref()
+                .is_some_and(|ignores| ignores.is_ignored(&path, metadata.is_dir()))
+            {
+                continue;
+            }
+
+            if metadata.is_dir() {
+                if self.options.max_depth.is_some_and(|max| depth >= max) {
+                    continue;
+                }
+                if let Err(e) = self.push_dir(&path, depth, ignores) {
+                    return Some(Err(e));
+                }
+            } else if metadata.is_file() {
+                return Some(Ok(path));
+            }
+            // sockets, fifos and devices aren't worth searching
+        }
+        None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::env;
+
+    // builds a small tree under the system temp dir that each test gets to itself
+    fn tree(name: &str) -> PathBuf {
+        let root = env::temp_dir().join(format!("minigrep-walk-{name}-{}", std::process::id()));
+        let _ = fs::remove_dir_all(&root);
+        fs::create_dir_all(root.join("b/deeper")).unwrap();
+        fs::write(root.join("a.txt"), "a").unwrap();
+        fs::write(root.join("b/c.txt"), "c").unwrap();
+        fs::write(root.join("b/deeper/d.txt"), "d").unwrap();
+        root
+    }
+
+    fn walk(root: &Path, options: WalkOptions) -> Vec<String> {
+        Walk::new(root, options)
+            .map(|path| {
+                let path = path.unwrap();
+                let relative = path.strip_prefix(root).unwrap();
+                relative.to_string_lossy().replace('\\', "/")
+            })
+            .collect()
+    }
+
+    #[test]
+    fn walks_in_sorted_order() {
+        let root = tree("sorted");
+        assert_eq!(
+            vec!["a.txt", "b/c.txt", "b/deeper/d.txt"],
+            walk(&root, WalkOptions::default())
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn max_depth_limits_the_walk() {
+        let root = tree("depth");
+        let options = WalkOptions {
+            max_depth: Some(2),
+            ..WalkOptions::default()
+        };
+        assert_eq!(vec!["a.txt", "b/c.txt"], walk(&root, options));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[test]
+    fn skips_hidden_and_ignored_files() {
+        let root = tree("ignore");
+        fs::create_dir_all(root.join(".git/info")).unwrap();
+        fs::write(root.join(".git/info/exclude"), "a.txt\n").unwrap();
+        fs::write(root.join(".gitignore"), "deeper/\n*.log\n").unwrap();
+        fs::write(root.join("b/.ignore"), "!keep.log\n").unwrap();
+        fs::write(root.join("b/keep.log"), "k").unwrap();
+        fs::write(root.join("b/drop.log"), "x").unwrap();
+        fs::write(root.join(".hidden.txt"), "h").unwrap();
+
+        assert_eq!(
+            vec!["b/c.txt", "b/keep.log"],
+            walk(&root, WalkOptions::default())
+        );
+        let options = WalkOptions {
+            hidden: true,
+            no_ignore: true,
+            ..WalkOptions::default()
+        };
+        let everything = walk(&root, options);
+        assert!(everything.contains(&String::from(".hidden.txt")));
+        assert!(everything.contains(&String::from("b/deeper/d.txt")));
+        assert!(everything.contains(&String::from(".git/info/exclude")));
+        fs::remove_dir_all(root).unwrap();
+    }
+
+    #[cfg(unix)]
+    #[test]
+    fn symlinks_are_optional_and_cannot_loop() {
+        let root = tree("links");
+        std::os::unix::fs::symlink(&root, root.join("b/loop")).unwrap();
+        std::os::unix::fs::symlink(root.join("a.txt"), root.join("z.txt")).unwrap();
+
+        assert_eq!(
+            vec!["a.txt", "b/c.txt", "b/deeper/d.txt"],
+            walk(&root, WalkOptions::default())
+        );
+
+        let options = WalkOptions {
+            follow_links: true,
+            ..WalkOptions::default()
+        };
+        assert_eq!(
+            vec!["a.txt", "b/c.txt", "b/deeper/d.txt", "z.txt"],
+            walk(&root, options)
+        );
+        fs::remove_dir_all(root).unwrap();
+    }
+}
@@ -0,0 +1,304 @@
+// runs the real binary, for the things only main can get right like exit codes
+use std::process::{Command, Output};
+
+fn minigrep(args: &[&str]) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(args)
+        .env_remove("IGNORE_CASE")
+        .output()
+        .expect("minigrep should run")
+}
+
+#[test]
+fn exit_codes_match_grep() {
+    let found = minigrep(&["frog", "poem.txt"]);
+    assert_eq!(Some(0), found.status.code());
+    assert_eq!(
+        "How public, like a frog\n",
+        String::from_utf8_lossy(&found.stdout)
+    );
+
+    let missing = minigrep(&["toad", "poem.txt"]);
+    assert_eq!(Some(1), missing.status.code());
+    assert!(missing.stdout.is_empty());
+
+    let no_file = minigrep(&["frog", "no-such-poem.txt"]);
+    assert_eq!(Some(2), no_file.status.code());
+    assert!(String::from_utf8_lossy(&no_file.stderr).contains("no-such-poem.txt"));
+
+    let bad_args = minigrep(&["--frog", "poem.txt"]);
+    assert_eq!(Some(2), bad_args.status.code());
+
+    let bad_pattern = minigrep(&["--regex", "fr(og", "poem.txt"]);
+    assert_eq!(Some(2), bad_pattern.status.code());
+}
+
+#[test]
+fn reads_standard_input() {
+    use std::io::Write;
+    use std::process::Stdio;
+
+    for args in [&["-n", "duct"][..], &["-n", "duct", "-"][..]] {
+        let mut child = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+            .args(args)
+            .stdin(Stdio::piped())
+            .stdout(Stdio::piped())
+            .spawn()
+            .unwrap();
+        child
+            .stdin
+            .take()
+            .unwrap()
+            .write_all(b"Rust:\nsafe, fast, productive.\nDuct tape.\n")
+            .unwrap();
+        let output = child.wait_with_output().unwrap();
+        assert_eq!(Some(0), output.status.code());
+        assert_eq!(
+            "2:safe, fast, productive.\n",
+            String::from_utf8_lossy(&output.stdout)
+        );
+    }
+}
+
+#[test]
+fn colors_only_when_asked() {
+    let color = |args: &[&str], env: &[(&str, &str)]| {
+        let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+            .args(args)
+            .env_remove("IGNORE_CASE")
+            .env_remove("NO_COLOR")
+            .env_remove("MINIGREP_COLORS")
+            .envs(env.iter().copied())
+            .output()
+            .unwrap();
+        String::from_utf8_lossy(&output.stdout).into_owned()
+    };
+
+    // the tests' stdout is a pipe, not a terminal
+    assert_eq!(
+        "How public, like a frog\n",
+        color(&["frog", "poem.txt"], &[])
+    );
+    assert_eq!(
+        "How public, like a \x1b[1;31mfrog\x1b[0m\n",
+        color(
+            &["--color=always", "frog", "poem.txt"],
+            &[("NO_COLOR", "1")]
+        )
+    );
+    assert_eq!(
+        "How public, like a \x1b[4mfrog\x1b[0m\n",
+        color(
+            &["--color", "always", "frog", "poem.txt"],
+            &[("MINIGREP_COLORS", "mt=4")]
+        )
+    );
+    assert_eq!(
+        "How public, like a frog\n",
+        color(
+            &["--color=never", "frog", "poem.txt"],
+            &[("MINIGREP_COLORS", "nonsense")]
+        )
+    );
+
+    // a bad color is a warning, the search still happens with the default colors
+    let output = Command::new(env!("CARGO_BIN_EXE_minigrep"))
+        .args(["--color=always", "frog", "poem.txt"])
+        .env("MINIGREP_COLORS", "mt=red")
+        .output()
+        .unwrap();
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        "How public, like a \x1b[1;31mfrog\x1b[0m\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    assert_eq!(
+        "minigrep: invalid color 'red' for 'mt' in MINIGREP_COLORS, using the default colors\n",
+        String::from_utf8_lossy(&output.stderr)
+    );
+}
+
+#[test]
+fn json_lines() {
+    let output = minigrep(&["--json", "frog", "poem.txt"]);
+    assert_eq!(Some(0), output.status.code());
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    let types: Vec<&str> = stdout
+        .lines()
+        .map(|line| line.split('"').nth(3).unwrap())
+        .collect();
+    assert_eq!(vec!["begin-file", "match", "end-file", "summary"], types);
+    assert!(stdout.contains(r#""line_number":7,"byte_offset":142,"#));
+    assert!(stdout.ends_with(
+        "{\"type\":\"summary\",\"data\":{\"files_searched\":1,\"files_matched\":1,\"errors\":0}}\n"
+    ));
+}
+
+#[test]
+fn replace_prints_and_dry_run_diffs() {
+    let output = minigrep(&["--regex", "--replace", "$2 $1", r"(\w+) (frog)", "poem.txt"]);
+    assert_eq!(
+        "How public, like frog a\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let output = minigrep(&["--replace", "toad", "--dry-run", "frog", "poem.txt"]);
+    assert_eq!(Some(0), output.status.code());
+    assert!(String::from_utf8_lossy(&output.stdout).contains(
+        "@@ -4,6 +4,6 @@\n They'd banish us, you know.\n \n How dreary to be somebody!\n-How public, like a frog\n+How public, like a toad\n"
+    ));
+
+    let output = minigrep(&["--in-place", "frog", "poem.txt"]);
+    assert_eq!(Some(2), output.status.code());
+}
+
+#[test]
+fn fuzzy_prints_the_distance() {
+    let output = minigrep(&["--fuzzy", "1", "-n", "somebdy", "poem.txt"]);
+    assert_eq!(
+        "6:~1:How dreary to be somebody!\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    let output = minigrep(&["--fuzzy", "1", "-o", "frg", "poem.txt"]);
+    assert_eq!("~1:frog\n", String::from_utf8_lossy(&output.stdout));
+
+    let output = minigrep(&["--fuzzy", "1", "--json", "somebdy", "poem.txt"]);
+    assert!(String::from_utf8_lossy(&output.stdout)
+        .contains(r#""pattern":{"text":"somebdy"},"distance":1}"#));
+
+    let output = minigrep(&["--fuzzy", "1", "--regex", "somebdy", "poem.txt"]);
+    assert_eq!(Some(2), output.status.code());
+}
+
+#[test]
+fn search_zip_reads_gzip() {
+    let plain = minigrep(&["-n", "-C", "1", "to", "poem.txt"]);
+    let zipped = minigrep(&["-z", "-n", "-C", "1", "to", "poem.txt.gz"]);
+    assert_eq!(Some(0), zipped.status.code());
+    assert_eq!(plain.stdout, zipped.stdout);
+
+    // without -z it's a binary file
+    let raw = minigrep(&["-c", "frog", "poem.txt.gz"]);
+    assert_eq!("0\n", String::from_utf8_lossy(&raw.stdout));
+
+    // a broken file is reported by name, the rest are still searched
+    let broken = std::env::temp_dir().join(format!("minigrep-broken-{}.gz", std::process::id()));
+    let gz = std::fs::read("poem.txt.gz").unwrap();
+    std::fs::write(&broken, &gz[..gz.len() / 2]).unwrap();
+    let output = minigrep(&["-z", "frog", broken.to_str().unwrap(), "poem.txt.gz"]);
+    std::fs::remove_file(&broken).unwrap();
+    assert_eq!(Some(2), output.status.code());
+    assert_eq!(
+        "poem.txt.gz:How public, like a frog\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains(&format!(
+        "{}: gzip data ends unexpectedly",
+        broken.display()
+    )));
+}
+
+#[test]
+fn query_finds_what_a_search_finds() {
+    let dir = std::env::temp_dir().join(format!("minigrep-query-{}", std::process::id()));
+    let _ = std::fs::remove_dir_all(&dir);
+    std::fs::create_dir_all(dir.join("docs")).unwrap();
+    std::fs::copy("poem.txt", dir.join("poem.txt")).unwrap();
+    std::fs::copy("poem.txt.gz", dir.join("docs/poem.txt.gz")).unwrap();
+    std::fs::write(dir.join("docs/rust.md"), "Rust:\nsafe, fast, productive.\n").unwrap();
+    let dir_arg = dir.to_str().unwrap();
+
+    let missing = minigrep(&["query", "frog", dir_arg]);
+    assert_eq!(Some(2), missing.status.code());
+    assert!(String::from_utf8_lossy(&missing.stderr).contains("minigrep index"));
+
+    let indexed = minigrep(&["index", dir_arg]);
+    assert_eq!(Some(0), indexed.status.code());
+    assert!(String::from_utf8_lossy(&indexed.stdout).ends_with(": 3 files, 3 read, 0 removed\n"));
+
+    for args in [
+        &["-n", "frog"][..],
+        &["-z", "-i", "PUBLIC"],
+        &["-c", "safe"],
+        &["toad"],
+    ] {
+        let search = minigrep(&[args, &[dir_arg]].concat());
+        let query = minigrep(&[&["query"], args, &[dir_arg]].concat());
+        assert_eq!(search.stdout, query.stdout, "{args:?}");
+        assert_eq!(search.status.code(), query.status.code(), "{args:?}");
+    }
+
+    // a file the index hasn't seen is still searched, with a warning
+    std::fs::write(dir.join("docs/toad.md"), "A toad.\n").unwrap();
+    let query = minigrep(&["query", "toad", dir_arg]);
+    assert_eq!(Some(0), query.status.code());
+    assert!(String::from_utf8_lossy(&query.stdout).ends_with("toad.md:A toad.\n"));
+    let stderr = String::from_utf8_lossy(&query.stderr);
+    assert!(stderr.contains(": the index doesn't know about 1 of the files, run 'minigrep index"));
+    std::fs::remove_file(dir.join("docs/toad.md")).unwrap();
+
+    let again = minigrep(&["index", dir_arg]);
+    assert!(String::from_utf8_lossy(&again.stdout).ends_with(": 3 files, 0 read, 0 removed\n"));
+    std::fs::remove_dir_all(&dir).unwrap();
+}
+
+#[test]
+fn multiline_prints_every_line_a_match_touches() {
+    let output = minigrep(&["-U", "-n", "--regex", r"frog\nTo tell", "poem.txt"]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        "7-8:How public, like a frog\nTo tell your name the livelong day\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // without -U a pattern never gets to see a newline
+    let output = minigrep(&["-n", r"frog\nTo tell", "poem.txt"]);
+    assert_eq!(Some(1), output.status.code());
+
+    let output = minigrep(&["-U", "--replace=x", "--in-place", "us", "poem.txt"]);
+    assert_eq!(Some(2), output.status.code());
+}
+
+#[test]
+fn query_expressions_combine_terms() {
+    let output = minigrep(&[
+        "-n",
+        "--query",
+        "i:TO AND (frog OR bog) AND NOT \"How public\"",
+        "poem.txt",
+    ]);
+    assert_eq!(Some(0), output.status.code());
+    assert_eq!(
+        "9:To an admiring bog!\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+
+    // a line picked for what's not in it is selected, with nothing in it that matched
+    let output = minigrep(&["-n", "--query", "NOT y", "poem.txt"]);
+    assert_eq!(
+        "3:Then there's a pair of us - don't tell!\n5:\n7:How public, like a frog\n9:To an admiring bog!\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    let output = minigrep(&["-o", "--query", "NOT y", "poem.txt"]);
+    assert_eq!(Some(0), output.status.code());
+    assert!(output.stdout.is_empty());
+    // only what was found gets replaced, the rest of the lines come out as they are
+    let output = minigrep(&["--replace=X", "--query", "frog OR NOT y", "poem.txt"]);
+    assert_eq!(
+        "Then there's a pair of us - don't tell!\n\nHow public, like a X\nTo an admiring bog!\n",
+        String::from_utf8_lossy(&output.stdout)
+    );
+    let output = minigrep(&["--json", "--query", "NOT y", "poem.txt"]);
+    let stdout = String::from_utf8_lossy(&output.stdout);
+    assert!(
+        stdout.contains(r#""line_number":5,"byte_offset":114,"line":{"text":""},"submatches":[]}"#)
+    );
+    assert!(stdout.contains(r#""matched_lines":4,"matches":0}"#));
+
+    let output = minigrep(&["--query", "frog OR", "poem.txt"]);
+    assert_eq!(Some(2), output.status.code());
+    let stderr = String::from_utf8_lossy(&output.stderr);
+    assert!(stderr.contains("error: expected a term, the query ends here at column 8"));
+}